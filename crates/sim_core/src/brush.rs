@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+use crate::stamp::StampTransform;
+
+/// Passes of a flood fill per frame, each spreading it by at least a cell.
+pub const FLOOD_PASSES_PER_FRAME: u32 = 32;
+/// Tag of the readback of the [`FloodFill::id`] and the cells its passes reached on that frame,
+/// as two `u32`s. The simulations record it every frame of a fill.
+pub const FLOOD_READBACK: &str = "flood_fill";
+
+/// Shape painted by the brush. The values match the `TOOL_*` constants of the shaders.
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BrushTool {
+    /// Paints under the mouse while it is held.
+    #[default]
+    Freehand = 0,
+    /// Paints some of the cells under the mouse while it is held.
+    Spray = 1,
+    /// Paints a line from where the mouse is pressed to where it is released.
+    Line = 2,
+    /// Paints a rectangle with corners where the mouse is pressed and released.
+    Rectangle = 3,
+    /// Paints an ellipse within the rectangle the mouse is dragged over.
+    Ellipse = 4,
+    /// Paints the connected cells alike to the clicked one.
+    FloodFill = 5,
+    /// Selects the rectangle the mouse is dragged over, to be saved as a stamp.
+    Select = 6,
+    /// Places the selected stamp centered on the mouse, again each time the mouse is held and
+    /// moved past it.
+    Stamp = 7,
+}
+
+impl BrushTool {
+    pub const ALL: [BrushTool; 8] = [
+        BrushTool::Freehand,
+        BrushTool::Spray,
+        BrushTool::Line,
+        BrushTool::Rectangle,
+        BrushTool::Ellipse,
+        BrushTool::FloodFill,
+        BrushTool::Select,
+        BrushTool::Stamp,
+    ];
+
+    /// Painted once the mouse is released, from where it was pressed.
+    pub fn is_shape(&self) -> bool {
+        matches!(
+            self,
+            BrushTool::Line | BrushTool::Rectangle | BrushTool::Ellipse
+        )
+    }
+
+    /// Follows the mouse from where it is pressed to where it is released.
+    pub fn is_dragged(&self) -> bool {
+        self.is_shape() || *self == BrushTool::Select
+    }
+}
+
+/// A flood fill spreading from the cell at `seed`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloodFill {
+    pub seed: Vec2,
+    /// Frames the fill has been spreading for, the fill starts on frame `0`.
+    pub frame: u32,
+    /// The [`Brush::seed`] of the first frame, kept by the GPU to tell the readbacks of the fills
+    /// apart.
+    pub id: u32,
+}
+
+/// How the mouse paints, shared by the simulations. [`Brush::update`] follows the mouse each
+/// frame, and the simulations paint [`Brush::segment`] on the frames the brush
+/// [`Brush::is_painting`].
+#[derive(Debug, Clone)]
+pub struct Brush {
+    pub tool: BrushTool,
+    /// Rectangles and ellipses are filled, otherwise outlined as thick as the brush size.
+    pub filled: bool,
+    /// Chance of each cell under the spray to be painted per frame.
+    pub spray_density: f32,
+    /// How the stamp tool turns the stamp.
+    pub stamp_transform: StampTransform,
+    /// Size of the stamp placed by the stamp tool once turned, if one is loaded.
+    pub stamp_size: Option<Vec2>,
+    /// Where the mouse was pressed, while dragging a shape.
+    drag_start: Option<Vec2>,
+    segment: (Vec2, Vec2),
+    is_painting: bool,
+    was_drawing: bool,
+    flood_fill: Option<FloodFill>,
+    /// Corners of the selected rectangle, following the mouse while it is dragged.
+    selection: Option<(Vec2, Vec2)>,
+    /// Where the stamp was last placed while the mouse is held.
+    last_stamp: Option<Vec2>,
+    /// Changes every frame, so the spray paints other cells.
+    seed: u32,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            tool: BrushTool::Freehand,
+            filled: true,
+            spray_density: 0.1,
+            stamp_transform: StampTransform::default(),
+            stamp_size: None,
+            drag_start: None,
+            segment: (Vec2::ZERO, Vec2::ZERO),
+            is_painting: false,
+            was_drawing: false,
+            flood_fill: None,
+            selection: None,
+            last_stamp: None,
+            seed: 0,
+        }
+    }
+}
+
+impl Brush {
+    /// Whether the simulations paint this frame.
+    pub fn is_painting(&self) -> bool {
+        self.is_painting
+    }
+
+    /// Points painted between, the last two mouse positions for the freehand tools, the start and
+    /// end of the drag for shapes, and the clicked cell for flood fills.
+    pub fn segment(&self) -> (Vec2, Vec2) {
+        self.segment
+    }
+
+    /// Where the shape being dragged starts.
+    pub fn drag_start(&self) -> Option<Vec2> {
+        self.drag_start
+    }
+
+    /// The flood fill spreading this frame.
+    pub fn flood_fill(&self) -> Option<FloodFill> {
+        self.flood_fill
+    }
+
+    /// Corners of the cells selected with the select tool.
+    pub fn selection(&self) -> Option<(Vec2, Vec2)> {
+        self.selection
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Corners of the cells that may be painted this frame, before adding the brush size.
+    pub fn painted_bounds(&self, size: Vec2) -> (Vec2, Vec2) {
+        match (self.flood_fill, self.tool, self.stamp_size) {
+            (Some(_), ..) => (Vec2::ZERO, size),
+            (None, BrushTool::Stamp, Some(stamp_size)) => (
+                self.segment.0 - stamp_size / 2.0,
+                self.segment.0 + stamp_size / 2.0,
+            ),
+            _ => self.segment,
+        }
+    }
+
+    /// Stops the flood fill once the [`FLOOD_READBACK`] of one of its frames tells that its passes
+    /// reached no new cell.
+    pub fn finish_flood_fill(&mut self, readback: &[u8]) {
+        let Ok([id, reached]) = bytemuck::try_pod_read_unaligned::<[u32; 2]>(readback) else {
+            return;
+        };
+        if reached == 0 && self.flood_fill.is_some_and(|fill| fill.id == id) {
+            self.flood_fill = None;
+        }
+    }
+
+    /// Follows the mouse, held down while `is_drawing`. Positions are in cells of a simulation of
+    /// `size`.
+    pub fn update(&mut self, is_drawing: bool, mouse_pos: Vec2, prev_mouse_pos: Vec2, size: Vec2) {
+        let was_drawing = std::mem::replace(&mut self.was_drawing, is_drawing);
+        self.seed = self.seed.wrapping_add(1);
+        self.is_painting = false;
+
+        if !self.tool.is_dragged() {
+            self.drag_start = None;
+        }
+        if !is_drawing || self.tool != BrushTool::Stamp {
+            self.last_stamp = None;
+        }
+
+        // A fill runs until a frame of passes reaches no new cell, see `finish_flood_fill`. A fill
+        // spreads by at least a cell per pass, so even a path through every cell is done after
+        // this many frames, in case the readbacks don't arrive
+        let flood_frames = (size.x * size.y) as u32 / FLOOD_PASSES_PER_FRAME + 1;
+        self.flood_fill = self
+            .flood_fill
+            .filter(|_| self.tool == BrushTool::FloodFill)
+            .map(|fill| FloodFill {
+                frame: fill.frame + 1,
+                ..fill
+            })
+            .filter(|fill| fill.frame < flood_frames);
+
+        match self.tool {
+            BrushTool::Freehand | BrushTool::Spray => {
+                self.is_painting = is_drawing;
+                self.segment = (mouse_pos, prev_mouse_pos);
+            }
+            BrushTool::Line | BrushTool::Rectangle | BrushTool::Ellipse => {
+                match (is_drawing, self.drag_start) {
+                    (true, None) => self.drag_start = Some(mouse_pos),
+                    (false, Some(start)) => {
+                        self.drag_start = None;
+                        self.is_painting = true;
+                        self.segment = (start, mouse_pos);
+                    }
+                    _ => {}
+                }
+            }
+            BrushTool::Select => {
+                if let Some(start) = self.drag_start {
+                    self.selection = Some((start, mouse_pos));
+                }
+                match (is_drawing, self.drag_start) {
+                    (true, None) => self.drag_start = Some(mouse_pos),
+                    (false, Some(_)) => self.drag_start = None,
+                    _ => {}
+                }
+            }
+            BrushTool::Stamp => {
+                // Placed next to the last one when held, so it also paints patterns
+                let is_past_last = |stamp_size: Vec2| {
+                    self.last_stamp.is_none_or(|last| {
+                        let distance = (mouse_pos - last).abs();
+                        distance.x >= stamp_size.x || distance.y >= stamp_size.y
+                    })
+                };
+                if let Some(stamp_size) = self.stamp_size {
+                    if is_drawing && is_past_last(stamp_size) {
+                        self.last_stamp = Some(mouse_pos);
+                        self.is_painting = true;
+                        self.segment = (mouse_pos, mouse_pos);
+                    }
+                }
+            }
+            BrushTool::FloodFill => {
+                if is_drawing && !was_drawing {
+                    self.flood_fill = Some(FloodFill {
+                        seed: mouse_pos,
+                        frame: 0,
+                        id: self.seed,
+                    });
+                }
+            }
+        }
+
+        if let Some(fill) = self.flood_fill {
+            self.is_painting = true;
+            self.segment = (fill.seed, fill.seed);
+        }
+    }
+}
+
+// ================================== UI ================================== //
+
+/// Tool picker, shared by the UIs of the simulations.
+pub fn brush_ui(ui: &mut egui::Ui, brush: &mut Brush) {
+    egui::ComboBox::from_label("Brush Tool")
+        .selected_text(format!("{:?}", brush.tool))
+        .show_ui(ui, |ui| {
+            for tool in BrushTool::ALL {
+                ui.selectable_value(&mut brush.tool, tool, format!("{:?}", tool));
+            }
+        });
+
+    match brush.tool {
+        BrushTool::Rectangle | BrushTool::Ellipse => {
+            ui.checkbox(&mut brush.filled, "Filled")
+                .on_hover_text("Otherwise outlined as thick as the brush size");
+        }
+        BrushTool::Spray => {
+            ui.add(egui::Slider::new(&mut brush.spray_density, 0.01..=1.0).text("Spray Density"));
+        }
+        _ => {}
+    }
+}
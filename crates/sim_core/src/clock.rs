@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use bevy::{
+    prelude::*,
+    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
+};
+use bevy_egui::egui;
+
+pub const INIT_TICKS_PER_SECOND: f32 = 60.0;
+/// Most ticks run in a single frame, so a slow frame doesn't make the following ones even slower.
+pub const MAX_TICKS_PER_FRAME: u32 = 8;
+
+pub struct SimClockPlugin;
+impl Plugin for SimClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimClock>()
+            .add_plugin(ExtractResourcePlugin::<SimClock>::default())
+            .add_system(advance_sim_clock.in_base_set(CoreSet::First));
+    }
+}
+
+/// Decides how many simulation steps run each frame, so the simulation speed doesn't depend on
+/// the frame rate.
+#[derive(Resource, ExtractResource, Debug, Clone)]
+pub struct SimClock {
+    /// Ticks per second at a speed of `1.0`.
+    pub ticks_per_second: f32,
+    /// Multiplies `ticks_per_second`.
+    pub speed: f32,
+    /// Simulation steps run by each tick.
+    pub substeps: u32,
+    pub is_paused: bool,
+    /// Steps run before this frame, which is also the index of the first step of this frame.
+    pub step: u32,
+    /// Steps to run this frame.
+    pub steps_this_frame: u32,
+    /// Time not yet turned into ticks, in seconds.
+    accumulated: f32,
+    step_requested: bool,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: INIT_TICKS_PER_SECOND,
+            speed: 1.0,
+            substeps: 1,
+            is_paused: false,
+            step: 0,
+            steps_this_frame: 0,
+            accumulated: 0.0,
+            step_requested: false,
+        }
+    }
+}
+
+impl SimClock {
+    /// Runs a single tick next frame while paused.
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+
+    /// Makes `step` the first step of this frame without running any, e.g. to resume from an
+    /// earlier state.
+    pub fn jump_to(&mut self, step: u32) {
+        self.step = step;
+        self.steps_this_frame = 0;
+        self.accumulated = 0.0;
+    }
+
+    /// Indices of the steps to run this frame.
+    pub fn steps(&self) -> Range<u32> {
+        self.step..self.next_step()
+    }
+
+    /// Index of the first step of the next frame.
+    pub fn next_step(&self) -> u32 {
+        self.step.wrapping_add(self.steps_this_frame)
+    }
+
+    /// Starts a new frame, `delta_seconds` after the previous one.
+    pub fn advance(&mut self, delta_seconds: f32) {
+        self.step = self.next_step();
+
+        let ticks = if self.is_paused {
+            self.accumulated = 0.0;
+            std::mem::take(&mut self.step_requested) as u32
+        } else {
+            self.step_requested = false;
+
+            let tick_length = 1.0 / (self.ticks_per_second * self.speed).max(f32::EPSILON);
+            self.accumulated += delta_seconds;
+            let ticks = (self.accumulated / tick_length) as u32;
+            if ticks > MAX_TICKS_PER_FRAME {
+                // Too far behind to catch up, drop the backlog
+                self.accumulated = 0.0;
+                MAX_TICKS_PER_FRAME
+            } else {
+                self.accumulated -= ticks as f32 * tick_length;
+                ticks
+            }
+        };
+
+        self.steps_this_frame = ticks * self.substeps;
+    }
+}
+
+pub fn advance_sim_clock(time: Res<Time>, mut clock: ResMut<SimClock>) {
+    clock.advance(time.delta_seconds());
+}
+
+/// Controls of the clock, shared by the UIs of the simulations.
+pub fn clock_ui(ui: &mut egui::Ui, clock: &mut SimClock) {
+    ui.horizontal(|ui| {
+        let label = if clock.is_paused { "Play" } else { "Pause" };
+        if ui.button(label).clicked() {
+            clock.is_paused = !clock.is_paused;
+        }
+        if ui
+            .add_enabled(clock.is_paused, egui::Button::new("Step"))
+            .clicked()
+        {
+            clock.request_step();
+        }
+    });
+
+    ui.add(egui::Slider::new(&mut clock.ticks_per_second, 1.0..=240.0).text("Ticks Per Second"));
+    ui.add(
+        egui::Slider::new(&mut clock.speed, 0.1..=10.0)
+            .logarithmic(true)
+            .text("Speed"),
+    );
+    ui.add(egui::Slider::new(&mut clock.substeps, 1..=16).text("Steps Per Tick"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f32 = 1.0 / INIT_TICKS_PER_SECOND;
+
+    #[test]
+    fn ticks_accumulate_across_frames() {
+        let mut clock = SimClock::default();
+        clock.advance(TICK * 0.6);
+        assert_eq!(clock.steps_this_frame, 0);
+        clock.advance(TICK * 0.6);
+        assert_eq!(clock.steps_this_frame, 1);
+        clock.advance(TICK * 2.0);
+        assert_eq!(clock.steps_this_frame, 2);
+        assert_eq!(clock.steps(), 1..3);
+    }
+
+    #[test]
+    fn slow_frames_are_capped_and_their_backlog_dropped() {
+        let mut clock = SimClock::default();
+        clock.advance(1.0);
+        assert_eq!(clock.steps_this_frame, MAX_TICKS_PER_FRAME);
+        clock.advance(0.0);
+        assert_eq!(clock.steps_this_frame, 0);
+    }
+
+    #[test]
+    fn each_tick_runs_the_substeps() {
+        let mut clock = SimClock {
+            substeps: 3,
+            speed: 2.0,
+            ..SimClock::default()
+        };
+        clock.advance(TICK);
+        assert_eq!(clock.steps(), 0..6);
+        clock.advance(TICK / 2.0);
+        assert_eq!(clock.steps(), 6..9);
+    }
+
+    #[test]
+    fn paused_clocks_only_run_requested_steps() {
+        let mut clock = SimClock {
+            is_paused: true,
+            substeps: 2,
+            ..SimClock::default()
+        };
+        clock.advance(1.0);
+        assert_eq!(clock.steps_this_frame, 0);
+
+        clock.request_step();
+        clock.advance(0.0);
+        assert_eq!(clock.steps(), 0..2);
+        clock.advance(0.0);
+        assert_eq!(clock.steps(), 2..2);
+    }
+}
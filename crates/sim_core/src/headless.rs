@@ -0,0 +1,180 @@
+//! Pieces of the headless runners, which advance a simulation without a window: on the GPU when
+//! there is an adapter, otherwise on the CPU stepper of the simulation.
+
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bevy::{prelude::*, render::RenderApp};
+
+use crate::clock::SimClock;
+use crate::snapshot::{LoadWorld, SaveWorld};
+
+/// Frames waited for the shaders and assets to load, and for the saved world to be read back.
+const MAX_WAIT_FRAMES: u32 = 10_000;
+
+pub const HEADLESS_USAGE: &str = "\
+Options:
+    --ticks <N>       ticks to run, defaults to 600
+    --world <FILE>    snapshot to start from, saved with the Save button
+    --out <FILE>      snapshot of the final world, defaults to out.snapshot
+    --stats <FILE>    writes statistics about the final world
+    --size <WxH>      size of the world when not starting from a snapshot
+    --seed <N>        seed of the random choices, sand only
+    --cpu             runs on the CPU even when there is a GPU";
+
+/// Options shared by the headless runners.
+#[derive(Debug, Clone)]
+pub struct HeadlessArgs {
+    pub ticks: u32,
+    pub world: Option<PathBuf>,
+    pub out: PathBuf,
+    pub stats: Option<PathBuf>,
+    pub size: Option<(u32, u32)>,
+    pub seed: Option<u32>,
+    pub force_cpu: bool,
+}
+
+impl Default for HeadlessArgs {
+    fn default() -> Self {
+        Self {
+            ticks: 600,
+            world: None,
+            out: PathBuf::from("out.snapshot"),
+            stats: None,
+            size: None,
+            seed: None,
+            force_cpu: false,
+        }
+    }
+}
+
+impl HeadlessArgs {
+    /// Parses the arguments, without the name of the program.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        fn value<T: std::str::FromStr>(
+            args: &mut impl Iterator<Item = String>,
+            name: &str,
+        ) -> Result<T, String> {
+            args.next()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| format!("{name} needs a valid value"))
+        }
+
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ticks" => parsed.ticks = value(&mut args, &arg)?,
+                "--world" => parsed.world = Some(value(&mut args, &arg)?),
+                "--out" => parsed.out = value(&mut args, &arg)?,
+                "--stats" => parsed.stats = Some(value(&mut args, &arg)?),
+                "--seed" => parsed.seed = Some(value(&mut args, &arg)?),
+                "--size" => {
+                    let size: String = value(&mut args, &arg)?;
+                    let parsed_size = size
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                    parsed.size = Some(
+                        parsed_size.ok_or_else(|| "--size must look like 512x512".to_string())?,
+                    );
+                }
+                "--cpu" => parsed.force_cpu = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Whether to run on the GPU, falling back to the CPU when no adapter is found.
+    pub fn use_gpu(&self) -> bool {
+        !self.force_cpu && has_gpu_adapter()
+    }
+}
+
+/// Whether wgpu finds an adapter, without creating a device.
+pub fn has_gpu_adapter() -> bool {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).is_some()
+}
+
+/// Writes `name: value` lines.
+pub fn write_stats(path: &Path, stats: &[(String, String)]) -> std::io::Result<()> {
+    let lines = stats
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\n"))
+        .collect::<String>();
+    std::fs::write(path, lines)
+}
+
+// ================================== GPU ================================== //
+
+/// Set by the render world once the passes of the simulation run, so the headless runners know
+/// when the steps they request are no longer dropped.
+#[derive(Resource, Clone, Default)]
+pub struct SimReady(Arc<AtomicBool>);
+
+impl SimReady {
+    pub fn mark(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Shares a [`SimReady`] between the main and the render world.
+pub struct SimReadyPlugin;
+impl Plugin for SimReadyPlugin {
+    fn build(&self, app: &mut App) {
+        let ready = SimReady::default();
+        app.insert_resource(ready.clone());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.insert_resource(ready);
+        }
+    }
+}
+
+/// Runs `args.ticks` ticks of a windowless app one frame at a time, then saves the world to
+/// `args.out`. The app needs the [`SimReadyPlugin`] and the snapshot plugins.
+pub fn run_on_gpu(app: &mut App, args: &HeadlessArgs) -> Result<(), String> {
+    let wait_for = |app: &mut App, done: &dyn Fn(&mut App) -> bool, what: &str| {
+        for _ in 0..MAX_WAIT_FRAMES {
+            if done(app) {
+                return Ok(());
+            }
+            app.update();
+        }
+        Err(format!("gave up waiting for {what}"))
+    };
+
+    // Steps only run when requested from now on
+    app.world.resource_mut::<SimClock>().is_paused = true;
+    wait_for(
+        app,
+        &|app| app.world.resource::<SimReady>().is_ready(),
+        "the simulation to load",
+    )?;
+
+    if let Some(world) = args.world.clone() {
+        app.world.send_event(LoadWorld { path: world });
+        app.update();
+    }
+
+    for _ in 0..args.ticks {
+        app.world.resource_mut::<SimClock>().request_step();
+        app.update();
+    }
+
+    // The file only appears once the readback of the world is done
+    if args.out.exists() {
+        std::fs::remove_file(&args.out).map_err(|error| error.to_string())?;
+    }
+    app.world.send_event(SaveWorld {
+        path: args.out.clone(),
+    });
+    wait_for(app, &|_| args.out.exists(), "the world to be saved")
+}
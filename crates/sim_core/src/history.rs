@@ -0,0 +1,370 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder},
+        renderer::RenderDevice,
+        RenderApp,
+    },
+};
+use bevy_egui::egui;
+
+use crate::clock::{advance_sim_clock, SimClock};
+
+/// Steps between two recorded states.
+pub const INIT_HISTORY_INTERVAL: u32 = 30;
+/// GPU memory the recorded states may take, in megabytes.
+pub const INIT_HISTORY_BUDGET_MB: u32 = 256;
+/// Most states kept however small they are.
+pub const MAX_HISTORY_CAPACITY: usize = 64;
+
+pub struct SimHistoryPlugin;
+impl Plugin for SimHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimHistory>()
+            .add_plugin(ExtractResourcePlugin::<SimHistory>::default())
+            .add_system(
+                record_history
+                    .after(advance_sim_clock)
+                    .in_base_set(CoreSet::First),
+            );
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<GpuHistory>();
+        }
+    }
+}
+
+/// A recorded state of the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryFrame {
+    /// Step the state is the input of.
+    pub step: u32,
+    /// Slot of the [`GpuHistory`] holding the state.
+    pub slot: usize,
+}
+
+/// What the render world does with the [`GpuHistory`] this frame, before running any pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCommand {
+    /// Copies the input of the first step of the frame into the slot.
+    Capture { slot: usize },
+    /// Copies the slot back into the input of the first step of the frame.
+    Restore { slot: usize },
+}
+
+/// Ring buffer of past states of the simulation, recorded every `interval` steps. The states
+/// themselves stay on the GPU in the [`GpuHistory`], only their steps are known here.
+#[derive(Resource, ExtractResource, Debug, Clone)]
+pub struct SimHistory {
+    /// Steps between two recorded states.
+    pub interval: u32,
+    /// GPU memory the recorded states may take, in megabytes. The oldest states are dropped to
+    /// stay within it.
+    pub budget_mb: u32,
+    /// Bytes of a recorded state, set by the simulations.
+    state_size: u64,
+    /// Recorded states, oldest first.
+    frames: VecDeque<HistoryFrame>,
+    /// Index in `frames` of the state being inspected, `None` while running.
+    scrub: Option<usize>,
+    command: Option<HistoryCommand>,
+}
+
+impl Default for SimHistory {
+    fn default() -> Self {
+        Self {
+            interval: INIT_HISTORY_INTERVAL,
+            budget_mb: INIT_HISTORY_BUDGET_MB,
+            state_size: 0,
+            frames: VecDeque::new(),
+            scrub: None,
+            command: None,
+        }
+    }
+}
+
+impl SimHistory {
+    pub fn frames(&self) -> &VecDeque<HistoryFrame> {
+        &self.frames
+    }
+
+    pub fn command(&self) -> Option<HistoryCommand> {
+        self.command
+    }
+
+    /// Most states kept within the budget, at least one.
+    pub fn capacity(&self) -> usize {
+        let budget = self.budget_mb as u64 * 1024 * 1024;
+        (budget / self.state_size.max(1)).clamp(1, MAX_HISTORY_CAPACITY as u64) as usize
+    }
+
+    /// Sizes the capacity for states of `bytes`, set along with the buffers of the simulation.
+    pub fn set_state_size(&mut self, bytes: u64) {
+        self.state_size = bytes;
+    }
+
+    /// The state being inspected, `None` while running.
+    pub fn scrubbed_frame(&self) -> Option<HistoryFrame> {
+        self.scrub.map(|index| self.frames[index])
+    }
+
+    /// Forgets every state, e.g. when they no longer fit the buffers of the simulation.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.scrub = None;
+        self.command = None;
+    }
+
+    /// Pauses the simulation and brings back the state at `index` in [`SimHistory::frames`].
+    /// Resuming continues from it and drops the states recorded after it.
+    pub fn scrub_to(&mut self, index: usize, clock: &mut SimClock) {
+        let Some(frame) = self.frames.get(index).copied() else {
+            return;
+        };
+
+        self.scrub = Some(index);
+        self.command = Some(HistoryCommand::Restore { slot: frame.slot });
+        clock.is_paused = true;
+        clock.jump_to(frame.step);
+    }
+
+    /// Resuming from an earlier state, the ones after it are replaced by the new ones.
+    fn resume_from(&mut self, index: usize) {
+        self.frames.truncate(index + 1);
+        self.scrub = None;
+    }
+
+    /// Drops the oldest states past the capacity, after the budget shrank.
+    fn shrink_to_capacity(&mut self) {
+        let excess = self.frames.len().saturating_sub(self.capacity());
+        if excess > 0 {
+            self.frames.drain(..excess);
+            self.scrub = self.scrub.and_then(|index| index.checked_sub(excess));
+        }
+    }
+
+    /// Records a state at `step`, returning the slot it goes into.
+    fn push(&mut self, step: u32) -> usize {
+        let mut freed = None;
+        while self.frames.len() >= self.capacity() {
+            freed = self.frames.pop_front().map(|frame| frame.slot);
+        }
+        let slot = freed.unwrap_or_else(|| {
+            (0..)
+                .find(|slot| self.frames.iter().all(|frame| frame.slot != *slot))
+                .unwrap_or_default()
+        });
+
+        self.frames.push_back(HistoryFrame { step, slot });
+        slot
+    }
+}
+
+pub fn record_history(clock: Res<SimClock>, mut history: ResMut<SimHistory>) {
+    // Commands only last a frame, the render world keeps the last extracted history
+    if history.command.is_some() {
+        history.command = None;
+    }
+    if history.frames.len() > history.capacity() {
+        history.shrink_to_capacity();
+    }
+
+    if let Some(index) = history.scrub {
+        if clock.is_paused {
+            return;
+        }
+
+        history.resume_from(index);
+    }
+
+    let is_due = history
+        .frames
+        .back()
+        .is_none_or(|frame| clock.step.wrapping_sub(frame.step) >= history.interval.max(1));
+    if is_due {
+        let slot = history.push(clock.step);
+        history.command = Some(HistoryCommand::Capture { slot });
+    }
+}
+
+// ================================== GPU ================================== //
+
+/// Copies of the simulation buffers, a set of buffers per slot of the [`SimHistory`].
+#[derive(Resource, Default)]
+pub struct GpuHistory {
+    slots: Vec<Vec<Buffer>>,
+}
+
+impl GpuHistory {
+    /// Makes sure the slot being captured this frame has a buffer of each of `sizes`, in bytes,
+    /// and frees the slots no state is recorded in. Run by the simulations while preparing their
+    /// buffers.
+    pub fn prepare(&mut self, device: &RenderDevice, history: &SimHistory, sizes: &[u64]) {
+        // Left over once the states are dropped, e.g. when the budget shrinks
+        for (slot, buffers) in self.slots.iter_mut().enumerate() {
+            if !buffers.is_empty() && history.frames.iter().all(|frame| frame.slot != slot) {
+                buffers.clear();
+            }
+        }
+        while self.slots.last().is_some_and(Vec::is_empty) {
+            self.slots.pop();
+        }
+
+        let Some(HistoryCommand::Capture { slot }) = history.command else {
+            return;
+        };
+
+        if self.slots.len() <= slot {
+            self.slots.resize_with(slot + 1, Vec::new);
+        }
+
+        let buffers = &mut self.slots[slot];
+        let fits = buffers.len() == sizes.len()
+            && buffers
+                .iter()
+                .zip(sizes)
+                .all(|(buffer, size)| buffer.size() == *size);
+        if !fits {
+            *buffers = sizes
+                .iter()
+                .map(|size| {
+                    device.create_buffer(&BufferDescriptor {
+                        label: Some("History Slot"),
+                        size: *size,
+                        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    })
+                })
+                .collect();
+        }
+    }
+
+    /// Copies `sources` into `slot`, in the order given to [`GpuHistory::prepare`].
+    pub fn capture(&self, encoder: &mut CommandEncoder, slot: usize, sources: &[&Buffer]) {
+        let Some(buffers) = self.slots.get(slot) else {
+            return;
+        };
+
+        for (source, buffer) in sources.iter().zip(buffers) {
+            if source.size() == buffer.size() {
+                encoder.copy_buffer_to_buffer(source, 0, buffer, 0, buffer.size());
+            }
+        }
+    }
+
+    /// Copies `slot` back into `targets`, in the order given to [`GpuHistory::capture`].
+    pub fn restore(&self, encoder: &mut CommandEncoder, slot: usize, targets: &[&Buffer]) {
+        let Some(buffers) = self.slots.get(slot) else {
+            return;
+        };
+
+        for (buffer, target) in buffers.iter().zip(targets) {
+            if target.size() == buffer.size() {
+                encoder.copy_buffer_to_buffer(buffer, 0, target, 0, buffer.size());
+            }
+        }
+    }
+}
+
+// ================================== UI ================================== //
+
+/// Timeline of the history, shared by the UIs of the simulations.
+pub fn history_ui(ui: &mut egui::Ui, history: &mut SimHistory, clock: &mut SimClock) {
+    ui.add(egui::Slider::new(&mut history.interval, 1..=600).text("History Interval"));
+    ui.add(
+        egui::Slider::new(&mut history.budget_mb, 16..=4096)
+            .logarithmic(true)
+            .text("History Memory (MB)"),
+    )
+    .on_hover_text(format!("Keeps up to {} states", history.capacity()));
+
+    let Some(latest) = history.frames.len().checked_sub(1) else {
+        ui.label("No history yet");
+        return;
+    };
+
+    let mut index = history.scrub.unwrap_or(latest);
+    if ui
+        .add(egui::Slider::new(&mut index, 0..=latest).text("Timeline"))
+        .changed()
+    {
+        history.scrub_to(index, clock);
+    }
+
+    match history.scrubbed_frame() {
+        Some(frame) => ui.label(format!("Inspecting step {}, play to resume", frame.step)),
+        None => ui.label(format!(
+            "Recorded up to step {}",
+            history.frames[latest].step
+        )),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A history keeping `capacity` states of a megabyte.
+    fn history(capacity: u32) -> SimHistory {
+        let mut history = SimHistory {
+            budget_mb: capacity,
+            ..SimHistory::default()
+        };
+        history.set_state_size(1024 * 1024);
+        history
+    }
+
+    fn slots(history: &SimHistory) -> Vec<usize> {
+        history.frames.iter().map(|frame| frame.slot).collect()
+    }
+
+    #[test]
+    fn capacity_follows_the_budget() {
+        assert_eq!(history(3).capacity(), 3);
+        assert_eq!(history(0).capacity(), 1);
+        assert_eq!(history(1000).capacity(), MAX_HISTORY_CAPACITY);
+    }
+
+    #[test]
+    fn oldest_states_give_their_slot_to_new_ones() {
+        let mut history = history(3);
+        let pushed = (0..5).map(|step| history.push(step)).collect::<Vec<_>>();
+        assert_eq!(pushed, [0, 1, 2, 0, 1]);
+        assert_eq!(slots(&history), [2, 0, 1]);
+        assert_eq!(history.frames[0].step, 2);
+    }
+
+    #[test]
+    fn resuming_reuses_the_slots_of_the_dropped_states() {
+        let mut history = history(4);
+        let mut clock = SimClock::default();
+        for step in 0..4 {
+            history.push(step);
+        }
+
+        history.scrub_to(1, &mut clock);
+        assert_eq!(history.command, Some(HistoryCommand::Restore { slot: 1 }));
+        history.resume_from(1);
+        assert_eq!(history.push(10), 2);
+        assert_eq!(history.push(11), 3);
+        assert_eq!(history.push(12), 0);
+    }
+
+    #[test]
+    fn shrinking_the_budget_drops_the_oldest_states() {
+        let mut history = history(4);
+        let mut clock = SimClock::default();
+        for step in 0..4 {
+            history.push(step);
+        }
+        history.scrub_to(3, &mut clock);
+
+        history.budget_mb = 2;
+        history.shrink_to_capacity();
+        assert_eq!(slots(&history), [2, 3]);
+        assert_eq!(history.scrubbed_frame().map(|frame| frame.step), Some(3));
+    }
+}
@@ -0,0 +1,149 @@
+//! Hover inspector: the cell under the mouse is read back from the GPU, so the UI can show its
+//! state. A single cell is in flight at a time, the next one is read once it arrived.
+
+use bevy::{
+    prelude::*,
+    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
+};
+use bevy_egui::egui;
+
+use crate::readback::ReadbackEvent;
+
+/// Tag of the readback of the inspected cell, the simulations read it in their own layout.
+pub const INSPECT_READBACK: &str = "inspect_cell";
+/// Frames a readback is waited for before reading the hovered cell again, in case it was lost.
+const INSPECT_TIMEOUT_FRAMES: u32 = 30;
+
+pub struct CellInspectorPlugin;
+impl Plugin for CellInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CellInspector>()
+            .add_plugin(ExtractResourcePlugin::<CellInspector>::default())
+            .add_system(clear_inspect_request.in_base_set(CoreSet::First))
+            .add_system(receive_inspected_cell);
+    }
+}
+
+/// A cell read back from the GPU, as the simulation stores it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InspectedCell {
+    pub pos: UVec2,
+    pub data: Vec<u8>,
+}
+
+/// The cell under the mouse. The simulations call [`CellInspector::hover`] every frame, and their
+/// render nodes read back [`CellInspector::request`] with the [`INSPECT_READBACK`] tag.
+#[derive(Resource, ExtractResource, Debug, Clone)]
+pub struct CellInspector {
+    pub is_enabled: bool,
+    is_hovering: bool,
+    /// Cell to read back this frame.
+    request: Option<UVec2>,
+    /// Cell being read back, with the frames waited for it.
+    pending: Option<(UVec2, u32)>,
+    inspected: Option<InspectedCell>,
+}
+
+impl Default for CellInspector {
+    fn default() -> Self {
+        Self {
+            is_enabled: true,
+            is_hovering: false,
+            request: None,
+            pending: None,
+            inspected: None,
+        }
+    }
+}
+
+impl CellInspector {
+    pub fn request(&self) -> Option<UVec2> {
+        self.request
+    }
+
+    /// The last cell read back, while the mouse is over the simulation.
+    pub fn inspected(&self) -> Option<&InspectedCell> {
+        self.inspected.as_ref().filter(|_| self.is_hovering)
+    }
+
+    /// Follows the cell under the mouse, `None` when the mouse isn't over the simulation.
+    pub fn hover(&mut self, cell: Option<UVec2>) {
+        self.is_hovering = cell.is_some() && self.is_enabled;
+        let Some(cell) = cell.filter(|_| self.is_hovering) else {
+            return;
+        };
+
+        match &mut self.pending {
+            Some((_, frames)) if *frames < INSPECT_TIMEOUT_FRAMES => *frames += 1,
+            _ => {
+                self.request = Some(cell);
+                self.pending = Some((cell, 0));
+            }
+        }
+    }
+}
+
+/// The cell under the mouse in a simulation of `size`, from a mouse position in cells.
+pub fn hovered_cell(mouse_pos: Vec2, size: UVec2) -> Option<UVec2> {
+    let is_inside = mouse_pos.cmpge(Vec2::ZERO).all() && mouse_pos.cmplt(size.as_vec2()).all();
+    is_inside.then(|| mouse_pos.floor().as_uvec2())
+}
+
+fn clear_inspect_request(mut inspector: ResMut<CellInspector>) {
+    if inspector.request.is_some() {
+        inspector.request = None;
+    }
+}
+
+fn receive_inspected_cell(
+    mut inspector: ResMut<CellInspector>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag != INSPECT_READBACK {
+            continue;
+        }
+        if let Some((pos, _)) = inspector.pending.take() {
+            inspector.inspected = Some(InspectedCell {
+                pos,
+                data: readback.data.clone(),
+            });
+        }
+    }
+}
+
+// ================================== UI ================================== //
+
+/// Shows the lines describing the inspected cell next to the mouse, shared by the UIs of the
+/// simulations.
+pub fn inspector_tooltip(ctx: &egui::Context, cell: &InspectedCell, lines: &[String]) {
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("cell_inspector"), |ui| {
+        ui.label(format!("Cell {}, {}", cell.pos.x, cell.pos.y));
+        for line in lines {
+            ui.label(line);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_cells_inside_the_simulation_are_hovered() {
+        let size = UVec2::new(8, 4);
+        assert_eq!(hovered_cell(Vec2::ZERO, size), Some(UVec2::ZERO));
+        assert_eq!(
+            hovered_cell(Vec2::new(7.9, 3.9), size),
+            Some(UVec2::new(7, 3))
+        );
+        for outside in [
+            Vec2::new(-0.1, 1.0),
+            Vec2::new(1.0, -0.1),
+            Vec2::new(8.0, 1.0),
+            Vec2::new(1.0, 4.0),
+        ] {
+            assert_eq!(hovered_cell(outside, size), None, "{}", outside);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Pieces shared by the simulations.
+
+pub mod brush;
+pub mod clock;
+pub mod headless;
+pub mod history;
+pub mod inspect;
+pub mod readback;
+pub mod recording;
+pub mod snapshot;
+pub mod stamp;
+pub mod undo;
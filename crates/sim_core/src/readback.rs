@@ -0,0 +1,245 @@
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Extent3d, ImageCopyBuffer,
+            ImageDataLayout, MapMode, Texture,
+        },
+        renderer::RenderDevice,
+        RenderApp, RenderSet,
+    },
+};
+use wgpu::{Maintain, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+pub struct ReadbackPlugin;
+impl Plugin for ReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+
+        app.add_event::<ReadbackEvent>()
+            .init_resource::<ReadbackRequests>()
+            .insert_resource(ReadbackReceiver(Mutex::new(receiver)))
+            .add_plugin(ExtractResourcePlugin::<ReadbackRequests>::default())
+            .add_system(receive_readbacks.in_base_set(CoreSet::First));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(GpuReadback {
+                    sender,
+                    recorded: Mutex::default(),
+                    mapping: Vec::new(),
+                })
+                .add_system(map_readbacks.in_set(RenderSet::Cleanup));
+        }
+    }
+}
+
+/// Data copied from the GPU, sent a few frames after it was recorded with [`GpuReadback`].
+pub struct ReadbackEvent {
+    /// Tag given when recording the copy.
+    pub tag: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Readbacks asked for this frame by the main world, by tag. The render world records the copies
+/// of the tags it knows about, the requests are cleared at the start of every frame.
+#[derive(Resource, ExtractResource, Debug, Clone, Default)]
+pub struct ReadbackRequests(Vec<&'static str>);
+
+impl ReadbackRequests {
+    pub fn request(&mut self, tag: &'static str) {
+        if !self.is_requested(tag) {
+            self.0.push(tag);
+        }
+    }
+
+    pub fn is_requested(&self, tag: &'static str) -> bool {
+        self.0.contains(&tag)
+    }
+}
+
+#[derive(Resource)]
+struct ReadbackReceiver(Mutex<Receiver<ReadbackEvent>>);
+
+fn receive_readbacks(
+    receiver: Res<ReadbackReceiver>,
+    mut requests: ResMut<ReadbackRequests>,
+    mut events: EventWriter<ReadbackEvent>,
+) {
+    if !requests.0.is_empty() {
+        requests.0.clear();
+    }
+
+    if let Ok(receiver) = receiver.0.lock() {
+        events.send_batch(receiver.try_iter());
+    }
+}
+
+// ================================== Render World ================================== //
+
+/// A staging buffer waiting for the GPU, rows are padded to [`COPY_BYTES_PER_ROW_ALIGNMENT`] for
+/// textures.
+struct Staging {
+    tag: &'static str,
+    buffer: Buffer,
+    /// Bytes per row of the data and of the staging buffer.
+    rows: Option<(u32, u32)>,
+}
+
+/// Copies GPU data back to the main world as [`ReadbackEvent`]s. Render nodes record the copies,
+/// which are mapped once the frame was submitted and sent without waiting for the GPU.
+#[derive(Resource)]
+pub struct GpuReadback {
+    sender: Sender<ReadbackEvent>,
+    recorded: Mutex<Vec<Staging>>,
+    /// Copies being mapped, with whether the mapping succeeded once it is done.
+    mapping: Vec<(Staging, Arc<Mutex<Option<bool>>>)>,
+}
+
+impl GpuReadback {
+    /// Copies the ranges of `buffers` one after the other, they are sent together. Ranges must be
+    /// multiples of 4 bytes.
+    pub fn read_buffers(
+        &self,
+        device: &RenderDevice,
+        encoder: &mut CommandEncoder,
+        tag: &'static str,
+        buffers: &[(&Buffer, std::ops::Range<u64>)],
+    ) {
+        let size = buffers
+            .iter()
+            .map(|(_, range)| range.end - range.start)
+            .sum();
+        let staging = Self::create_staging(device, size);
+
+        let mut offset = 0;
+        for (buffer, range) in buffers {
+            let len = range.end - range.start;
+            encoder.copy_buffer_to_buffer(buffer, range.start, &staging, offset, len);
+            offset += len;
+        }
+
+        self.record(Staging {
+            tag,
+            buffer: staging,
+            rows: None,
+        });
+    }
+
+    /// Copies a 2D texture with `bytes_per_pixel` bytes per texel, sent without the row padding.
+    pub fn read_texture(
+        &self,
+        device: &RenderDevice,
+        encoder: &mut CommandEncoder,
+        tag: &'static str,
+        texture: &Texture,
+        size: (u32, u32),
+        bytes_per_pixel: u32,
+    ) {
+        let row = size.0 * bytes_per_pixel;
+        let padded_row = row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = Self::create_staging(device, padded_row as u64 * size.1 as u64);
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &staging,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.record(Staging {
+            tag,
+            buffer: staging,
+            rows: Some((row, padded_row)),
+        });
+    }
+
+    fn create_staging(device: &RenderDevice, size: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Staging"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn record(&self, staging: Staging) {
+        if let Ok(mut recorded) = self.recorded.lock() {
+            recorded.push(staging);
+        }
+    }
+}
+
+/// Maps the copies recorded this frame, and sends the ones the GPU is done with.
+pub fn map_readbacks(device: Res<RenderDevice>, mut readback: ResMut<GpuReadback>) {
+    let recorded = readback
+        .recorded
+        .get_mut()
+        .map(std::mem::take)
+        .unwrap_or_default();
+    for staging in recorded {
+        let mapped = Arc::new(Mutex::new(None));
+        let on_mapped = mapped.clone();
+        staging
+            .buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                if let Ok(mut mapped) = on_mapped.lock() {
+                    *mapped = Some(result.is_ok());
+                }
+            });
+        readback.mapping.push((staging, mapped));
+    }
+
+    if readback.mapping.is_empty() {
+        return;
+    }
+    device.wgpu_device().poll(Maintain::Poll);
+
+    let GpuReadback {
+        sender, mapping, ..
+    } = &mut *readback;
+    mapping.retain(|(staging, mapped)| {
+        match mapped.lock().map(|mapped| *mapped) {
+            Ok(Some(true)) => {}
+            // Dropped without sending anything
+            Ok(Some(false)) | Err(_) => return false,
+            Ok(None) => return true,
+        }
+
+        let data = {
+            let view = staging.buffer.slice(..).get_mapped_range();
+            match staging.rows {
+                Some((row, padded_row)) => view
+                    .chunks(padded_row as usize)
+                    .flat_map(|padded| &padded[..row as usize])
+                    .copied()
+                    .collect(),
+                None => view.to_vec(),
+            }
+        };
+        staging.buffer.unmap();
+
+        let _ = sender.send(ReadbackEvent {
+            tag: staging.tag,
+            data,
+        });
+        false
+    });
+}
@@ -0,0 +1,469 @@
+//! Records the canvas of a simulation every few steps, as numbered PNGs or a single animation.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{CommandEncoderDescriptor, TextureFormat},
+        renderer::{RenderDevice, RenderQueue},
+        RenderApp, RenderSet,
+    },
+};
+use bevy_egui::egui;
+
+use crate::clock::SimClock;
+use crate::readback::{map_readbacks, GpuReadback, ReadbackEvent, ReadbackRequests};
+
+pub const INIT_RECORDING_INTERVAL: u32 = 4;
+/// Default name of the recordings, extended with the frame numbers or the file extension.
+pub const DEFAULT_RECORDING_PATH: &str = "recording";
+/// Most bytes of raw frames in an animation, the recording stops once reached. APNGs keep their
+/// frames in memory until written, and GIFs may be encoded slower than they are recorded.
+pub const MAX_ANIMATION_BYTES: usize = 1024 * 1024 * 1024;
+
+/// Tag of the readback of the canvas.
+const FRAME_READBACK: &str = "recording_frame";
+
+/// Records the canvas held by `R`, which must be extracted to the render world. Needs the
+/// [`crate::readback::ReadbackPlugin`].
+pub struct RecordingPlugin<R>(PhantomData<R>);
+
+impl<R> Default for RecordingPlugin<R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<R: Resource + std::ops::Deref<Target = Handle<Image>>> Plugin for RecordingPlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimRecording>()
+            .add_systems((update_recording::<R>, receive_frames).chain());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_system(
+                copy_frame::<R>
+                    .before(map_readbacks)
+                    .in_set(RenderSet::Cleanup),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// A PNG per frame, numbered, in a directory.
+    #[default]
+    PngSequence,
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    pub const ALL: [RecordingFormat; 3] = [
+        RecordingFormat::PngSequence,
+        RecordingFormat::Gif,
+        RecordingFormat::Apng,
+    ];
+}
+
+/// Settings of the recording, and the frames of the animation being recorded.
+#[derive(Resource, Debug)]
+pub struct SimRecording {
+    pub format: RecordingFormat,
+    /// Steps between two frames.
+    pub interval: u32,
+    /// Directory of the PNG sequences, or file of the animations without its extension.
+    pub path: String,
+    /// Starts recording when set, and stops and writes the animation when cleared.
+    pub is_recording: bool,
+    active: Option<ActiveRecording>,
+}
+
+impl Default for SimRecording {
+    fn default() -> Self {
+        Self {
+            format: RecordingFormat::default(),
+            interval: INIT_RECORDING_INTERVAL,
+            path: DEFAULT_RECORDING_PATH.to_string(),
+            is_recording: false,
+            active: None,
+        }
+    }
+}
+
+impl SimRecording {
+    /// Frames recorded so far, `None` while not recording.
+    pub fn frame_count(&self) -> Option<usize> {
+        self.active.as_ref().map(|active| active.frame_count)
+    }
+}
+
+/// A recording in progress. Its frames are sent to a thread encoding them as they come.
+#[derive(Debug)]
+struct ActiveRecording {
+    format: RecordingFormat,
+    size: (u32, u32),
+    /// Step of the last requested frame.
+    last_step: Option<u32>,
+    frame_count: usize,
+    /// Bytes of the frames sent so far.
+    bytes: usize,
+    frames: Sender<Vec<u8>>,
+}
+
+impl ActiveRecording {
+    fn frame_size(&self) -> usize {
+        self.size.0 as usize * self.size.1 as usize * 4
+    }
+
+    /// Whether another frame would take the animation past [`MAX_ANIMATION_BYTES`].
+    fn is_full(&self) -> bool {
+        self.format != RecordingFormat::PngSequence
+            && self.bytes + self.frame_size() > MAX_ANIMATION_BYTES
+    }
+
+    /// Sends a frame to the encoding thread, returning whether it is still encoding.
+    fn add_frame(&mut self, data: Vec<u8>) -> bool {
+        if data.len() != self.frame_size() {
+            return true;
+        }
+
+        self.bytes += data.len();
+        self.frame_count += 1;
+        self.frames.send(data).is_ok()
+    }
+}
+
+fn update_recording<R: Resource + std::ops::Deref<Target = Handle<Image>>>(
+    clock: Res<SimClock>,
+    images: Res<Assets<Image>>,
+    canvas: Option<Res<R>>,
+    mut recording: ResMut<SimRecording>,
+    mut requests: ResMut<ReadbackRequests>,
+) {
+    let size = canvas.and_then(|canvas| images.get(&canvas)).map(|image| {
+        let size = image.texture_descriptor.size;
+        (size.width, size.height)
+    });
+
+    // A resize, or a full animation, ends the recording
+    let must_stop = recording
+        .active
+        .as_ref()
+        .is_some_and(|active| Some(active.size) != size || active.is_full());
+    if must_stop || !recording.is_recording {
+        recording.is_recording = false;
+        // Dropping the sender lets the encoding thread finish the file
+        recording.active = None;
+        return;
+    }
+
+    if recording.active.is_none() {
+        let Some(size) = size else {
+            return;
+        };
+        match start_recording(&recording, size, &clock) {
+            Ok(active) => recording.active = Some(active),
+            Err(error) => {
+                warn!("Failed to start recording to {}: {}", recording.path, error);
+                recording.is_recording = false;
+                return;
+            }
+        }
+    }
+
+    let interval = recording.interval.max(1);
+    let Some(active) = recording.active.as_mut() else {
+        return;
+    };
+    let is_due = active
+        .last_step
+        .is_none_or(|step| clock.step.wrapping_sub(step) >= interval);
+    if is_due {
+        active.last_step = Some(clock.step);
+        requests.request(FRAME_READBACK);
+    }
+}
+
+fn start_recording(
+    recording: &SimRecording,
+    size: (u32, u32),
+    clock: &SimClock,
+) -> Result<ActiveRecording, Box<dyn std::error::Error>> {
+    let steps_per_second = clock.ticks_per_second * clock.speed * clock.substeps.max(1) as f32;
+    let frame_delay_ms = (recording.interval.max(1) as f32 * 1000.0 / steps_per_second.max(1.0))
+        .round()
+        .max(1.0) as u32;
+
+    let sink = FrameSink::open(recording.format, &recording.path, size, frame_delay_ms)?;
+    let (sender, receiver) = channel();
+    std::thread::Builder::new()
+        .name("Recording Encoder".to_string())
+        .spawn(move || encode_frames(sink, receiver))?;
+
+    Ok(ActiveRecording {
+        format: recording.format,
+        size,
+        last_step: None,
+        frame_count: 0,
+        bytes: 0,
+        frames: sender,
+    })
+}
+
+fn receive_frames(
+    mut recording: ResMut<SimRecording>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag != FRAME_READBACK {
+            continue;
+        }
+        let Some(active) = recording.active.as_mut() else {
+            continue;
+        };
+        // The encoding thread stops on errors, which it logs
+        if !active.add_frame(readback.data.clone()) {
+            recording.is_recording = false;
+            recording.active = None;
+        }
+    }
+}
+
+// ================================== Render World ================================== //
+
+/// Copies the canvas once the frame drew it.
+fn copy_frame<R: Resource + std::ops::Deref<Target = Handle<Image>>>(
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    gpu_images: Res<RenderAssets<Image>>,
+    readback: Res<GpuReadback>,
+    requests: Option<Res<ReadbackRequests>>,
+    canvas: Option<Res<R>>,
+) {
+    if !requests.is_some_and(|requests| requests.is_requested(FRAME_READBACK)) {
+        return;
+    }
+    let Some(gpu_image) = canvas.and_then(|canvas| gpu_images.get(&canvas)) else {
+        return;
+    };
+    if !matches!(
+        gpu_image.texture_format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    ) {
+        return;
+    }
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Recording Encoder"),
+    });
+    readback.read_texture(
+        &render_device,
+        &mut encoder,
+        FRAME_READBACK,
+        &gpu_image.texture,
+        (gpu_image.size.x as u32, gpu_image.size.y as u32),
+        4,
+    );
+    render_queue.submit([encoder.finish()]);
+}
+
+// ================================== Encoding ================================== //
+
+type EncodeResult = Result<(), Box<dyn std::error::Error>>;
+
+fn png_encoder(
+    path: &Path,
+    size: (u32, u32),
+) -> Result<png::Encoder<'_, BufWriter<File>>, std::io::Error> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size.0, size.1);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    Ok(encoder)
+}
+
+fn write_png(path: &Path, size: (u32, u32), data: &[u8]) -> EncodeResult {
+    let mut writer = png_encoder(path, size)?.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn write_apng(path: &Path, size: (u32, u32), delay_ms: u32, frames: &[Vec<u8>]) -> EncodeResult {
+    let mut encoder = png_encoder(path, size)?;
+    encoder.set_animated(frames.len().max(1) as u32, 0)?;
+    encoder.set_frame_delay(delay_ms.min(u16::MAX as u32) as u16, 1000)?;
+
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(frame)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Where the frames of a recording are written, owned by its encoding thread.
+enum FrameSink {
+    /// Numbered PNGs in a directory.
+    PngSequence { dir: PathBuf, size: (u32, u32) },
+    Gif {
+        path: PathBuf,
+        encoder: gif::Encoder<BufWriter<File>>,
+        size: (u16, u16),
+        /// In hundredths of a second.
+        delay: u16,
+    },
+    /// The frame count goes in the header, so the frames are kept until the recording stops.
+    Apng {
+        path: PathBuf,
+        size: (u32, u32),
+        delay_ms: u32,
+        frames: Vec<Vec<u8>>,
+    },
+}
+
+impl FrameSink {
+    /// Creates the files of the recording at `path`, extended with the file extension of
+    /// animations.
+    fn open(
+        format: RecordingFormat,
+        path: &str,
+        size: (u32, u32),
+        delay_ms: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        match format {
+            RecordingFormat::PngSequence => {
+                let dir = PathBuf::from(path);
+                std::fs::create_dir_all(&dir)?;
+                Ok(Self::PngSequence { dir, size })
+            }
+            RecordingFormat::Gif => {
+                let (Ok(width), Ok(height)) = (u16::try_from(size.0), u16::try_from(size.1)) else {
+                    return Err(format!(
+                        "{}x{} pixels is too large for a GIF, record an APNG instead",
+                        size.0, size.1
+                    )
+                    .into());
+                };
+                let path = Path::new(path).with_extension("gif");
+                let mut encoder =
+                    gif::Encoder::new(BufWriter::new(File::create(&path)?), width, height, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                Ok(Self::Gif {
+                    path,
+                    encoder,
+                    size: (width, height),
+                    delay: delay_ms.div_ceil(10).min(u16::MAX as u32) as u16,
+                })
+            }
+            RecordingFormat::Apng => Ok(Self::Apng {
+                path: Path::new(path).with_extension("png"),
+                size,
+                delay_ms,
+                frames: Vec::new(),
+            }),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Self::PngSequence { dir, .. } => dir,
+            Self::Gif { path, .. } | Self::Apng { path, .. } => path,
+        }
+    }
+
+    fn write(&mut self, index: usize, mut frame: Vec<u8>) -> EncodeResult {
+        match self {
+            Self::PngSequence { dir, size } => {
+                write_png(&dir.join(format!("frame_{index:05}.png")), *size, &frame)
+            }
+            Self::Gif {
+                encoder,
+                size,
+                delay,
+                ..
+            } => {
+                let mut frame = gif::Frame::from_rgba_speed(size.0, size.1, &mut frame, 10);
+                frame.delay = *delay;
+                encoder.write_frame(&frame)?;
+                Ok(())
+            }
+            Self::Apng { frames, .. } => {
+                frames.push(frame);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> EncodeResult {
+        match self {
+            // The trailer of the GIF is written when its encoder is dropped
+            Self::PngSequence { .. } | Self::Gif { .. } => Ok(()),
+            Self::Apng {
+                path,
+                size,
+                delay_ms,
+                frames,
+            } => write_apng(&path, size, delay_ms, &frames),
+        }
+    }
+}
+
+/// Writes the frames as they are received, until the recording stops and drops its sender.
+fn encode_frames(mut sink: FrameSink, frames: Receiver<Vec<u8>>) {
+    let path = sink.path().to_path_buf();
+    let mut frame_count = 0;
+    for frame in frames {
+        if let Err(error) = sink.write(frame_count, frame) {
+            warn!("Failed to write {}: {}", path.display(), error);
+            return;
+        }
+        frame_count += 1;
+    }
+
+    match sink.finish() {
+        Ok(()) => info!("Recorded {} frames to {}", frame_count, path.display()),
+        Err(error) => warn!("Failed to write {}: {}", path.display(), error),
+    }
+}
+
+// ================================== UI ================================== //
+
+/// Recording controls, shared by the UIs of the simulations.
+pub fn recording_ui(ui: &mut egui::Ui, recording: &mut SimRecording) {
+    let is_active = recording.frame_count().is_some();
+    ui.add_enabled_ui(!is_active, |ui| {
+        egui::ComboBox::from_label("Recording Format")
+            .selected_text(format!("{:?}", recording.format))
+            .show_ui(ui, |ui| {
+                for format in RecordingFormat::ALL {
+                    ui.selectable_value(&mut recording.format, format, format!("{:?}", format));
+                }
+            });
+        ui.add(egui::Slider::new(&mut recording.interval, 1..=120).text("Steps per Frame"));
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut recording.path);
+            ui.label("Recording");
+        });
+    });
+
+    ui.horizontal(|ui| {
+        let label = if recording.is_recording {
+            "Stop Recording"
+        } else {
+            "Start Recording"
+        };
+        if ui.button(label).clicked() {
+            recording.is_recording = !recording.is_recording;
+        }
+        if let Some(frame_count) = recording.frame_count() {
+            ui.label(format!("{frame_count} frames"));
+        }
+    });
+}
@@ -0,0 +1,474 @@
+//! Versioned file format of saved worlds: a [`SnapshotHeader`] followed by the sections written by
+//! each simulation, cells are run-length encoded.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{prelude::*, render::renderer::RenderDevice};
+use bevy_egui::egui;
+
+/// Version written in new files, older versions are read as long as they are supported.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Default file the worlds are saved to and loaded from, next to the executable.
+pub const DEFAULT_SNAPSHOT_PATH: &str = "world.snapshot";
+/// Widest and highest snapshot read, checked before the cells are allocated. Devices may hold less,
+/// see [`SizeLimits`].
+pub const MAX_SNAPSHOT_SIDE: u32 = 8192;
+
+pub struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SnapshotPath>()
+            .add_event::<SaveWorld>()
+            .add_event::<LoadWorld>();
+    }
+}
+
+/// File picked in the UI.
+#[derive(Resource, Debug, Clone)]
+pub struct SnapshotPath(pub String);
+
+impl Default for SnapshotPath {
+    fn default() -> Self {
+        Self(DEFAULT_SNAPSHOT_PATH.to_string())
+    }
+}
+
+/// Saves the world as it is at the start of the next frame.
+pub struct SaveWorld {
+    pub path: PathBuf,
+}
+
+/// Replaces the world, and its size if it differs.
+pub struct LoadWorld {
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    /// The file was saved by another simulation, or isn't a snapshot.
+    WrongMagic([u8; 4]),
+    UnsupportedVersion(u32),
+    /// The file ends in the middle of a section.
+    Truncated,
+    /// The cells don't fill the size in the header.
+    WrongCellCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The world doesn't fit in the [`SizeLimits`].
+    TooLarge {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "{error}"),
+            SnapshotError::WrongMagic(magic) => {
+                write!(f, "not a snapshot of this simulation ({magic:?})")
+            }
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "unsupported snapshot version {version}")
+            }
+            SnapshotError::Truncated => write!(f, "truncated snapshot"),
+            SnapshotError::WrongCellCount { expected, found } => {
+                write!(f, "expected {expected} cells, found {found}")
+            }
+            SnapshotError::TooLarge { width, height } => {
+                write!(f, "{width}x{height} cells is too large")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(error: std::io::Error) -> Self {
+        SnapshotError::Io(error)
+    }
+}
+
+/// Start of every snapshot file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotHeader {
+    /// Identifies the simulation the file belongs to.
+    pub magic: [u8; 4],
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Seed of the random choices, `0` for simulations without any.
+    pub seed: u32,
+    /// Step the saved cells are the input of.
+    pub step: u32,
+}
+
+impl SnapshotHeader {
+    pub fn new(magic: [u8; 4], size: (u32, u32), seed: u32, step: u32) -> Self {
+        Self {
+            magic,
+            version: SNAPSHOT_VERSION,
+            width: size.0,
+            height: size.1,
+            seed,
+            step,
+        }
+    }
+
+    pub fn num_of_cells(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+/// Largest simulation that can be created, a world is checked against them before its cells are
+/// allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeLimits {
+    /// Widest and highest simulation, the canvas is a texture of its size.
+    pub max_side: u32,
+    /// Largest buffer of cells in bytes.
+    pub max_buffer_size: u64,
+}
+
+impl Default for SizeLimits {
+    /// Only [`MAX_SNAPSHOT_SIDE`], for the simulations running without a GPU.
+    fn default() -> Self {
+        Self {
+            max_side: MAX_SNAPSHOT_SIDE,
+            max_buffer_size: u64::MAX,
+        }
+    }
+}
+
+impl SizeLimits {
+    /// The textures and storage buffers `device` can bind.
+    pub fn of_device(device: &RenderDevice) -> Self {
+        let limits = device.limits();
+        Self {
+            max_side: limits.max_texture_dimension_2d.min(MAX_SNAPSHOT_SIDE),
+            max_buffer_size: limits
+                .max_buffer_size
+                .min(limits.max_storage_buffer_binding_size as u64),
+        }
+    }
+
+    /// Checks a simulation of `size` storing `bytes_per_cell` in its largest buffer.
+    pub fn check(&self, size: (u32, u32), bytes_per_cell: u64) -> Result<(), SnapshotError> {
+        let buffer_size = size.0 as u64 * size.1 as u64 * bytes_per_cell;
+        if size.0 > self.max_side || size.1 > self.max_side || buffer_size > self.max_buffer_size {
+            return Err(SnapshotError::TooLarge {
+                width: size.0,
+                height: size.1,
+            });
+        }
+        Ok(())
+    }
+}
+
+// ================================== Writing ================================== //
+
+/// Builds a snapshot in memory, all values are little endian.
+pub struct SnapshotWriter {
+    data: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    pub fn new(header: &SnapshotHeader) -> Self {
+        let mut writer = Self {
+            data: header.magic.to_vec(),
+        };
+        writer.u32(header.version);
+        writer.u32(header.width);
+        writer.u32(header.height);
+        writer.u32(header.seed);
+        writer.u32(header.step);
+        writer
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.data.extend_from_slice(value.as_bytes());
+    }
+
+    /// Writes `cells` as runs of identical cells, each run being its length and one cell.
+    pub fn cells<T: bytemuck::Pod>(&mut self, cells: &[T]) {
+        let runs = run_lengths(cells);
+        self.u32(runs.len() as u32);
+        for (len, cell) in runs {
+            self.u32(len);
+            self.data.extend_from_slice(bytemuck::bytes_of(cell));
+        }
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn save(self, path: &Path) -> Result<(), SnapshotError> {
+        std::fs::write(path, self.data)?;
+        Ok(())
+    }
+}
+
+fn run_lengths<T: bytemuck::Pod>(cells: &[T]) -> Vec<(u32, &T)> {
+    let mut runs: Vec<(u32, &T)> = Vec::new();
+    for cell in cells {
+        match runs.last_mut() {
+            Some((len, last)) if bytemuck::bytes_of(*last) == bytemuck::bytes_of(cell) => *len += 1,
+            _ => runs.push((1, cell)),
+        }
+    }
+    runs
+}
+
+// ================================== Reading ================================== //
+
+/// Reads a snapshot written by a [`SnapshotWriter`], in the same order.
+pub struct SnapshotReader<'a> {
+    header: SnapshotHeader,
+    data: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    /// Checks the header, `magic` being the one of the simulation reading the file.
+    pub fn new(data: &'a [u8], magic: [u8; 4]) -> Result<Self, SnapshotError> {
+        let found: [u8; 4] = data
+            .get(..4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(SnapshotError::Truncated)?;
+        if found != magic {
+            return Err(SnapshotError::WrongMagic(found));
+        }
+
+        let mut reader = Self {
+            header: SnapshotHeader::new(magic, (0, 0), 0, 0),
+            data: &data[4..],
+        };
+        let version = reader.u32()?;
+        if version == 0 || version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        reader.header = SnapshotHeader {
+            magic,
+            version,
+            width: reader.u32()?,
+            height: reader.u32()?,
+            seed: reader.u32()?,
+            step: reader.u32()?,
+        };
+        SizeLimits::default().check((reader.header.width, reader.header.height), 0)?;
+        Ok(reader)
+    }
+
+    /// Checks the size of the header against `limits`, for a simulation storing `bytes_per_cell`
+    /// in its largest buffer.
+    pub fn check_size(
+        &self,
+        limits: &SizeLimits,
+        bytes_per_cell: u64,
+    ) -> Result<(), SnapshotError> {
+        limits.check((self.header.width, self.header.height), bytes_per_cell)
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.data.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn string(&mut self) -> Result<String, SnapshotError> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    /// Reads the cells written by [`SnapshotWriter::cells`], which must fill the size of the
+    /// header. They grow with the runs actually read, so a header claiming a larger size than the
+    /// data holds fails before allocating it.
+    pub fn cells<T: bytemuck::Pod>(&mut self) -> Result<Vec<T>, SnapshotError> {
+        let expected = self.header.num_of_cells();
+        let runs = self.u32()?;
+        if runs as usize > self.data.len() / (4 + std::mem::size_of::<T>()) {
+            return Err(SnapshotError::Truncated);
+        }
+
+        let mut cells = Vec::new();
+        for _ in 0..runs {
+            let len = self.u32()? as usize;
+            let cell = bytemuck::pod_read_unaligned(self.take(std::mem::size_of::<T>())?);
+            if cells.len() + len > expected {
+                return Err(SnapshotError::WrongCellCount {
+                    expected,
+                    found: cells.len() + len,
+                });
+            }
+            cells.resize(cells.len() + len, cell);
+        }
+
+        if cells.len() != expected {
+            return Err(SnapshotError::WrongCellCount {
+                expected,
+                found: cells.len(),
+            });
+        }
+        Ok(cells)
+    }
+}
+
+// ================================== UI ================================== //
+
+/// Save and load controls, shared by the UIs of the simulations.
+pub fn snapshot_ui(
+    ui: &mut egui::Ui,
+    path: &mut SnapshotPath,
+    save_events: &mut EventWriter<SaveWorld>,
+    load_events: &mut EventWriter<LoadWorld>,
+) {
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut path.0);
+        if ui.button("Save").clicked() {
+            save_events.send(SaveWorld {
+                path: PathBuf::from(&path.0),
+            });
+        }
+        if ui.button("Load").clicked() {
+            load_events.send(LoadWorld {
+                path: PathBuf::from(&path.0),
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAGIC: [u8; 4] = *b"TEST";
+
+    fn snapshot(size: (u32, u32), cells: &[[u32; 2]]) -> Vec<u8> {
+        let mut writer = SnapshotWriter::new(&SnapshotHeader::new(MAGIC, size, 7, 42));
+        writer.string("cells");
+        writer.cells(cells);
+        writer.finish()
+    }
+
+    fn read(data: &[u8]) -> Result<(SnapshotHeader, String, Vec<[u32; 2]>), SnapshotError> {
+        let mut reader = SnapshotReader::new(data, MAGIC)?;
+        let name = reader.string()?;
+        let cells = reader.cells()?;
+        Ok((*reader.header(), name, cells))
+    }
+
+    #[test]
+    fn cells_round_trip_as_runs() {
+        let cells = [
+            [0, 0],
+            [0, 0],
+            [0, 0],
+            [1, 5],
+            [0, 0],
+            [2, 2],
+            [2, 2],
+            [2, 2],
+        ];
+        assert_eq!(run_lengths(&cells).len(), 4);
+
+        let (header, name, read_cells) = read(&snapshot((4, 2), &cells)).unwrap();
+        assert_eq!(header, SnapshotHeader::new(MAGIC, (4, 2), 7, 42));
+        assert_eq!(name, "cells");
+        assert_eq!(read_cells, cells);
+    }
+
+    #[test]
+    fn truncated_snapshots_are_rejected() {
+        let data = snapshot((4, 2), &[[3, 1]; 8]);
+        for len in 0..data.len() {
+            assert!(read(&data[..len]).is_err(), "read {len} bytes");
+        }
+    }
+
+    #[test]
+    fn claimed_sizes_are_not_allocated_up_front() {
+        let mut data = snapshot((4, 2), &[]);
+        // Runs the data can't hold
+        data.truncate(data.len() - 4);
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(read(&data), Err(SnapshotError::Truncated)));
+
+        let data = snapshot((MAX_SNAPSHOT_SIDE, MAX_SNAPSHOT_SIDE), &[]);
+        assert!(matches!(
+            read(&data),
+            Err(SnapshotError::WrongCellCount { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn corrupt_snapshots_are_rejected() {
+        assert!(matches!(
+            read(&snapshot((4, 2), &[[1, 1]; 7])),
+            Err(SnapshotError::WrongCellCount {
+                expected: 8,
+                found: 7
+            })
+        ));
+        assert!(matches!(
+            read(&snapshot((4, 2), &[[1, 1]; 9])),
+            Err(SnapshotError::WrongCellCount {
+                expected: 8,
+                found: 9
+            })
+        ));
+        assert!(matches!(
+            SnapshotReader::new(&snapshot((4, 2), &[]), *b"LIFE"),
+            Err(SnapshotError::WrongMagic(MAGIC))
+        ));
+
+        let mut newer = snapshot((4, 2), &[[0, 0]; 8]);
+        newer[4..8].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            read(&newer),
+            Err(SnapshotError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn sizes_are_checked_before_reading_the_cells() {
+        let data = snapshot((u32::MAX, u32::MAX), &[]);
+        assert!(matches!(read(&data), Err(SnapshotError::TooLarge { .. })));
+
+        let data = snapshot((1024, 1024), &[]);
+        let reader = SnapshotReader::new(&data, MAGIC).unwrap();
+        let limits = SizeLimits {
+            max_side: 2048,
+            max_buffer_size: 1 << 22,
+        };
+        assert!(reader.check_size(&limits, 4).is_ok());
+        assert!(matches!(
+            reader.check_size(&limits, 8),
+            Err(SnapshotError::TooLarge {
+                width: 1024,
+                height: 1024
+            })
+        ));
+    }
+}
@@ -0,0 +1,393 @@
+//! Stamps are regions of a world saved to be placed again with the mouse, as files in a directory
+//! per simulation. The simulations read and write the cells, only their `u32` kinds are placed.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{Buffer, BufferInitDescriptor, BufferUsages},
+        renderer::RenderDevice,
+        RenderApp, RenderSet,
+    },
+};
+use bevy_egui::egui;
+
+use crate::brush::{Brush, BrushTool};
+
+/// Extension of the stamp files.
+pub const STAMP_EXTENSION: &str = "stamp";
+
+/// Lists the stamps found in `dir`, relative to the working directory.
+pub struct StampPlugin {
+    pub dir: &'static str,
+}
+
+impl Plugin for StampPlugin {
+    fn build(&self, app: &mut App) {
+        let mut library = StampLibrary {
+            dir: PathBuf::from(self.dir),
+            ..default()
+        };
+        library.refresh();
+
+        app.insert_resource(library)
+            .init_resource::<LoadedStamp>()
+            .add_plugin(ExtractResourcePlugin::<LoadedStamp>::default())
+            .add_event::<SaveStamp>();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<GpuStamp>()
+                .add_system(prepare_stamp.in_set(RenderSet::Prepare));
+        }
+    }
+}
+
+/// How a stamp is turned before being placed, quarter turns clockwise after mirroring.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StampTransform {
+    /// From `0` to `3`.
+    pub quarter_turns: u32,
+    /// Left to right.
+    pub mirrored: bool,
+}
+
+impl StampTransform {
+    /// Size of a stamp of `size` once turned.
+    pub fn apply_to_size(&self, size: UVec2) -> UVec2 {
+        if self.quarter_turns.is_multiple_of(2) {
+            size
+        } else {
+            UVec2::new(size.y, size.x)
+        }
+    }
+
+    /// The turns in the first two bits and the mirroring in the third, as read by the shaders.
+    pub fn bits(&self) -> u32 {
+        (self.quarter_turns % 4) | ((self.mirrored as u32) << 2)
+    }
+}
+
+/// The stamps saved on disk, and the one placed by the stamp tool.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StampLibrary {
+    pub dir: PathBuf,
+    /// Names of the stamps in `dir`, sorted.
+    names: Vec<String>,
+    /// Name of the stamp placed by the stamp tool.
+    pub selected: Option<String>,
+    /// Name the selection is saved as.
+    pub save_name: String,
+}
+
+impl StampLibrary {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// File of the stamp `name` in `dir`. Names can't hold separators or dots, which would place
+    /// the file outside of `dir` or lose the end of the name to the extension.
+    pub fn path_of(&self, name: &str) -> std::io::Result<PathBuf> {
+        if !is_valid_stamp_name(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("invalid stamp name {:?}", name),
+            ));
+        }
+        Ok(self.dir.join(format!("{}.{}", name, STAMP_EXTENSION)))
+    }
+
+    /// Lists the stamps in the directory again, it doesn't need to exist.
+    pub fn refresh(&mut self) {
+        let entries = std::fs::read_dir(&self.dir).into_iter().flatten().flatten();
+        self.names = entries
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == STAMP_EXTENSION))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .filter(|name| is_valid_stamp_name(name))
+            .collect();
+        self.names.sort();
+    }
+
+    /// Creates the directory if needed before writing a stamp.
+    pub fn save(&mut self, name: &str, data: &[u8]) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.path_of(name)?;
+        std::fs::write(&path, data)?;
+        self.refresh();
+        Ok(path)
+    }
+}
+
+/// Whether `name` can be saved in the library, see [`StampLibrary::path_of`].
+pub fn is_valid_stamp_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c == '.' || std::path::is_separator(c))
+}
+
+/// Cells from `min` included to `max` excluded of a simulation of `size`, covered by the corners
+/// of a selection.
+pub fn selection_region(corners: (Vec2, Vec2), size: UVec2) -> Option<(UVec2, UVec2)> {
+    let low = corners.0.min(corners.1).floor().max(Vec2::ZERO).as_uvec2();
+    let high = (corners.0.max(corners.1).floor() + 1.0)
+        .max(Vec2::ZERO)
+        .as_uvec2()
+        .min(size);
+    (high.x > low.x && high.y > low.y).then_some((low, high))
+}
+
+/// Cells from `min` included to `max` excluded of a row-major grid `width` cells wide.
+pub fn crop<T: Copy>(cells: &[T], width: u32, min: UVec2, max: UVec2) -> Vec<T> {
+    (min.y..max.y)
+        .flat_map(|y| {
+            let row = (y * width) as usize;
+            &cells[row + min.x as usize..row + max.x as usize]
+        })
+        .copied()
+        .collect()
+}
+
+/// Saves the cells from `min` included to `max` excluded as the stamp `name`, as they are at the
+/// start of the next frame.
+#[derive(Debug, Clone)]
+pub struct SaveStamp {
+    pub name: String,
+    pub min: UVec2,
+    pub max: UVec2,
+}
+
+/// Cells of a stamp as placed on the GPU, a kind per cell: a matter id or whether it is alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StampCells {
+    pub name: String,
+    pub size: UVec2,
+    pub cells: Vec<u32>,
+}
+
+/// The stamp placed by the stamp tool, loaded by the simulations when another one is selected.
+#[derive(Resource, ExtractResource, Debug, Clone, Default)]
+pub struct LoadedStamp(pub Option<Arc<StampCells>>);
+
+impl LoadedStamp {
+    pub fn size(&self) -> Option<UVec2> {
+        self.0.as_ref().map(|stamp| stamp.size)
+    }
+
+    /// Whether the selected stamp of the library still has to be loaded.
+    pub fn is_outdated(&self, library: &StampLibrary) -> bool {
+        self.0.as_ref().map(|stamp| &stamp.name) != library.selected.as_ref()
+    }
+}
+
+/// Reads the stamp `name` of the library, with `read` parsing the cells of the simulation.
+pub fn read_stamp_file<T, E: std::fmt::Display>(
+    library: &StampLibrary,
+    name: &str,
+    read: impl FnOnce(&[u8]) -> Result<T, E>,
+) -> Result<T, String> {
+    let path = library.path_of(name).map_err(|error| error.to_string())?;
+    std::fs::read(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|data| read(&data).map_err(|error| error.to_string()))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+// ================================== GPU ================================== //
+
+/// The [`LoadedStamp`] uploaded to the GPU, a single empty cell until one is loaded.
+#[derive(Resource)]
+pub struct GpuStamp {
+    pub buffer: Buffer,
+    source: Option<Arc<StampCells>>,
+}
+
+impl FromWorld for GpuStamp {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            buffer: create_stamp_buffer(world.resource::<RenderDevice>(), &[0]),
+            source: None,
+        }
+    }
+}
+
+fn create_stamp_buffer(device: &RenderDevice, cells: &[u32]) -> Buffer {
+    device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("Stamp"),
+        contents: bytemuck::cast_slice(cells),
+        usage: BufferUsages::STORAGE,
+    })
+}
+
+fn prepare_stamp(device: Res<RenderDevice>, loaded: Res<LoadedStamp>, mut gpu: ResMut<GpuStamp>) {
+    let is_uploaded = match (&loaded.0, &gpu.source) {
+        (Some(loaded), Some(source)) => Arc::ptr_eq(loaded, source),
+        (None, None) => true,
+        _ => false,
+    };
+    if is_uploaded {
+        return;
+    }
+
+    let cells = loaded.0.as_ref().map_or(&[0][..], |stamp| &stamp.cells);
+    gpu.buffer = create_stamp_buffer(&device, if cells.is_empty() { &[0] } else { cells });
+    gpu.source = loaded.0.clone();
+}
+
+// ================================== UI ================================== //
+
+/// Stamp library, shared by the UIs of the simulations. The selection is saved from the cells of a
+/// simulation of `size`.
+pub fn stamp_ui(
+    ui: &mut egui::Ui,
+    library: &mut StampLibrary,
+    brush: &mut Brush,
+    size: UVec2,
+    save_events: &mut EventWriter<SaveStamp>,
+) {
+    let region = brush
+        .selection()
+        .and_then(|corners| selection_region(corners, size));
+    match region {
+        Some((min, max)) => {
+            let size = max - min;
+            ui.label(format!(
+                "Selected {}x{} at {}, {}",
+                size.x, size.y, min.x, min.y
+            ))
+        }
+        None => ui.label("Drag with the Select tool to pick a region"),
+    };
+
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut library.save_name);
+        let can_save = region.is_some() && is_valid_stamp_name(library.save_name.trim());
+        if ui
+            .add_enabled(can_save, egui::Button::new("Save Stamp"))
+            .on_disabled_hover_text("Select a region and name it, without dots or slashes")
+            .clicked()
+        {
+            if let Some((min, max)) = region {
+                save_events.send(SaveStamp {
+                    name: library.save_name.trim().to_string(),
+                    min,
+                    max,
+                });
+            }
+        }
+    });
+
+    ui.separator();
+    if library.names().is_empty() {
+        ui.label(format!("No stamps in {}", library.dir.display()));
+    }
+    let mut picked = None;
+    for name in library.names() {
+        let is_selected = library.selected.as_ref() == Some(name);
+        if ui.selectable_label(is_selected, name).clicked() {
+            picked = Some(name.clone());
+        }
+    }
+    if let Some(name) = picked {
+        library.selected = Some(name);
+        brush.tool = BrushTool::Stamp;
+    }
+
+    ui.horizontal(|ui| {
+        let transform = &mut brush.stamp_transform;
+        if ui.button("Rotate").clicked() {
+            transform.quarter_turns = (transform.quarter_turns + 1) % 4;
+        }
+        ui.checkbox(&mut transform.mirrored, "Mirror");
+        if ui.button("Refresh").clicked() {
+            library.refresh();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn stamp_names_stay_in_the_library() {
+        let library = StampLibrary {
+            dir: PathBuf::from("stamps"),
+            ..default()
+        };
+        assert_eq!(
+            library.path_of("glider").unwrap(),
+            Path::new("stamps").join("glider.stamp")
+        );
+        for name in ["", "../x", "a/b", "a.b", ".."] {
+            assert!(library.path_of(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn odd_turns_swap_the_sides() {
+        let size = UVec2::new(3, 5);
+        for quarter_turns in 0..4 {
+            let transform = StampTransform {
+                quarter_turns,
+                mirrored: false,
+            };
+            let expected = if quarter_turns.is_multiple_of(2) {
+                size
+            } else {
+                UVec2::new(5, 3)
+            };
+            assert_eq!(transform.apply_to_size(size), expected);
+        }
+    }
+
+    #[test]
+    fn transforms_pack_into_the_shader_bits() {
+        let transform = |quarter_turns, mirrored| StampTransform {
+            quarter_turns,
+            mirrored,
+        };
+        assert_eq!(StampTransform::default().bits(), 0);
+        assert_eq!(transform(3, false).bits(), 0b011);
+        assert_eq!(transform(1, true).bits(), 0b101);
+        // Whole turns wrap around
+        assert_eq!(transform(5, false).bits(), 0b001);
+    }
+
+    #[test]
+    fn selections_include_both_corners_and_stay_in_the_canvas() {
+        let size = UVec2::new(16, 8);
+        assert_eq!(
+            selection_region((Vec2::new(5.5, 6.2), Vec2::new(2.1, 1.9)), size),
+            Some((UVec2::new(2, 1), UVec2::new(6, 7)))
+        );
+        assert_eq!(
+            selection_region((Vec2::new(-4.0, -4.0), Vec2::new(40.0, 40.0)), size),
+            Some((UVec2::ZERO, size))
+        );
+        assert_eq!(
+            selection_region((Vec2::new(3.0, 3.0), Vec2::new(3.9, 3.1)), size),
+            Some((UVec2::new(3, 3), UVec2::new(4, 4)))
+        );
+        assert_eq!(
+            selection_region((Vec2::new(-9.0, 2.0), Vec2::new(-1.0, 4.0)), size),
+            None
+        );
+        assert_eq!(
+            selection_region((Vec2::new(20.0, 2.0), Vec2::new(30.0, 4.0)), size),
+            None
+        );
+    }
+
+    #[test]
+    fn crops_keep_the_rows_in_order() {
+        let cells = (0..12).collect::<Vec<u32>>();
+        assert_eq!(
+            crop(&cells, 4, UVec2::new(1, 1), UVec2::new(3, 3)),
+            [5, 6, 9, 10]
+        );
+    }
+}
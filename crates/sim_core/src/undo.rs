@@ -0,0 +1,568 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_resource::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder},
+        renderer::RenderDevice,
+        RenderApp,
+    },
+};
+
+/// GPU memory the regions of the strokes may take, in megabytes.
+pub const INIT_UNDO_BUDGET_MB: u32 = 128;
+/// Most strokes kept however small they are.
+pub const MAX_UNDO_CAPACITY: usize = 64;
+
+pub struct StrokeHistoryPlugin;
+impl Plugin for StrokeHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StrokeHistory>()
+            .add_plugin(ExtractResourcePlugin::<StrokeHistory>::default())
+            .add_system(clear_stroke_command.in_base_set(CoreSet::First));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<GpuStrokeHistory>();
+        }
+    }
+}
+
+/// Cells painted by a stroke, from `min` included to `max` excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrokeRegion {
+    pub min: UVec2,
+    pub max: UVec2,
+}
+
+impl StrokeRegion {
+    const EMPTY: Self = Self {
+        min: UVec2::splat(u32::MAX),
+        max: UVec2::ZERO,
+    };
+
+    /// Cells a brush of `radius` may paint along the line from `start` to `end`, within `size`.
+    pub fn around_line(start: Vec2, end: Vec2, radius: f32, size: UVec2) -> Self {
+        // One more cell on each side for the rounding of the brush shapes
+        let margin = radius.max(0.0) + 1.0;
+        let min = (start.min(end) - margin).floor().max(Vec2::ZERO);
+        let max = (start.max(end) + margin).ceil() + 1.0;
+        Self {
+            min: min.as_uvec2().min(size),
+            max: max.max(Vec2::ZERO).as_uvec2().min(size),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max.x <= self.min.x || self.max.y <= self.min.y
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn size(&self) -> UVec2 {
+        UVec2::new(
+            self.max.x.saturating_sub(self.min.x),
+            self.max.y.saturating_sub(self.min.y),
+        )
+    }
+
+    pub fn num_of_cells(&self) -> u64 {
+        let size = self.size();
+        size.x as u64 * size.y as u64
+    }
+}
+
+/// A finished stroke that can be undone or redone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stroke {
+    pub region: StrokeRegion,
+    /// Slot of the [`GpuStrokeHistory`] holding the other version of the region.
+    pub slot: usize,
+}
+
+/// What the render world does with the [`GpuStrokeHistory`] this frame, before anything draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrokeCommand {
+    /// Copies the buffers aside, before the first draw of a stroke.
+    Begin,
+    /// Keeps the region of the copy made by [`StrokeCommand::Begin`] in the slot.
+    End { slot: usize, region: StrokeRegion },
+    /// Swaps the region of the buffers with the slot, undoing or redoing a stroke.
+    Swap { slot: usize, region: StrokeRegion },
+}
+
+/// Undo history of the brush strokes. Only the bounds of the strokes are known here, the cells
+/// they replaced stay on the GPU in the [`GpuStrokeHistory`].
+#[derive(Resource, ExtractResource, Debug, Clone)]
+pub struct StrokeHistory {
+    /// GPU memory the regions of the strokes may take, in megabytes. The oldest strokes can no
+    /// longer be undone past it.
+    pub budget_mb: u32,
+    /// Bytes of a cell across the buffers of the simulation.
+    cell_size: u64,
+    /// Finished strokes, oldest first.
+    strokes: VecDeque<Stroke>,
+    /// Number of strokes in `strokes` currently painted, the ones after them can be redone.
+    applied: usize,
+    /// Cells painted so far by the stroke in progress, `None` when not drawing.
+    current: Option<StrokeRegion>,
+    command: Option<StrokeCommand>,
+}
+
+impl Default for StrokeHistory {
+    fn default() -> Self {
+        Self {
+            budget_mb: INIT_UNDO_BUDGET_MB,
+            cell_size: 0,
+            strokes: VecDeque::new(),
+            applied: 0,
+            current: None,
+            command: None,
+        }
+    }
+}
+
+impl StrokeHistory {
+    pub fn strokes(&self) -> &VecDeque<Stroke> {
+        &self.strokes
+    }
+
+    pub fn command(&self) -> Option<StrokeCommand> {
+        self.command
+    }
+
+    /// Set by the simulations whenever their buffers are created.
+    pub fn set_cell_size(&mut self, bytes: u64) {
+        self.cell_size = bytes;
+    }
+
+    /// GPU memory taken by the regions of the recorded strokes, in bytes.
+    pub fn memory_used(&self) -> u64 {
+        self.strokes
+            .iter()
+            .map(|stroke| self.region_size(&stroke.region))
+            .sum()
+    }
+
+    fn region_size(&self, region: &StrokeRegion) -> u64 {
+        region.num_of_cells() * self.cell_size
+    }
+
+    /// Strokes are undone and redone between strokes, once the command of the frame is sent, as a
+    /// single command is run per frame.
+    pub fn can_undo(&self) -> bool {
+        self.current.is_none() && self.command.is_none() && self.applied > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.current.is_none() && self.command.is_none() && self.applied < self.strokes.len()
+    }
+
+    /// Forgets every stroke, e.g. when they no longer fit the buffers of the simulation.
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.applied = 0;
+        self.current = None;
+        self.command = None;
+    }
+
+    /// Follows the brush each frame: a stroke starts when drawing starts, grows with each segment
+    /// drawn from `start` to `end`, and is recorded when drawing stops.
+    pub fn track(&mut self, is_drawing: bool, start: Vec2, end: Vec2, radius: f32, size: UVec2) {
+        match (is_drawing, self.current) {
+            (true, current) => {
+                if current.is_none() {
+                    self.command = Some(StrokeCommand::Begin);
+                }
+                let region = StrokeRegion::around_line(start, end, radius, size);
+                self.current = Some(current.unwrap_or(StrokeRegion::EMPTY).union(&region));
+            }
+            (false, Some(region)) => {
+                self.current = None;
+                if !region.is_empty() {
+                    let slot = self.push(region);
+                    self.command = Some(StrokeCommand::End { slot, region });
+                }
+            }
+            (false, None) => {}
+        }
+    }
+
+    /// Brings back the cells under the last painted stroke.
+    pub fn undo(&mut self) {
+        if !self.can_undo() {
+            return;
+        }
+
+        self.applied -= 1;
+        let stroke = self.strokes[self.applied];
+        self.command = Some(StrokeCommand::Swap {
+            slot: stroke.slot,
+            region: stroke.region,
+        });
+    }
+
+    /// Paints the last undone stroke again.
+    pub fn redo(&mut self) {
+        if !self.can_redo() {
+            return;
+        }
+
+        let stroke = self.strokes[self.applied];
+        self.applied += 1;
+        self.command = Some(StrokeCommand::Swap {
+            slot: stroke.slot,
+            region: stroke.region,
+        });
+    }
+
+    /// Records a finished stroke, returning the slot it goes into. The undone strokes can no
+    /// longer be redone, and the oldest ones are dropped until the new one fits the budget. A
+    /// stroke larger than the whole budget is still kept on its own.
+    fn push(&mut self, region: StrokeRegion) -> usize {
+        self.strokes.truncate(self.applied);
+        let budget = self.budget_mb as u64 * 1024 * 1024;
+        let size = self.region_size(&region);
+        let mut used = self.memory_used();
+        while self.strokes.len() >= MAX_UNDO_CAPACITY || used + size > budget {
+            let Some(stroke) = self.strokes.pop_front() else {
+                break;
+            };
+            used -= self.region_size(&stroke.region);
+        }
+        let slot = (0..)
+            .find(|slot| self.strokes.iter().all(|stroke| stroke.slot != *slot))
+            .unwrap_or_default();
+
+        self.strokes.push_back(Stroke { region, slot });
+        self.applied = self.strokes.len();
+        slot
+    }
+}
+
+/// Commands only last a frame, the render world keeps the last extracted history.
+pub fn clear_stroke_command(mut history: ResMut<StrokeHistory>) {
+    if history.command.is_some() {
+        history.command = None;
+    }
+}
+
+// ================================== GPU ================================== //
+
+/// Cells replaced by the strokes. Every buffer of the simulation is copied aside when a stroke
+/// begins, and the region it painted is kept in its slot when it ends.
+#[derive(Resource, Default)]
+pub struct GpuStrokeHistory {
+    /// Width of the simulation in cells.
+    width: u32,
+    /// Bytes per cell of each buffer.
+    cell_sizes: Vec<u64>,
+    /// Copies of the buffers made when the stroke in progress began.
+    scratch: Vec<Buffer>,
+    /// Regions of the buffers, a set of buffers per slot of the [`StrokeHistory`].
+    slots: Vec<Vec<Buffer>>,
+    /// Holds the region of the buffers while a slot is swapped in.
+    swap: Vec<Buffer>,
+}
+
+impl GpuStrokeHistory {
+    /// Makes sure the buffers used this frame exist, for simulation buffers holding `cell_sizes`
+    /// bytes per cell, and frees the slots no stroke is recorded in. Run by the simulations while
+    /// preparing their buffers.
+    pub fn prepare(
+        &mut self,
+        device: &RenderDevice,
+        history: &StrokeHistory,
+        size: UVec2,
+        cell_sizes: &[u64],
+    ) {
+        if self.width != size.x || self.cell_sizes != cell_sizes {
+            // The copies no longer match the buffers, the history is cleared along with them
+            self.scratch.clear();
+            self.slots.clear();
+            self.swap.clear();
+            self.width = size.x;
+            self.cell_sizes = cell_sizes.to_vec();
+        }
+
+        // Left over once their strokes are dropped, e.g. past the budget or painted over
+        for (slot, buffers) in self.slots.iter_mut().enumerate() {
+            if !buffers.is_empty() && history.strokes.iter().all(|stroke| stroke.slot != slot) {
+                buffers.clear();
+            }
+        }
+        while self.slots.last().is_some_and(Vec::is_empty) {
+            self.slots.pop();
+        }
+
+        let create_buffers = |buffers: &mut Vec<Buffer>, cells: u64, label: &str| {
+            let fits = buffers.len() == cell_sizes.len()
+                && buffers
+                    .iter()
+                    .zip(cell_sizes)
+                    .all(|(buffer, cell_size)| buffer.size() == cells * cell_size);
+            if !fits {
+                *buffers = cell_sizes
+                    .iter()
+                    .map(|cell_size| {
+                        device.create_buffer(&BufferDescriptor {
+                            label: Some(label),
+                            size: cells * cell_size,
+                            usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                            mapped_at_creation: false,
+                        })
+                    })
+                    .collect();
+            }
+        };
+
+        match history.command {
+            Some(StrokeCommand::Begin) => {
+                let cells = size.x as u64 * size.y as u64;
+                create_buffers(&mut self.scratch, cells, "Stroke Scratch");
+            }
+            Some(StrokeCommand::End { slot, region }) => {
+                if self.slots.len() <= slot {
+                    self.slots.resize_with(slot + 1, Vec::new);
+                }
+                create_buffers(&mut self.slots[slot], region.num_of_cells(), "Stroke Slot");
+            }
+            Some(StrokeCommand::Swap { region, .. }) => {
+                create_buffers(&mut self.swap, region.num_of_cells(), "Stroke Swap");
+            }
+            None => {}
+        }
+    }
+
+    /// Runs the command of this frame on `buffers`, in the order given to
+    /// [`GpuStrokeHistory::prepare`]. Swapped regions are also written to `mirrors`, for the
+    /// simulations keeping the same cells in two sets of buffers.
+    pub fn run(
+        &self,
+        encoder: &mut CommandEncoder,
+        history: &StrokeHistory,
+        buffers: &[&Buffer],
+        mirrors: &[&Buffer],
+    ) {
+        match history.command {
+            Some(StrokeCommand::Begin) => {
+                for (buffer, scratch) in buffers.iter().zip(&self.scratch) {
+                    if buffer.size() == scratch.size() {
+                        encoder.copy_buffer_to_buffer(buffer, 0, scratch, 0, scratch.size());
+                    }
+                }
+            }
+            Some(StrokeCommand::End { slot, region }) => {
+                let Some(slot) = self.slots.get(slot) else {
+                    return;
+                };
+                for ((scratch, slot), cell_size) in
+                    self.scratch.iter().zip(slot).zip(&self.cell_sizes)
+                {
+                    self.copy_region(encoder, region, *cell_size, scratch, slot, false);
+                }
+            }
+            Some(StrokeCommand::Swap { slot, region }) => {
+                let Some(slot) = self.slots.get(slot) else {
+                    return;
+                };
+                for (index, ((slot, swap), cell_size)) in slot
+                    .iter()
+                    .zip(&self.swap)
+                    .zip(&self.cell_sizes)
+                    .enumerate()
+                {
+                    if slot.size() != swap.size() {
+                        continue;
+                    }
+
+                    let Some(buffer) = buffers.get(index) else {
+                        continue;
+                    };
+                    self.copy_region(encoder, region, *cell_size, buffer, swap, false);
+                    self.copy_region(encoder, region, *cell_size, slot, buffer, true);
+                    if let Some(mirror) = mirrors.get(index) {
+                        self.copy_region(encoder, region, *cell_size, slot, mirror, true);
+                    }
+                    encoder.copy_buffer_to_buffer(swap, 0, slot, 0, slot.size());
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Copies the rows of `region` from a buffer of the whole simulation into a buffer of the
+    /// region only, or the other way around when `into_simulation` is set.
+    fn copy_region(
+        &self,
+        encoder: &mut CommandEncoder,
+        region: StrokeRegion,
+        cell_size: u64,
+        source: &Buffer,
+        target: &Buffer,
+        into_simulation: bool,
+    ) {
+        if region.is_empty() || region.max.x > self.width {
+            return;
+        }
+
+        let row_size = region.size().x as u64 * cell_size;
+        let simulation = if into_simulation { target } else { source };
+        let last_row_end =
+            ((region.max.y as u64 - 1) * self.width as u64 + region.max.x as u64) * cell_size;
+        if simulation.size() < last_row_end
+            || source.size().min(target.size()) < region.num_of_cells() * cell_size
+        {
+            return;
+        }
+
+        for (row, y) in (region.min.y..region.max.y).enumerate() {
+            let simulation_offset =
+                (y as u64 * self.width as u64 + region.min.x as u64) * cell_size;
+            let region_offset = row as u64 * row_size;
+            let (source_offset, target_offset) = if into_simulation {
+                (region_offset, simulation_offset)
+            } else {
+                (simulation_offset, region_offset)
+            };
+            encoder.copy_buffer_to_buffer(source, source_offset, target, target_offset, row_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: UVec2 = UVec2::splat(64);
+
+    /// Paints a stroke at `x` over two frames, returning the command that ends it.
+    fn paint(history: &mut StrokeHistory, x: f32) -> Option<StrokeCommand> {
+        let pos = Vec2::new(x, 8.0);
+        history.track(true, pos, pos, 1.0, SIZE);
+        assert_eq!(history.command, Some(StrokeCommand::Begin));
+        history.command = None;
+        history.track(false, pos, pos, 1.0, SIZE);
+        history.command.take()
+    }
+
+    /// A history whose budget fits `count` strokes painted by [`paint`], of 5x5 cells each.
+    fn history_fitting(count: u64) -> StrokeHistory {
+        let mut history = StrokeHistory {
+            budget_mb: 1,
+            ..default()
+        };
+        history.set_cell_size(1024 * 1024 / (25 * count));
+        history
+    }
+
+    fn slots(history: &StrokeHistory) -> Vec<usize> {
+        history.strokes().iter().map(|stroke| stroke.slot).collect()
+    }
+
+    #[test]
+    fn oldest_strokes_are_dropped_and_their_slots_reused() {
+        let mut history = history_fitting(2);
+        for x in [4.0, 16.0, 32.0] {
+            assert!(matches!(
+                paint(&mut history, x),
+                Some(StrokeCommand::End { .. })
+            ));
+        }
+        assert_eq!(slots(&history), [1, 0]);
+        assert_eq!(history.strokes()[1].region.min.x, 30);
+    }
+
+    #[test]
+    fn strokes_larger_than_the_budget_are_kept_alone() {
+        let mut history = StrokeHistory {
+            budget_mb: 0,
+            ..default()
+        };
+        history.set_cell_size(4);
+        for x in [4.0, 16.0, 32.0] {
+            paint(&mut history, x);
+        }
+        assert_eq!(slots(&history), [0]);
+        assert_eq!(history.memory_used(), 25 * 4);
+    }
+
+    #[test]
+    fn small_strokes_are_capped_in_number() {
+        let mut history = StrokeHistory::default();
+        history.set_cell_size(4);
+        for x in 0..=MAX_UNDO_CAPACITY {
+            paint(&mut history, x as f32 % 32.0);
+        }
+        assert_eq!(history.strokes().len(), MAX_UNDO_CAPACITY);
+    }
+
+    #[test]
+    fn painting_after_undo_drops_the_undone_strokes() {
+        let mut history = StrokeHistory::default();
+        for x in [4.0, 16.0, 32.0] {
+            paint(&mut history, x);
+        }
+        history.undo();
+        history.command = None;
+        history.undo();
+        history.command = None;
+        assert!(history.can_redo());
+
+        let Some(StrokeCommand::End { slot, .. }) = paint(&mut history, 48.0) else {
+            panic!("the stroke wasn't recorded");
+        };
+        assert_eq!(slot, 1);
+        assert_eq!(slots(&history), [0, 1]);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn undo_waits_for_the_pending_command() {
+        let mut history = StrokeHistory::default();
+        let pos = Vec2::splat(8.0);
+        history.track(true, pos, pos, 1.0, SIZE);
+        history.command = None;
+        history.track(false, pos, pos, 1.0, SIZE);
+        let end = history.command;
+
+        history.undo();
+        assert_eq!(history.command, end);
+        history.command = None;
+        history.undo();
+        assert!(matches!(
+            history.command,
+            Some(StrokeCommand::Swap { slot: 0, .. })
+        ));
+        history.redo();
+        assert!(!history.can_redo() && !history.can_undo());
+    }
+
+    #[test]
+    fn regions_cover_the_brush_along_the_line() {
+        let region =
+            StrokeRegion::around_line(Vec2::new(20.0, 10.0), Vec2::new(10.0, 12.0), 2.0, SIZE);
+        assert_eq!(region.min, UVec2::new(7, 7));
+        assert_eq!(region.max, UVec2::new(24, 16));
+        assert_eq!(region.num_of_cells(), 17 * 9);
+    }
+
+    #[test]
+    fn regions_are_clamped_to_the_canvas() {
+        let region =
+            StrokeRegion::around_line(Vec2::new(1.0, 2.0), Vec2::new(62.0, 63.0), 4.0, SIZE);
+        assert_eq!(region.min, UVec2::ZERO);
+        assert_eq!(region.max, SIZE);
+
+        let before = StrokeRegion::around_line(Vec2::splat(-20.0), Vec2::splat(-10.0), 1.0, SIZE);
+        assert!(before.is_empty());
+        let after = StrokeRegion::around_line(Vec2::splat(100.0), Vec2::splat(90.0), 1.0, SIZE);
+        assert!(after.is_empty());
+        assert_eq!(after.num_of_cells(), 0);
+    }
+}
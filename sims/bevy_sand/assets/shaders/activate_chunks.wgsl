@@ -0,0 +1,49 @@
+#import bevy_sand::core
+
+// Indirect dispatch arguments of the movement passes, only bound here as the movement passes can't
+// bind the buffer they are dispatched with
+struct DispatchArgs {
+    workgroups_x: atomic<u32>,
+    workgroups_y: u32,
+    workgroups_z: u32,
+}
+
+@group(1) @binding(0)
+var<storage, read_write> dispatch_args : DispatchArgs;
+
+// A chunk is active when a cell of it or of a neighboring chunk changed last step, as matter can
+// only move in or out of a chunk next to one that changed. The draw pass uses the index of the
+// first step of the frame, so painting wakes chunks up right away
+fn is_awake(chunk: vec2<i32>) -> bool {
+	let grid: vec2<i32> = chunk_grid_size();
+	for (var y: i32 = -1; y <= 1; y++) {
+		for (var x: i32 = -1; x <= 1; x++) {
+			var neighbor: vec2<i32> = chunk + vec2<i32>(x, y);
+			if (pc.boundary == BOUNDARY_WRAP) {
+				neighbor = (neighbor % grid + grid) % grid;
+			}
+			let is_inside: bool = all(neighbor >= vec2<i32>(0, 0)) && all(neighbor < grid);
+			if (is_inside && atomicLoad(&chunks[get_chunk_index(neighbor)].dirty) + 1u >= pc.sim_steps) {
+				return true;
+			}
+		}
+	}
+	return false;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
+{
+	let chunk = vec2<i32>(invocation_id.xy);
+	if (any(chunk >= chunk_grid_size())) {
+		return;
+	}
+
+	let index: i32 = get_chunk_index(chunk);
+	let is_active: bool = is_awake(chunk);
+	chunks[index].awake = u32(is_active);
+	if (is_active) {
+		let slot: u32 = atomicAdd(&dispatch_args.workgroups_x, WORKGROUPS_PER_CHUNK) / WORKGROUPS_PER_CHUNK;
+		active_chunks[slot] = u32(index);
+	}
+}
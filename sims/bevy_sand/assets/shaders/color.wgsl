@@ -5,5 +5,14 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
 {
     let location = vec2<i32>(invocation_id.xy);
     let matter = read_matter(location);
-    textureStore(texture, location, matter.color);
+    var color = matter.color;
+    // Show painted wind faintly on empty cells
+    if (is_empty(matter)) {
+        color = vec4<f32>(color.rgb + 0.15 * min(length(read_wind(location)), 1.0), color.a);
+    }
+    textureStore(texture, location, color);
+
+    // The next frame reads its input from the other buffers
+    write_matter(location, matter);
+    write_temperature(location, read_temperature(location));
 }
\ No newline at end of file
@@ -1,21 +1,5 @@
 #import bevy_sand::core
 
-fn vary_color_rgb(color: vec4<f32>, seed_pos: vec2<i32>) -> vec4<f32> {
-	let seed: f32 = 0.1;
-	let p: f32 = rand(seed_pos, seed);
-	let variation: f32 = -0.1 + 0.2 * p;
-    var c = color;
-	c.r = c.r + variation;
-	c.g = c.g + variation;
-	c.b = c.b + variation;
-	return c;
-}
-
-fn variate_color(pos: vec2<i32>, color_f32: vec4<f32>) -> vec4<f32> {
-	return vary_color_rgb(color_f32, pos);
-}
-
-
 // Line v->w, point p
 // https://stackoverflow.com/questions/849211/shortest-distance-between-a-point-and-a-line-segment
 fn closest_point_on_line(v: vec2<f32>, w: vec2<f32>, p: vec2<f32>) -> vec2<f32> {
@@ -34,14 +18,34 @@ fn color_matter_at(pos: vec2<i32>, matter: Matter){
     var m = matter;
 
     // 3. Vary color only if not empty
-    if(!is_empty(m)) { 
-        m.color = variate_color(pos, m.color); 
+    if(!is_empty(m)) {
+        m = new_matter_at(pos, m.id);
     }
 
     write_matter_input(pos, m);
+    write_temperature_input(pos, matter_definition(m.id).temperature);
+}
+
+fn heat_at(pos: vec2<i32>) {
+    write_temperature_input(pos, read_temperature(pos) + pc.draw_heat);
+}
+
+fn paint_at(pos: vec2<i32>, matter: Matter) {
+    if (pc.brush == BRUSH_HEAT) {
+        heat_at(pos);
+    } else if (pc.brush == BRUSH_WIND) {
+        // Keep the painted wind while the mouse is held still
+        if (length(pc.draw_wind) > 0.0) {
+            write_wind(pos, pc.draw_wind);
+        }
+    } else if (pc.brush == BRUSH_CALM) {
+        write_wind(pos, vec2<f32>(0.0, 0.0));
+    } else {
+        color_matter_at(pos, matter);
+    }
 }
 
-fn draw_particle_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32, matter: Matter) {
+fn is_in_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32) -> bool {
     let y_start = draw_pos.y - radius;
     let y_end = draw_pos.y + radius;
     let x_start = draw_pos.x - radius;
@@ -49,20 +53,91 @@ fn draw_particle_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32, matter
     if (pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end) {
         let diff = pos - draw_pos;
         let dist = length(diff);
-        if (round(dist) <= radius) {
-            color_matter_at(vec2<i32>(pos), matter);
-        }
+        return round(dist) <= radius;
     }
+    return false;
 }
 
-fn draw_particle_square(pos: vec2<f32>, draw_pos: vec2<f32>, size: f32, matter: Matter)  {
+fn is_in_square(pos: vec2<f32>, draw_pos: vec2<f32>, size: f32) -> bool {
 	let y_start = draw_pos.y - size / 2.;
 	let y_end = draw_pos.y + size / 2.;
 	let x_start = draw_pos.x - size / 2.;
 	let x_end = draw_pos.x + size / 2.;
-	if (pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end) {
-        color_matter_at(vec2<i32>(pos), matter);
-	}
+	return pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end;
+}
+
+// Shapes are outlined as thick as the brush size, at least a cell
+fn is_in_rectangle(pos: vec2<f32>, corner_a: vec2<f32>, corner_b: vec2<f32>) -> bool {
+    let low = min(corner_a, corner_b);
+    let high = max(corner_a, corner_b);
+    if (any(pos < low) || any(pos > high)) {
+        return false;
+    }
+    let edge = min(pos - low, high - pos);
+    return bool(pc.filled) || min(edge.x, edge.y) < max(pc.draw_radius, 1.0);
+}
+
+fn is_in_ellipse(pos: vec2<f32>, corner_a: vec2<f32>, corner_b: vec2<f32>) -> bool {
+    let center = (corner_a + corner_b) / 2.0;
+    let radii = max(abs(corner_b - corner_a) / 2.0, vec2<f32>(0.5, 0.5));
+    let dist = length((pos - center) / radii);
+    if (dist > 1.0) {
+        return false;
+    }
+    return bool(pc.filled) || (1.0 - dist) * min(radii.x, radii.y) < max(pc.draw_radius, 1.0);
+}
+
+// Whether the brush covers the cell at `pos` this frame
+fn is_brushed(pos: vec2<f32>) -> bool {
+    if (pc.tool == TOOL_RECTANGLE) {
+        return is_in_rectangle(pos, pc.draw_start, pc.draw_end);
+    }
+    if (pc.tool == TOOL_ELLIPSE) {
+        return is_in_ellipse(pos, pc.draw_start, pc.draw_end);
+    }
+
+    // Freehand, spray and lines stamp the brush along the segment
+    let point_on_line = closest_point_on_line(pc.draw_start, pc.draw_end, pos);
+    var is_stamped: bool;
+    if (bool(pc.draw_square)) {
+        is_stamped = is_in_square(pos, point_on_line, pc.draw_radius);
+    } else {
+        is_stamped = is_in_circle(pos, point_on_line, pc.draw_radius);
+    }
+    if (pc.tool == TOOL_SPRAY) {
+        return is_stamped && rand_at(vec2<i32>(pos), pc.brush_seed) < pc.spray_density;
+    }
+    return is_stamped;
+}
+
+// Heat and wind can be painted on anything, where matter goes depends on the paint mode
+fn paint_if_allowed(pos: vec2<i32>, matter: Matter) {
+    var draw_matter = matter;
+    if (pc.brush != BRUSH_MATTER) {
+        paint_at(pos, draw_matter);
+        return;
+    }
+
+    let matter_at = read_matter(pos);
+    if (pc.paint_mode == PAINT_REPLACE_MATTER || pc.paint_mode == PAINT_ERASE_MATTER) {
+        if (matter_at.id != pc.mask_matter) {
+            return;
+        }
+        if (pc.paint_mode == PAINT_ERASE_MATTER) {
+            draw_matter = EMPTY_MATTER;
+        }
+    } else if (pc.paint_mode != PAINT_REPLACE_ALL) {
+        // Fills empty cells, erasing works anywhere
+        if (!is_empty(matter_at) && !is_empty(draw_matter)) {
+            return;
+        }
+    }
+
+    // Painting over the same matter would reset it every frame
+    if (pc.paint_mode != PAINT_FILL_EMPTY && matter_at.id == draw_matter.id) {
+        return;
+    }
+    paint_at(pos, draw_matter);
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -74,18 +149,111 @@ fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
         return ;
     }
 
-    if (pc.draw_radius > 0.0) {
-        let pos = vec2<f32>(pixel);
-        let point_on_line = closest_point_on_line(pc.draw_start, pc.draw_end, pos);
-        let matter_at = read_matter(pixel);
-        let draw_matter = pc.matter;
+    if (pc.draw_radius > 0.0 && is_brushed(vec2<f32>(pixel))) {
+        paint_if_allowed(pixel, new_matter(pc.draw_matter));
+    }
+}
+
+/*
+FLOOD FILL
+Spreads over the cells of the same matter as the clicked one, a cell at a time per pass. Each cell
+is painted once, on the pass it is reached. The cells reached are counted, the fill is done once a
+frame of passes reaches none.
+*/
+const FLOOD_UNVISITED: u32 = 0u;
+const FLOOD_REACHED: u32 = 1u;
+const FLOOD_PAINTED: u32 = 2u;
+
+fn is_flooded(pos: vec2<i32>) -> bool {
+    let size = sim_canvas_size();
+    if (any(pos < vec2<i32>(0, 0)) || any(pos >= size)) {
+        return false;
+    }
+    return atomicLoad(&flood.cells[get_index(pos)]) != FLOOD_UNVISITED;
+}
+
+// Run once on the cleared states, before the first pass
+@compute @workgroup_size(1, 1, 1)
+fn flood_seed() {
+    flood.id = pc.brush_seed;
+    let seed = vec2<i32>(pc.draw_start);
+    let size = sim_canvas_size();
+    if (any(seed < vec2<i32>(0, 0)) || any(seed >= size)) {
+        return;
+    }
+
+    flood.kind = read_matter(seed).id;
+    atomicStore(&flood.cells[get_index(seed)], FLOOD_REACHED);
+}
 
-        if(matter_at.id == empty_matter || draw_matter.id == empty_matter) {
-            if (bool(pc.draw_square)){
-                draw_particle_square(pos, point_on_line, pc.draw_radius,draw_matter);
-            }else{
-                draw_particle_circle(pos, point_on_line, pc.draw_radius,draw_matter);
-            }
+@compute @workgroup_size(8, 8, 1)
+fn flood_fill(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let pos = vec2<i32>(invocation_id.xy);
+    let size = sim_canvas_size();
+    if (pos.x >= size.x || pos.y >= size.y) {
+        return;
+    }
+
+    let index = get_index(pos);
+    var state = atomicLoad(&flood.cells[index]);
+    if (state == FLOOD_UNVISITED && read_matter(pos).id == flood.kind) {
+        let is_reached = is_flooded(pos + vec2<i32>(1, 0))
+            || is_flooded(pos - vec2<i32>(1, 0))
+            || is_flooded(pos + vec2<i32>(0, 1))
+            || is_flooded(pos - vec2<i32>(0, 1));
+        if (is_reached) {
+            state = FLOOD_REACHED;
+            atomicAdd(&flood.reached, 1u);
         }
     }
-}
\ No newline at end of file
+
+    if (state == FLOOD_REACHED) {
+        paint_if_allowed(pos, new_matter(pc.draw_matter));
+        atomicStore(&flood.cells[index], FLOOD_PAINTED);
+    }
+}
+
+/*
+STAMP
+Places the stamp centered on `draw_start`, mirrored then turned as `stamp_transform` tells.
+*/
+
+// Index in the stamp of the cell placed at `pos`, or -1 outside of it
+fn stamp_index(pos: vec2<i32>) -> i32 {
+    let quarter_turns = pc.stamp_transform & 3u;
+    var turned_size = vec2<i32>(pc.stamp_size);
+    if (quarter_turns % 2u == 1u) {
+        turned_size = turned_size.yx;
+    }
+
+    var cell = pos - (vec2<i32>(floor(pc.draw_start)) - turned_size / 2);
+    if (any(cell < vec2<i32>(0, 0)) || any(cell >= turned_size)) {
+        return -1;
+    }
+
+    // Undo the turns one at a time, then the mirroring
+    var cell_size = turned_size;
+    for (var i = 0u; i < quarter_turns; i = i + 1u) {
+        cell = vec2<i32>(cell.y, cell_size.x - 1 - cell.x);
+        cell_size = cell_size.yx;
+    }
+    if ((pc.stamp_transform & 4u) != 0u) {
+        cell.x = cell_size.x - 1 - cell.x;
+    }
+    return cell.y * i32(pc.stamp_size.x) + cell.x;
+}
+
+// Empty cells of the stamp leave the world as it is, the others are painted as the paint mode allows
+@compute @workgroup_size(8, 8, 1)
+fn place_stamp(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let pos = vec2<i32>(invocation_id.xy);
+    let size = sim_canvas_size();
+    if (pos.x >= size.x || pos.y >= size.y) {
+        return;
+    }
+
+    let index = stamp_index(pos);
+    if (index >= 0 && stamp[index] != EMPTY_MATTER.id) {
+        paint_if_allowed(pos, new_matter(stamp[index]));
+    }
+}
@@ -1,15 +1,39 @@
 #import bevy_sand::core
 
+// Falls happen on the first move step only, the distance comes from the velocity so the falling
+// speed doesn't depend on the number of move steps
 fn fall_empty(pos: vec2<i32>)  {
 	let current: Matter = read_matter(pos);
-	let up: Matter = get_neighbor(pos, UP);
-	let down: Matter = get_neighbor(pos, DOWN);
 	var m: Matter = current;
 
-	if (!is_at_border_top(pos) && falls_on_empty(up, current)) {
-		m = up;
-	} else if (!is_at_border_bottom(pos) && falls_on_empty(current, down)){
-		m = down;
+	if (pc.move_step != 0u) {
+		write_matter(pos, m);
+		return;
+	}
+
+	if (is_empty(current)) {
+		// The first matter above lands here if its fall ends here
+		for (var k: i32 = 1; k <= MAX_FALL_SPEED; k++) {
+			let from_pos: vec2<i32> = pos - gravity_offset() * k;
+			if (!is_inside_boundary(from_pos)) {
+				break;
+			}
+			let above: Matter = read_matter_at(from_pos);
+			if (!is_empty(above)) {
+				if (fall_distance(from_pos, above) == k) {
+					m = above;
+					m.velocity = velocity_after_fall(from_pos, above, k);
+				}
+				break;
+			}
+		}
+	} else if (is_gravity(current)) {
+		let distance: i32 = fall_distance(pos, current);
+		if (distance > 0) {
+			m = read_matter_at(pos + gravity_offset() * distance);
+		} else {
+			m.velocity = velocity_after_fall(pos, current, 0);
+		}
 	}
 	
 	write_matter(pos, m);
@@ -17,5 +41,5 @@ fn fall_empty(pos: vec2<i32>)  {
 
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)  {
-	fall_empty(get_current_sim_pos(invocation_id));
-} 
\ No newline at end of file
+	fall_empty(get_active_sim_pos(invocation_id));
+} 
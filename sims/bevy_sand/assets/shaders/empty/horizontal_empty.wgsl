@@ -6,7 +6,7 @@ fn cellular_automata_move_left_empty(pos: vec2<i32>)  {
 	var right: Matter = get_neighbor(pos, RIGHT);
 	var left: Matter = get_neighbor(pos, LEFT);
 	let down_right: Matter = get_neighbor(pos, DOWN_RIGHT);
-	let right_right: Matter = get_neighbor(get_pos_at_dir(pos, RIGHT), RIGHT);
+	let right_right: Matter = get_neighbor(get_pos_towards(pos, RIGHT), RIGHT);
 	var m: Matter = current;
 
 	if(!is_at_border_right(pos) && moves_on_empty_certainly(right, current, right_right, down_right)) {
@@ -14,9 +14,9 @@ fn cellular_automata_move_left_empty(pos: vec2<i32>)  {
 	} else if(!is_at_border_left(pos) && moves_on_empty_certainly(current, left, right, down)) {
 		m = left;
 	} else if(!is_at_border_right(pos) 
-		&& moves_on_empty_maybe(right, current, right_right, down_right, rand(get_pos_at_dir(pos, RIGHT), pc.seed))) {
+		&& moves_on_empty_maybe(right, current, right_right, down_right, dispersion_chance(get_pos_towards(pos, RIGHT), LEFT))) {
 		m = right;
-	} else if(!is_at_border_left(pos) && moves_on_empty_maybe(current, left, right, down, rand(pos, pc.seed))) {
+	} else if(!is_at_border_left(pos) && moves_on_empty_maybe(current, left, right, down, dispersion_chance(pos, LEFT))) {
 		m = left;
 	}
 
@@ -29,7 +29,7 @@ fn cellular_automata_move_right_empty(pos: vec2<i32>)  {
 	let right: Matter = get_neighbor(pos, RIGHT);
 	let left: Matter = get_neighbor(pos, LEFT);
 	let down_left: Matter = get_neighbor(pos, DOWN_LEFT);
-	let left_left: Matter = get_neighbor(get_pos_at_dir(pos, LEFT), LEFT);
+	let left_left: Matter = get_neighbor(get_pos_towards(pos, LEFT), LEFT);
 	var m: Matter = current;
 
 	if(!is_at_border_left(pos) && moves_on_empty_certainly(left, current, left_left, down_left)) {
@@ -37,9 +37,9 @@ fn cellular_automata_move_right_empty(pos: vec2<i32>)  {
 	} else if(!is_at_border_right(pos) && moves_on_empty_certainly(current, right, left, down)) {
 		m = right;
 	} else if(!is_at_border_left(pos) 
-		&& moves_on_empty_maybe(left, current, left_left, down_left, rand(get_pos_at_dir(pos, LEFT), pc.seed))) {
+		&& moves_on_empty_maybe(left, current, left_left, down_left, dispersion_chance(get_pos_towards(pos, LEFT), RIGHT))) {
 		m = left;
-	} else if(!is_at_border_right(pos) && moves_on_empty_maybe(current, right, left, down, rand(pos, pc.seed))) {
+	} else if(!is_at_border_right(pos) && moves_on_empty_maybe(current, right, left, down, dispersion_chance(pos, RIGHT))) {
 		m = right;
 	}
 
@@ -56,5 +56,5 @@ fn cellular_automata_move_horizontal_empty(pos: vec2<i32>)  {
 
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>){
-	cellular_automata_move_horizontal_empty(get_current_sim_pos(invocation_id));
+	cellular_automata_move_horizontal_empty(get_active_sim_pos(invocation_id));
 } 
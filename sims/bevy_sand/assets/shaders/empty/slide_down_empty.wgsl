@@ -44,5 +44,5 @@ fn cellular_automata_slide_down_empty(pos: vec2<i32>)  {
 @compute @workgroup_size(8, 8, 1)
 fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
 {
-	cellular_automata_slide_down_empty(get_current_sim_pos(invocation_id));
+	cellular_automata_slide_down_empty(get_active_sim_pos(invocation_id));
 } 
\ No newline at end of file
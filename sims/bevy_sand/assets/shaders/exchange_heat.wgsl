@@ -0,0 +1,43 @@
+#import bevy_sand::core
+
+// Heat flows from and to the 4 direct neighbors, limited by the least conductive of the two matters.
+// It wraps around with the wrap boundary and doesn't leave the canvas otherwise
+fn diffuse_heat(pos: vec2<i32>, current: Matter) -> f32 {
+	let temperature: f32 = read_temperature(pos);
+	let conductivity: f32 = matter_definition(current.id).conductivity;
+	var flow: f32 = 0.0;
+
+	// UP, RIGHT, DOWN, LEFT
+	for (var dir: i32 = UP; dir <= LEFT; dir += 2) {
+		let neighbor_pos: vec2<i32> = boundary_pos(get_pos_at_dir(pos, dir));
+		if (is_inside_sim_canvas(neighbor_pos)) {
+			let neighbor_conductivity: f32 = matter_definition(read_matter(neighbor_pos).id).conductivity;
+			flow += (read_temperature(neighbor_pos) - temperature) * min(conductivity, neighbor_conductivity);
+		}
+	}
+
+	return temperature + 0.25 * flow;
+}
+
+fn phase_transition(pos: vec2<i32>, current: Matter, temperature: f32) -> Matter {
+	let definition: MatterDefinition = matter_definition(current.id);
+
+	if (definition.heated_into != NO_TRANSITION && temperature >= definition.heated_temperature) {
+		return new_matter_at(pos, definition.heated_into);
+	} 
+	if (definition.cooled_into != NO_TRANSITION && temperature <= definition.cooled_temperature) {
+		return new_matter_at(pos, definition.cooled_into);
+	}
+	return current;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
+{
+	let pos = get_current_sim_pos(invocation_id);
+	let current: Matter = read_matter(pos);
+	let temperature: f32 = diffuse_heat(pos, current);
+
+	write_matter(pos, phase_transition(pos, current, temperature));
+	write_temperature(pos, temperature);
+}
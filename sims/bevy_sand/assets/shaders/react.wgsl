@@ -0,0 +1,96 @@
+#import bevy_sand::core
+
+// Each cell is paired with a single neighbor, alternating between horizontal and vertical pairs and
+// their offset every step
+fn reaction_partner(pos: vec2<i32>) -> vec2<i32> {
+	let config: u32 = pc.sim_steps % 4u;
+	var axis: vec2<i32> = vec2<i32>(0, 1);
+	var coord: i32 = pos.y;
+	if (config < 2u) {
+		axis = vec2<i32>(1, 0);
+		coord = pos.x;
+	}
+
+	if ((coord + i32(config % 2u)) % 2 == 0) {
+		return pos + axis;
+	}
+	return pos - axis;
+}
+
+fn is_reactant(reactant: u32, other_reactant: u32, m: Matter) -> bool {
+	if (reactant == ANY_MATTER) {
+		return !is_empty(m) && m.id != other_reactant;
+	}
+	return m.id == reactant;
+}
+
+// Products identical to their reactant are kept as is, so a burning cell spreading fire keeps its
+// lifetime
+fn react_into(pos: vec2<i32>, current: Matter, product: u32) -> Matter {
+	if (current.id == product) {
+		return current;
+	}
+	return new_matter_at(pos, product);
+}
+
+fn react(pos: vec2<i32>) -> Matter {
+	let current: Matter = read_matter(pos);
+	let partner_pos: vec2<i32> = reaction_partner(pos);
+	let partner_cell: vec2<i32> = boundary_pos(partner_pos);
+	// Pairs wrapping around a canvas with an odd size don't match up
+	if (!is_inside_sim_canvas(partner_cell) || any(boundary_pos(reaction_partner(partner_cell)) != pos)) {
+		return current;
+	}
+	let partner: Matter = read_matter(partner_cell);
+
+	// Both cells of a pair roll the same number and find the same first reaction, so they agree on
+	// whether it happens
+	let p: f32 = rand(boundary_pos(min(pos, partner_pos)));
+	for (var i: u32 = 0u; i < arrayLength(&reactions); i++) {
+		let reaction: Reaction = reactions[i];
+		if (is_reactant(reaction.reactant_a, reaction.reactant_b, current) 
+			&& is_reactant(reaction.reactant_b, reaction.reactant_a, partner)) {
+			if (p < reaction.probability) {
+				return react_into(pos, current, reaction.product_a);
+			}
+			return current;
+		}
+		if (is_reactant(reaction.reactant_b, reaction.reactant_a, current) 
+			&& is_reactant(reaction.reactant_a, reaction.reactant_b, partner)) {
+			if (p < reaction.probability) {
+				return react_into(pos, current, reaction.product_b);
+			}
+			return current;
+		}
+	}
+
+	return current;
+}
+
+// Matters with a decay get a random lifetime on their first tick, then decay once it runs out
+fn age(pos: vec2<i32>, current: Matter) -> Matter {
+	let definition: MatterDefinition = matter_definition(current.id);
+	if (definition.decays_into == NO_TRANSITION) {
+		return current;
+	}
+
+	var m: Matter = current;
+	if (m.lifetime == 0u) {
+		let lifetime_range: u32 = definition.max_lifetime - definition.min_lifetime + 1u;
+		m.lifetime = definition.min_lifetime + u32(rand(pos) * f32(lifetime_range));
+	}
+
+	if (m.lifetime <= 1u) {
+		return new_matter_at(pos, definition.decays_into);
+	}
+	m.lifetime -= 1u;
+	return m;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) invocation_id: vec3<u32>)
+{
+	let pos = get_current_sim_pos(invocation_id);
+	write_matter(pos, age(pos, react(pos)));
+	write_temperature(pos, read_temperature(pos));
+}
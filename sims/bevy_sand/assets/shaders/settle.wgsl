@@ -0,0 +1,22 @@
+#import bevy_sand::core
+
+// Same buffer as in `activate_chunks.wgsl`, nothing else writes to it while this pass runs
+struct DispatchArgs {
+    workgroups_x: u32,
+    workgroups_y: u32,
+    workgroups_z: u32,
+}
+
+@group(1) @binding(0)
+var<storage, read_write> dispatch_args : DispatchArgs;
+
+// Runs after each movement step of the adaptive schedule. Once a movement step moved nothing the
+// world has settled, so the remaining movement passes of the step are dispatched without workgroups
+@compute @workgroup_size(1, 1, 1)
+fn main()
+{
+	if (atomicLoad(&moved) == 0u) {
+		dispatch_args.workgroups_x = 0u;
+	}
+	atomicStore(&moved, 0u);
+}
@@ -0,0 +1,271 @@
+//! Runs the sand simulation without a window: loads a world, advances it and saves the result.
+//! Runs on the GPU when there is an adapter, otherwise on the [`CpuSandWorld`], which only moves
+//! matter and leaves temperatures, reactions and decay aside. Worlds whose matters need them are
+//! refused on the CPU.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bevy::prelude::*;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use bevy_sand::constants::AMBIENT_TEMPERATURE;
+use bevy_sand::cpu::CpuSandWorld;
+use bevy_sand::pipeline_assets::{Matter, SandPushConstants};
+use bevy_sand::reactions::ANY_MATTER;
+use bevy_sand::registry::{MatterRegistry, MATTER_REGISTRY_PATH};
+use bevy_sand::settings::{SandAppSettings, SimSize};
+use bevy_sand::snapshot::SandWorld;
+use bevy_sand::SandSimPlugin;
+use sim_core::headless::{run_on_gpu, write_stats, HeadlessArgs, HEADLESS_USAGE};
+use sim_core::snapshot::SizeLimits;
+
+/// What the stats of a CPU run leave out, see [`CpuSandWorld`].
+const CPU_CAVEATS: &str = "matter movement only, without temperatures, reactions or decay";
+
+fn main() {
+    let args = match HeadlessArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{HEADLESS_USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if args.use_gpu() {
+        run_gpu(args);
+    } else {
+        let started = Instant::now();
+        let result = read_registry().and_then(|registry| {
+            let world = run_cpu(&args, &registry)?;
+            Ok((world, registry))
+        });
+        finish(&args, "cpu", Some(CPU_CAVEATS), started, result);
+    }
+}
+
+/// Saves the stats and exits, with an error code if anything failed.
+fn finish(
+    args: &HeadlessArgs,
+    backend: &str,
+    caveats: Option<&str>,
+    started: Instant,
+    result: Result<(SandWorld, MatterRegistry), String>,
+) {
+    let (world, registry) = match result {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(stats_path) = args.stats.as_ref() {
+        let mut stats = vec![
+            ("backend".to_string(), backend.to_string()),
+            ("ticks".to_string(), args.ticks.to_string()),
+            ("size".to_string(), world.size.to_string()),
+            (
+                "seconds".to_string(),
+                format!("{:.3}", started.elapsed().as_secs_f32()),
+            ),
+        ];
+        if let Some(caveats) = caveats {
+            stats.push(("caveats".to_string(), caveats.to_string()));
+        }
+
+        let mut counts = vec![0usize; registry.matters.len()];
+        for matter in world.matter.iter() {
+            if let Some(count) = counts.get_mut(matter.id as usize) {
+                *count += 1;
+            }
+        }
+        stats.extend(
+            registry
+                .matters
+                .iter()
+                .zip(counts)
+                .filter(|(_, count)| *count > 0)
+                .map(|(definition, count)| {
+                    (format!("cells.{}", definition.name), count.to_string())
+                }),
+        );
+
+        if let Err(error) = write_stats(stats_path, &stats) {
+            eprintln!("Failed to write {}: {}", stats_path.display(), error);
+            std::process::exit(1);
+        }
+    }
+
+    println!(
+        "Ran {} ticks on the {backend}, saved to {}",
+        args.ticks,
+        args.out.display()
+    );
+}
+
+// ================================== GPU ================================== //
+
+fn run_gpu(args: HeadlessArgs) {
+    let mut app = App::new();
+    if let Some((width, height)) = args.size {
+        app.insert_resource(SimSize::new(width, height));
+    }
+
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .disable::<WinitPlugin>(),
+    )
+    .add_plugin(SandSimPlugin);
+
+    if let Some(seed) = args.seed {
+        app.world.resource_mut::<SandAppSettings>().seed = seed;
+    }
+
+    app.set_runner(move |mut app| {
+        let started = Instant::now();
+        let result = run_on_gpu(&mut app, &args).and_then(|()| {
+            let registry = app
+                .world
+                .get_resource::<MatterRegistry>()
+                .cloned()
+                .ok_or("the matters never loaded")?;
+            Ok((read_world(&args.out, &registry)?, registry))
+        });
+        finish(&args, "gpu", None, started, result);
+    })
+    .run();
+}
+
+// ================================== CPU ================================== //
+
+/// Reads the registry the way the asset server finds it, next to the crate or the executable.
+fn read_registry() -> Result<MatterRegistry, String> {
+    let base = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| Some(std::env::current_exe().ok()?.parent()?.to_path_buf()))
+        .unwrap_or_default();
+    let path = base.join("assets").join(MATTER_REGISTRY_PATH);
+
+    let data = std::fs::read(&path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    ron::de::from_bytes(&data).map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+fn read_world(path: &Path, registry: &MatterRegistry) -> Result<SandWorld, String> {
+    std::fs::read(path)
+        .map_err(|error| error.to_string())
+        .and_then(|data| {
+            SandWorld::read(&data, registry, &SizeLimits::default())
+                .map_err(|error| error.to_string())
+        })
+        .map(|(_, world)| world)
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+fn run_cpu(args: &HeadlessArgs, registry: &MatterRegistry) -> Result<SandWorld, String> {
+    let mut settings = SandAppSettings::default();
+    let (world, first_step) = match args.world.as_ref() {
+        Some(path) => {
+            let data =
+                std::fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            let (header, world) = SandWorld::read(&data, registry, &SizeLimits::default())
+                .map_err(|error| format!("{}: {}", path.display(), error))?;
+            settings.seed = header.seed;
+            (world, header.step)
+        }
+        None => {
+            let size = args
+                .size
+                .map(|(width, height)| SimSize::new(width, height))
+                .unwrap_or_default();
+            let world = SandWorld {
+                size,
+                matter: vec![Matter::EMPTY; size.num_of_cells()],
+                temperature: vec![AMBIENT_TEMPERATURE; size.num_of_cells()],
+            };
+            (world, 0)
+        }
+    };
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
+    }
+
+    let unsupported = gpu_only_matters(&world, registry);
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "the CPU can't simulate {}, which need temperatures, reactions or decay, run on a GPU",
+            unsupported.join(", ")
+        ));
+    }
+
+    let mut cpu_world = CpuSandWorld::from_cells(world.size.as_tuple(), world.matter);
+    let mut pc = SandPushConstants {
+        seed: settings.seed,
+        gravity_dir: settings.gravity as u32,
+        boundary: settings.boundary as u32,
+        ..SandPushConstants::default()
+    };
+    let last_step = first_step.wrapping_add(args.ticks);
+    for step in first_step..last_step {
+        pc.sim_step = step;
+        cpu_world.step(&mut pc, &settings);
+    }
+
+    let world = SandWorld {
+        size: world.size,
+        matter: cpu_world.cells().to_vec(),
+        temperature: world.temperature,
+    };
+    world
+        .write(registry, settings.seed, last_step)
+        .save(&args.out)
+        .map_err(|error| format!("{}: {}", args.out.display(), error))?;
+    Ok(world)
+}
+
+/// Matters of `world` the [`CpuSandWorld`] would get wrong: the ones changing with temperature,
+/// decaying, giving off heat, or reacting with another matter of the world.
+fn gpu_only_matters(world: &SandWorld, registry: &MatterRegistry) -> Vec<String> {
+    let mut is_present = vec![false; registry.matters.len()];
+    for matter in world.matter.iter() {
+        if let Some(present) = is_present.get_mut(matter.id as usize) {
+            *present = true;
+        }
+    }
+    let is_present_name = |name: &str| {
+        registry
+            .id_of(name)
+            .is_some_and(|id| is_present[id as usize])
+    };
+    let is_reactant = |name: &str| {
+        registry.reactions.iter().any(|reaction| {
+            let (a, b) = (&reaction.reactants.0, &reaction.reactants.1);
+            let pairs_with = |reactant: &str, other: &str| {
+                (reactant == name || reactant == ANY_MATTER)
+                    && (other == ANY_MATTER || is_present_name(other))
+            };
+            pairs_with(a, b) || pairs_with(b, a)
+        })
+    };
+
+    registry
+        .matters
+        .iter()
+        .zip(is_present.iter())
+        .filter(|(_, present)| **present)
+        .map(|(definition, _)| definition)
+        .filter(|definition| {
+            definition.heated.is_some()
+                || definition.cooled.is_some()
+                || definition.decay.is_some()
+                || definition.temperature != AMBIENT_TEMPERATURE
+                || is_reactant(&definition.name)
+        })
+        .map(|definition| definition.name.clone())
+        .collect()
+}
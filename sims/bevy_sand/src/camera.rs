@@ -1,8 +1,9 @@
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
 
-use crate::constants::{SIM_SIZE, WINDOW_SIZE};
+use crate::constants::WINDOW_SIZE;
 use crate::input::AutomataParams;
+use crate::settings::SimSize;
 
 const CAMERA_MOVE_SPEED: f32 = 500.0;
 
@@ -10,19 +11,28 @@ pub struct CameraPlugin;
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system(setup_camera)
+            .add_system(fit_camera_to_sim.run_if(resource_changed::<SimSize>()))
             .add_system(camera_controller);
     }
 }
 
 pub fn setup_camera(mut commands: Commands) {
-    let mut camera = Camera2dBundle::default();
+    commands.spawn(Camera2dBundle::default());
+}
 
-    let visible_pixels = SIM_SIZE.0;
-    let actual_pixels = WINDOW_SIZE.1;
-    let scale = visible_pixels as f32 / (actual_pixels);
+/// Zooms so the whole simulation fits the window height, also after the size changes.
+pub fn fit_camera_to_sim(
+    sim_size: Res<SimSize>,
+    mut query: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    for (mut transform, mut ortho) in query.iter_mut() {
+        let visible_pixels = sim_size.width;
+        let actual_pixels = WINDOW_SIZE.1;
+        ortho.scale = visible_pixels as f32 / (actual_pixels);
 
-    camera.projection.scale = scale;
-    commands.spawn(camera);
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+    }
 }
 
 pub fn camera_controller(
@@ -4,12 +4,17 @@ use bevy::{
 };
 
 pub const WORKGROUP_SIZE: u32 = 8;
-pub const GRID_W: u32 = SIM_SIZE.0 / WORKGROUP_SIZE;
-pub const GRID_H: u32 = SIM_SIZE.1 / WORKGROUP_SIZE;
 
 pub const WINDOW_SIZE: (f32, f32) = (1024., 720.);
-pub const SIM_SIZE: (u32, u32) = (512, 512);
-pub const NUM_OF_CELLS: usize = (SIM_SIZE.0 * SIM_SIZE.1) as usize;
+/// Size of the simulation when no [`SimSize`](crate::settings::SimSize) is chosen at startup.
+pub const DEFAULT_SIM_SIZE: (u32, u32) = (512, 512);
+
+/// Side of the square chunks the movement passes skip while nothing changes in them, in cells.
+/// Must match `CHUNK_SIZE` in `core.wgsl` and divide the sim size.
+pub const CHUNK_SIZE: u32 = 32;
+
+/// Temperature of every cell when the simulation starts, in degrees Celsius.
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
 
 pub const SHADER_CORE: HandleUntyped =
     HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 1371231089456109822);
@@ -0,0 +1,913 @@
+use bevy::prelude::{IVec2, Vec2};
+
+use crate::pipeline_assets::{Matter, MatterState, SandPushConstants};
+use crate::settings::SandAppSettings;
+
+// ================================== Directions ================================== //
+
+// | 0 1 2 |
+// | 7 x 3 |
+// | 6 5 4 |
+const UP_LEFT: usize = 0;
+const UP: usize = 1;
+const UP_RIGHT: usize = 2;
+const RIGHT: usize = 3;
+const DOWN_RIGHT: usize = 4;
+const DOWN: usize = 5;
+const DOWN_LEFT: usize = 6;
+const LEFT: usize = 7;
+
+const OFFSETS: [IVec2; 8] = [
+    IVec2::new(-1, -1),
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, 0),
+];
+
+fn get_pos_at_dir(pos: IVec2, dir: usize) -> IVec2 {
+    pos + OFFSETS[dir]
+}
+
+// Same as `rotate_dir` in `core.wgsl`, the passes use directions relative to gravity.
+fn rotate_dir(pc: &SandPushConstants, dir: usize) -> usize {
+    if pc.gravity_dir >= SandPushConstants::NO_GRAVITY {
+        return dir;
+    }
+    (dir + pc.gravity_dir as usize + 8 - DOWN) % 8
+}
+
+fn get_pos_towards(pc: &SandPushConstants, pos: IVec2, dir: usize) -> IVec2 {
+    get_pos_at_dir(pos, rotate_dir(pc, dir))
+}
+
+fn gravity_offset(pc: &SandPushConstants) -> IVec2 {
+    get_pos_towards(pc, IVec2::ZERO, DOWN)
+}
+
+// Same hashes as in `core.wgsl`, so both sides pick the same random moves.
+fn pcg_hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+fn rand_at(pos: IVec2, seed: u32) -> f32 {
+    let hash =
+        pcg_hash(pcg_hash(pcg_hash(seed).wrapping_add(pos.x as u32)).wrapping_add(pos.y as u32));
+    (hash >> 8) as f32 / 16777216.0
+}
+
+fn pass_id(pc: &SandPushConstants) -> u32 {
+    pc.move_step + 256 * (pc.dispersion_step + 256 * pc.dispersion_dir)
+}
+
+fn rand(pc: &SandPushConstants, pos: IVec2) -> f32 {
+    let step = pcg_hash(pc.seed.wrapping_add(pcg_hash(pc.sim_step)));
+    rand_at(pos, pcg_hash(step.wrapping_add(pass_id(pc))))
+}
+
+// ================================== Matter Queries ================================== //
+
+// Same as `GRAVITY` and `MAX_FALL_SPEED` in `query.wgsl`.
+const GRAVITY: f32 = 0.5;
+const MAX_FALL_SPEED: i32 = 8;
+
+fn is_empty(matter: &Matter) -> bool {
+    matter.state == MatterState::Empty as u32
+}
+fn is_powder(matter: &Matter) -> bool {
+    matter.state == MatterState::Powder as u32
+}
+fn is_liquid(matter: &Matter) -> bool {
+    matter.state == MatterState::Liquid as u32
+}
+fn is_gas(matter: &Matter) -> bool {
+    matter.state == MatterState::Gas as u32
+}
+fn is_solid_gravity(matter: &Matter) -> bool {
+    matter.state == MatterState::SolidGravity as u32
+}
+fn is_gravity(matter: &Matter) -> bool {
+    is_powder(matter) || is_liquid(matter) || is_solid_gravity(matter)
+}
+
+// Same as `has_changed` in `core.wgsl`.
+fn has_changed(before: &Matter, after: &Matter) -> bool {
+    before.id != after.id || before.velocity != after.velocity || before.color != after.color
+}
+
+fn moves_on_empty_maybe(
+    pc: &SandPushConstants,
+    (from, to, opposite, down): (&Matter, &Matter, &Matter, &Matter),
+    p: f32,
+) -> bool {
+    p < 0.5
+        && pc.dispersion_step < from.dispersion
+        && (is_liquid(from) && !is_empty(down) || is_gas(from))
+        && is_empty(to)
+        && is_empty(opposite)
+}
+
+fn moves_on_empty_certainly(
+    pc: &SandPushConstants,
+    (from, to, opposite, down): (&Matter, &Matter, &Matter, &Matter),
+) -> bool {
+    pc.dispersion_step < from.dispersion
+        && (is_liquid(from) && !is_empty(down) || is_gas(from))
+        && is_empty(to)
+        && !is_empty(opposite)
+}
+
+fn slides_on_empty(from_diagonal: &Matter, to_diagonal: &Matter, from_down: &Matter) -> bool {
+    is_powder(from_diagonal)
+        && !is_empty(from_down)
+        && !is_liquid(from_down)
+        && is_empty(to_diagonal)
+}
+
+/// Velocity along gravity, in cells per tick.
+fn fall_speed(pc: &SandPushConstants, matter: &Matter) -> f32 {
+    let g = gravity_offset(pc).as_vec2();
+    Vec2::from(matter.velocity).dot(g) / g.dot(g)
+}
+
+fn wanted_fall_distance(pc: &SandPushConstants, matter: &Matter) -> i32 {
+    (fall_speed(pc, matter) as i32).clamp(1, MAX_FALL_SPEED)
+}
+
+fn rises_on_empty(from: &Matter, to: &Matter) -> bool {
+    is_gas(from) && is_empty(to)
+}
+
+fn moves_on_swap_maybe(
+    pc: &SandPushConstants,
+    (from, to, opposite): (&Matter, &Matter, &Matter),
+    p: f32,
+) -> bool {
+    p < 0.5
+        && pc.dispersion_step < from.dispersion
+        && (is_liquid(from) || is_gas(from))
+        && (is_liquid(to) || is_gas(to))
+        && (is_liquid(opposite) || is_gas(opposite))
+        && opposite.weight < from.weight
+        && to.weight < from.weight
+}
+
+fn moves_on_swap_certainly(
+    pc: &SandPushConstants,
+    (from, to, opposite): (&Matter, &Matter, &Matter),
+) -> bool {
+    pc.dispersion_step < from.dispersion
+        && (is_liquid(from) || is_gas(from))
+        && (is_liquid(to) || is_gas(to))
+        && !(is_liquid(opposite) && opposite.weight < from.weight)
+        && to.weight < from.weight
+}
+
+fn falls_on_swap(from: &Matter, to: &Matter) -> bool {
+    is_gravity(from) && (is_liquid(to) || is_gas(to)) && to.weight < from.weight
+}
+
+fn slides_on_swap(from_diagonal: &Matter, to_diagonal: &Matter, from_down: &Matter) -> bool {
+    is_powder(from_diagonal)
+        && !is_empty(from_down)
+        && !is_liquid(from_down)
+        && is_liquid(to_diagonal)
+        && to_diagonal.weight < from_diagonal.weight
+}
+
+fn rises_on_swap(from: &Matter, to: &Matter) -> bool {
+    is_gas(from) && (is_liquid(to) || is_powder(to)) && to.weight > from.weight
+}
+
+// ================================== World ================================== //
+
+type Pass = fn(&CpuSandWorld, &SandPushConstants, IVec2) -> Matter;
+
+struct HorizontalNeighbors {
+    from_pos: IVec2,
+    from: Matter,
+    from_from: Matter,
+    down_from: Matter,
+    to: Matter,
+    down: Matter,
+    at_from_border: bool,
+    at_to_border: bool,
+    to_dir: usize,
+}
+
+/// A GPU-free copy of the sand simulation.
+///
+/// Every pass dispatched by `SandPipelines::move_once` and `SandPipelines::disperse` is mirrored
+/// here with the same rules as `query.wgsl`, so the stepper can be used to test the rules and as
+/// an oracle for the shaders. Chunks never sleep here, every pass runs on every cell.
+#[derive(Debug, Clone)]
+pub struct CpuSandWorld {
+    size: (u32, u32),
+    cells: Vec<Matter>,
+    scratch: Vec<Matter>,
+    /// Same as the wind buffer, only changed by [`CpuSandWorld::set_wind`].
+    wind: Vec<Vec2>,
+}
+
+impl CpuSandWorld {
+    pub fn new(size: (u32, u32)) -> Self {
+        let cells = vec![Matter::EMPTY; (size.0 * size.1) as usize];
+        Self {
+            size,
+            scratch: cells.clone(),
+            wind: vec![Vec2::ZERO; cells.len()],
+            cells,
+        }
+    }
+
+    /// A world starting from `cells`, row by row, e.g. the cells of a loaded snapshot.
+    pub fn from_cells(size: (u32, u32), cells: Vec<Matter>) -> Self {
+        assert_eq!(cells.len(), (size.0 * size.1) as usize);
+        Self {
+            size,
+            scratch: cells.clone(),
+            wind: vec![Vec2::ZERO; cells.len()],
+            cells,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn cells(&self) -> &[Matter] {
+        &self.cells
+    }
+
+    pub fn get(&self, pos: IVec2) -> Matter {
+        self.cells[self.get_index(pos)]
+    }
+
+    pub fn set(&mut self, pos: IVec2, matter: Matter) {
+        let index = self.get_index(pos);
+        self.cells[index] = matter;
+    }
+
+    pub fn set_wind(&mut self, pos: IVec2, wind: Vec2) {
+        let index = self.get_index(pos);
+        self.wind[index] = wind;
+    }
+
+    fn get_index(&self, pos: IVec2) -> usize {
+        (pos.y * self.size.0 as i32 + pos.x) as usize
+    }
+
+    fn is_inside_sim_canvas(&self, pos: IVec2) -> bool {
+        pos.x >= 0 && pos.x < self.size.0 as i32 && pos.y >= 0 && pos.y < self.size.1 as i32
+    }
+
+    /// Same as `boundary_pos` in `query.wgsl`.
+    fn boundary_pos(&self, pc: &SandPushConstants, pos: IVec2) -> IVec2 {
+        if pc.boundary == SandPushConstants::BOUNDARY_WRAP {
+            IVec2::new(
+                pos.x.rem_euclid(self.size.0 as i32),
+                pos.y.rem_euclid(self.size.1 as i32),
+            )
+        } else {
+            pos
+        }
+    }
+
+    fn is_inside_boundary(&self, pc: &SandPushConstants, pos: IVec2) -> bool {
+        self.is_inside_sim_canvas(self.boundary_pos(pc, pos))
+    }
+
+    /// Matter outside the canvas is empty, like `read_matter_at` in `query.wgsl`.
+    fn read_matter_at(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        if self.is_inside_boundary(pc, pos) {
+            self.get(self.boundary_pos(pc, pos))
+        } else {
+            Matter::EMPTY
+        }
+    }
+
+    /// Relative to gravity, like the borders in `query.wgsl`. Only walls have borders.
+    fn is_at_border(&self, pc: &SandPushConstants, pos: IVec2, dir: usize) -> bool {
+        pc.boundary == SandPushConstants::BOUNDARY_WALL
+            && !self.is_inside_sim_canvas(get_pos_towards(pc, pos, dir))
+    }
+
+    /// Same as `dispersion_chance` in `core.wgsl`.
+    fn dispersion_chance(&self, pc: &SandPushConstants, pos: IVec2, dir: usize) -> f32 {
+        let cell_pos = self.boundary_pos(pc, pos);
+        if !self.is_inside_sim_canvas(cell_pos) {
+            return 1.0;
+        }
+        let dir_offset = get_pos_towards(pc, IVec2::ZERO, dir).as_vec2().normalize();
+        rand(pc, cell_pos) - 0.5 * self.wind[self.get_index(cell_pos)].dot(dir_offset)
+    }
+
+    fn fall_distance(&self, pc: &SandPushConstants, pos: IVec2, matter: &Matter) -> i32 {
+        if !is_gravity(matter) {
+            return 0;
+        }
+
+        (1..=wanted_fall_distance(pc, matter))
+            .take_while(|k| {
+                let to_pos = pos + gravity_offset(pc) * *k;
+                let is_wall = pc.boundary == SandPushConstants::BOUNDARY_WALL
+                    && !self.is_inside_sim_canvas(to_pos);
+                !is_wall && is_empty(&self.read_matter_at(pc, to_pos))
+            })
+            .last()
+            .unwrap_or(0)
+    }
+
+    fn velocity_after_fall(
+        &self,
+        pc: &SandPushConstants,
+        pos: IVec2,
+        matter: &Matter,
+        distance: i32,
+    ) -> [f32; 2] {
+        let mut speed = fall_speed(pc, matter);
+        if distance == wanted_fall_distance(pc, matter) {
+            speed = (speed + GRAVITY).min(MAX_FALL_SPEED as f32);
+        } else {
+            let blocker_pos = pos + gravity_offset(pc) * (distance + 1);
+            speed = if self.is_inside_boundary(pc, blocker_pos) {
+                speed.min(fall_speed(pc, &self.read_matter_at(pc, blocker_pos)))
+            } else {
+                0.0
+            };
+        }
+        (gravity_offset(pc).as_vec2() * speed).to_array()
+    }
+
+    fn get_neighbor(&self, pc: &SandPushConstants, pos: IVec2, dir: usize) -> Matter {
+        self.read_matter_at(pc, get_pos_towards(pc, pos, dir))
+    }
+
+    /// Runs `pass` for every cell, reading the current cells and writing a new generation, like a
+    /// single compute dispatch between the ping-pong buffers. Returns the number of changed cells,
+    /// as counted in the `moved` buffer.
+    fn run_pass(&mut self, pc: &SandPushConstants, pass: Pass) -> u32 {
+        let mut out = std::mem::take(&mut self.scratch);
+        let mut moved = 0;
+        for y in 0..self.size.1 as i32 {
+            for x in 0..self.size.0 as i32 {
+                let pos = IVec2::new(x, y);
+                let index = self.get_index(pos);
+                out[index] = pass(self, pc, pos);
+                moved += has_changed(&self.cells[index], &out[index]) as u32;
+            }
+        }
+        self.scratch = std::mem::replace(&mut self.cells, out);
+        moved
+    }
+
+    // ================================== Passes ================================== //
+
+    /// Falls happen on the first move step only, as far as the velocity allows.
+    fn fall_empty(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let mut current = self.get(pos);
+        if pc.move_step != 0 {
+            return current;
+        }
+
+        if is_empty(&current) {
+            // The first matter above lands here if its fall ends here
+            for k in 1..=MAX_FALL_SPEED {
+                let from_pos = pos - gravity_offset(pc) * k;
+                if !self.is_inside_boundary(pc, from_pos) {
+                    break;
+                }
+                let mut above = self.read_matter_at(pc, from_pos);
+                if !is_empty(&above) {
+                    if self.fall_distance(pc, from_pos, &above) == k {
+                        above.velocity = self.velocity_after_fall(pc, from_pos, &above, k);
+                        return above;
+                    }
+                    break;
+                }
+            }
+        } else if is_gravity(&current) {
+            let distance = self.fall_distance(pc, pos, &current);
+            if distance > 0 {
+                return self.read_matter_at(pc, pos + gravity_offset(pc) * distance);
+            }
+            current.velocity = self.velocity_after_fall(pc, pos, &current, 0);
+        }
+
+        current
+    }
+
+    fn fall_swap(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let current = self.get(pos);
+        let up = self.get_neighbor(pc, pos, UP);
+        let down = self.get_neighbor(pc, pos, DOWN);
+
+        if !self.is_at_border(pc, pos, UP) && falls_on_swap(&up, &current) {
+            up
+        } else if !self.is_at_border(pc, pos, DOWN) && falls_on_swap(&current, &down) {
+            down
+        } else {
+            current
+        }
+    }
+
+    fn rise_empty(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let current = self.get(pos);
+        let up = self.get_neighbor(pc, pos, UP);
+        let down = self.get_neighbor(pc, pos, DOWN);
+
+        if !self.is_at_border(pc, pos, DOWN) && rises_on_empty(&down, &current) {
+            down
+        } else if !self.is_at_border(pc, pos, UP) && rises_on_empty(&current, &up) {
+            up
+        } else {
+            current
+        }
+    }
+
+    fn rise_swap(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let current = self.get(pos);
+        let up = self.get_neighbor(pc, pos, UP);
+        let down = self.get_neighbor(pc, pos, DOWN);
+
+        if !self.is_at_border(pc, pos, DOWN) && rises_on_swap(&down, &current) {
+            down
+        } else if !self.is_at_border(pc, pos, UP) && rises_on_swap(&current, &up) {
+            up
+        } else {
+            current
+        }
+    }
+
+    /// Powders slide to the left on even steps and to the right on odd steps.
+    fn slide_down(
+        &self,
+        pc: &SandPushConstants,
+        pos: IVec2,
+        slides: fn(&Matter, &Matter, &Matter) -> bool,
+    ) -> Matter {
+        let current = self.get(pos);
+        let down = self.get_neighbor(pc, pos, DOWN);
+
+        let (side, up_side, down_other, at_side_border, at_other_border) =
+            if pc.sim_step.wrapping_add(pc.move_step).is_multiple_of(2) {
+                (
+                    self.get_neighbor(pc, pos, RIGHT),
+                    self.get_neighbor(pc, pos, UP_RIGHT),
+                    self.get_neighbor(pc, pos, DOWN_LEFT),
+                    self.is_at_border(pc, pos, RIGHT),
+                    self.is_at_border(pc, pos, LEFT),
+                )
+            } else {
+                (
+                    self.get_neighbor(pc, pos, LEFT),
+                    self.get_neighbor(pc, pos, UP_LEFT),
+                    self.get_neighbor(pc, pos, DOWN_RIGHT),
+                    self.is_at_border(pc, pos, LEFT),
+                    self.is_at_border(pc, pos, RIGHT),
+                )
+            };
+
+        if !self.is_at_border(pc, pos, UP) && !at_side_border && slides(&up_side, &current, &side) {
+            up_side
+        } else if !self.is_at_border(pc, pos, DOWN)
+            && !at_other_border
+            && slides(&current, &down_other, &down)
+        {
+            down_other
+        } else {
+            current
+        }
+    }
+
+    fn slide_down_empty(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        self.slide_down(pc, pos, slides_on_empty)
+    }
+
+    fn slide_down_swap(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        self.slide_down(pc, pos, slides_on_swap)
+    }
+
+    /// Neighbours used by the horizontal passes. `dispersion_dir == 0` pulls matter from the right
+    /// into the current cell and pushes the current cell to the left, `1` does the opposite.
+    fn horizontal_neighbors(&self, pc: &SandPushConstants, pos: IVec2) -> HorizontalNeighbors {
+        let (from_dir, to_dir, down_from_dir) = if pc.dispersion_dir == 0 {
+            (RIGHT, LEFT, DOWN_RIGHT)
+        } else {
+            (LEFT, RIGHT, DOWN_LEFT)
+        };
+        let from_pos = get_pos_towards(pc, pos, from_dir);
+        HorizontalNeighbors {
+            from_pos,
+            from: self.get_neighbor(pc, pos, from_dir),
+            from_from: self.get_neighbor(pc, from_pos, from_dir),
+            down_from: self.get_neighbor(pc, pos, down_from_dir),
+            to: self.get_neighbor(pc, pos, to_dir),
+            down: self.get_neighbor(pc, pos, DOWN),
+            at_from_border: self.is_at_border(pc, pos, from_dir),
+            at_to_border: self.is_at_border(pc, pos, to_dir),
+            to_dir,
+        }
+    }
+
+    fn horizontal_empty(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let current = self.get(pos);
+        let HorizontalNeighbors {
+            from_pos,
+            from,
+            from_from,
+            down_from,
+            to,
+            down,
+            at_from_border,
+            at_to_border,
+            to_dir,
+        } = self.horizontal_neighbors(pc, pos);
+
+        if !at_from_border
+            && moves_on_empty_certainly(pc, (&from, &current, &from_from, &down_from))
+        {
+            from
+        } else if !at_to_border && moves_on_empty_certainly(pc, (&current, &to, &from, &down)) {
+            to
+        } else if !at_from_border
+            && moves_on_empty_maybe(
+                pc,
+                (&from, &current, &from_from, &down_from),
+                self.dispersion_chance(pc, from_pos, to_dir),
+            )
+        {
+            from
+        } else if !at_to_border
+            && moves_on_empty_maybe(
+                pc,
+                (&current, &to, &from, &down),
+                self.dispersion_chance(pc, pos, to_dir),
+            )
+        {
+            to
+        } else {
+            current
+        }
+    }
+
+    fn horizontal_swap(&self, pc: &SandPushConstants, pos: IVec2) -> Matter {
+        let current = self.get(pos);
+        let HorizontalNeighbors {
+            from_pos,
+            from,
+            from_from,
+            to,
+            at_from_border,
+            at_to_border,
+            to_dir,
+            ..
+        } = self.horizontal_neighbors(pc, pos);
+
+        if !at_from_border && moves_on_swap_certainly(pc, (&from, &current, &from_from)) {
+            from
+        } else if !at_to_border && moves_on_swap_certainly(pc, (&current, &to, &from)) {
+            to
+        } else if !at_from_border
+            && moves_on_swap_maybe(
+                pc,
+                (&from, &current, &from_from),
+                self.dispersion_chance(pc, from_pos, to_dir),
+            )
+        {
+            from
+        } else if !at_to_border
+            && moves_on_swap_maybe(
+                pc,
+                (&current, &to, &from),
+                self.dispersion_chance(pc, pos, to_dir),
+            )
+        {
+            to
+        } else {
+            current
+        }
+    }
+
+    // ================================== Steps ================================== //
+
+    /// Mirrors `SandPipelines::move_once`, returns the number of changed cells.
+    pub fn move_once(&mut self, pc: &mut SandPushConstants, move_step: u32) -> u32 {
+        if pc.gravity_dir == SandPushConstants::NO_GRAVITY {
+            return 0;
+        }
+        pc.move_step = move_step;
+
+        // Fall
+        self.run_pass(pc, Self::fall_empty)
+            + self.run_pass(pc, Self::fall_swap)
+            // Risers
+            + self.run_pass(pc, Self::rise_empty)
+            + self.run_pass(pc, Self::rise_swap)
+            // Sliders
+            + self.run_pass(pc, Self::slide_down_empty)
+            + self.run_pass(pc, Self::slide_down_swap)
+    }
+
+    /// Mirrors `SandPipelines::disperse`, returns the number of changed cells.
+    pub fn disperse(
+        &mut self,
+        pc: &mut SandPushConstants,
+        direction: u32,
+        dispersion_steps: u32,
+    ) -> u32 {
+        pc.dispersion_dir = direction;
+        let mut moved = 0;
+        for dispersion_step in 0..dispersion_steps {
+            pc.dispersion_step = dispersion_step;
+
+            moved += self.run_pass(pc, Self::horizontal_empty);
+            moved += self.run_pass(pc, Self::horizontal_swap);
+        }
+        moved
+    }
+
+    /// Advances the world by one step, in the same order as `SandPipelines::step`. `pc.sim_step` is
+    /// the index of the step and `pc.seed` the random seed.
+    pub fn step(&mut self, pc: &mut SandPushConstants, settings: &SandAppSettings) {
+        for move_step in 0..settings.movement_steps {
+            let direction = pc.dispersion_dir_after(move_step);
+            let moved = self.move_once(pc, move_step)
+                + self.disperse(pc, direction, settings.dispersion_steps);
+
+            // Same as `settle.wgsl`
+            if settings.adaptive_movement && moved == 0 {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Gravity;
+
+    const EMPTY: Matter = Matter::EMPTY;
+    const SAND: Matter = Matter::new(1, MatterState::Powder, [1.0, 1.0, 0.0, 1.0], 2.0, 0);
+    const WATER: Matter = Matter::new(2, MatterState::Liquid, [0.0, 0.0, 1.0, 1.0], 1.0, 3);
+    const STONE: Matter = Matter::new(3, MatterState::Solid, [0.5, 0.5, 0.5, 1.0], 3.0, 0);
+
+    /// A world of `width` columns, `cells` given row by row from the top.
+    fn world_of(width: u32, cells: &[Matter]) -> CpuSandWorld {
+        let mut world = CpuSandWorld::new((width, cells.len() as u32 / width));
+        for (index, matter) in cells.iter().enumerate() {
+            let index = index as i32;
+            world.set(
+                IVec2::new(index % width as i32, index / width as i32),
+                *matter,
+            );
+        }
+        world
+    }
+
+    fn ids(world: &CpuSandWorld) -> Vec<u32> {
+        world.cells().iter().map(|matter| matter.id).collect()
+    }
+
+    fn constants(boundary: u32) -> SandPushConstants {
+        SandPushConstants {
+            gravity_dir: SandPushConstants::DOWN_GRAVITY,
+            boundary,
+            ..SandPushConstants::default()
+        }
+    }
+
+    #[test]
+    fn matter_falls_into_empty_cells() {
+        let pc = constants(SandPushConstants::BOUNDARY_WALL);
+        let mut world = world_of(1, &[SAND, EMPTY, EMPTY]);
+
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 1, 0]);
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 0, 1]);
+    }
+
+    fn falling(mut matter: Matter, speed: f32) -> Matter {
+        matter.velocity = [0.0, speed];
+        matter
+    }
+
+    #[test]
+    fn matter_falls_as_far_as_its_velocity_allows() {
+        let pc = SandPushConstants::default();
+        let mut cells = [EMPTY; 6];
+        cells[0] = falling(SAND, 3.0);
+        let mut world = world_of(1, &cells);
+
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 0, 0, 1, 0, 0]);
+        assert_eq!(world.get(IVec2::new(0, 3)).velocity, [0.0, 3.0 + GRAVITY]);
+    }
+
+    #[test]
+    fn falls_stop_at_the_first_collision_on_the_way() {
+        let pc = SandPushConstants::default();
+        let mut world = world_of(1, &[falling(SAND, 4.0), EMPTY, EMPTY, STONE, EMPTY, EMPTY]);
+
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 0, 1, 3, 0, 0]);
+        // Landing on resting matter stops the fall
+        assert_eq!(world.get(IVec2::new(0, 2)).velocity, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn falls_are_capped_at_the_max_fall_speed() {
+        let pc = SandPushConstants::default();
+        let mut cells = [EMPTY; 12];
+        cells[0] = falling(SAND, MAX_FALL_SPEED as f32 + 4.0);
+        let mut world = world_of(1, &cells);
+
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        let landed = IVec2::new(0, MAX_FALL_SPEED);
+        assert_eq!(world.get(landed).id, SAND.id);
+        assert_eq!(world.get(landed).velocity, [0.0, MAX_FALL_SPEED as f32]);
+    }
+
+    #[test]
+    fn matter_swaps_with_lighter_matter() {
+        let pc = constants(SandPushConstants::BOUNDARY_WALL);
+        let mut world = world_of(1, &[SAND, WATER]);
+        world.run_pass(&pc, CpuSandWorld::fall_swap);
+        assert_eq!(ids(&world), [2, 1]);
+
+        // Heavier matter stays below
+        let mut world = world_of(1, &[WATER, SAND]);
+        world.run_pass(&pc, CpuSandWorld::fall_swap);
+        assert_eq!(ids(&world), [2, 1]);
+    }
+
+    #[test]
+    fn powders_slide_down_to_alternating_sides() {
+        let mut pc = constants(SandPushConstants::BOUNDARY_WALL);
+        let cells = [EMPTY, SAND, EMPTY, EMPTY, STONE, EMPTY];
+
+        let mut left = world_of(3, &cells);
+        left.run_pass(&pc, CpuSandWorld::slide_down_empty);
+        assert_eq!(ids(&left), [0, 0, 0, 1, 3, 0]);
+
+        pc.sim_step = 1;
+        let mut right = world_of(3, &cells);
+        right.run_pass(&pc, CpuSandWorld::slide_down_empty);
+        assert_eq!(ids(&right), [0, 0, 0, 0, 3, 1]);
+    }
+
+    #[test]
+    fn liquids_disperse_horizontally() {
+        let mut pc = constants(SandPushConstants::BOUNDARY_WALL);
+        let cells = [EMPTY, WATER, STONE, STONE, STONE, STONE];
+
+        let mut world = world_of(3, &cells);
+        world.disperse(&mut pc, 0, 1);
+        assert_eq!(ids(&world), [2, 0, 3, 3, 3, 3]);
+
+        // Past its dispersion, it stays in place
+        let mut world = world_of(3, &cells);
+        pc.dispersion_dir = 0;
+        pc.dispersion_step = WATER.dispersion;
+        world.run_pass(&pc, CpuSandWorld::horizontal_empty);
+        assert_eq!(ids(&world), [0, 2, 3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn walls_keep_matter_in() {
+        let pc = constants(SandPushConstants::BOUNDARY_WALL);
+        let mut world = world_of(1, &[EMPTY, SAND]);
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 1]);
+
+        let mut world = world_of(2, &[WATER, STONE]);
+        world.disperse(&mut constants(SandPushConstants::BOUNDARY_WALL), 0, 1);
+        assert_eq!(ids(&world), [2, 3]);
+    }
+
+    #[test]
+    fn matter_falls_out_of_the_void() {
+        let pc = constants(SandPushConstants::BOUNDARY_VOID);
+        let mut world = world_of(1, &[EMPTY, SAND]);
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 0]);
+    }
+
+    #[test]
+    fn matter_wraps_around_the_edges() {
+        let pc = constants(SandPushConstants::BOUNDARY_WRAP);
+        let mut world = world_of(1, &[EMPTY, SAND]);
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [1, 0]);
+    }
+
+    fn with_gravity(gravity: Gravity) -> SandPushConstants {
+        SandPushConstants {
+            gravity_dir: gravity as u32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn directions_are_rotated_towards_gravity() {
+        let pc = with_gravity(Gravity::Right);
+        assert_eq!(rotate_dir(&pc, DOWN), RIGHT);
+        assert_eq!(rotate_dir(&pc, UP), LEFT);
+        assert_eq!(rotate_dir(&pc, DOWN_LEFT), DOWN_RIGHT);
+
+        let pc = with_gravity(Gravity::Zero);
+        for dir in 0..8 {
+            assert_eq!(rotate_dir(&pc, dir), dir);
+        }
+    }
+
+    #[test]
+    fn matter_falls_along_the_gravity_direction() {
+        let pc = with_gravity(Gravity::Left);
+        let mut world = world_of(3, &[EMPTY, EMPTY, SAND]);
+
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [0, 1, 0]);
+        assert_eq!(world.get(IVec2::new(1, 0)).velocity, [-GRAVITY, 0.0]);
+        world.run_pass(&pc, CpuSandWorld::fall_empty);
+        assert_eq!(ids(&world), [1, 0, 0]);
+    }
+
+    #[test]
+    fn nothing_moves_without_gravity() {
+        let mut pc = with_gravity(Gravity::Zero);
+        let mut world = world_of(1, &[SAND, EMPTY, EMPTY]);
+
+        world.move_once(&mut pc, 0);
+        assert_eq!(ids(&world), [1, 0, 0]);
+    }
+
+    #[test]
+    fn random_choices_only_depend_on_the_seed_step_and_pass() {
+        let pc = SandPushConstants {
+            seed: 7,
+            sim_step: 3,
+            ..SandPushConstants::default()
+        };
+        let values = |pc: &SandPushConstants| -> Vec<f32> {
+            (0..64)
+                .map(|i| rand(pc, IVec2::new(i % 8, i / 8)))
+                .collect()
+        };
+
+        let first = values(&pc);
+        assert_eq!(first, values(&pc));
+        assert!(first.iter().all(|value| (0.0..1.0).contains(value)));
+
+        for other in [
+            SandPushConstants { seed: 8, ..pc },
+            SandPushConstants { sim_step: 4, ..pc },
+            SandPushConstants {
+                dispersion_step: 1,
+                ..pc
+            },
+        ] {
+            assert_ne!(first, values(&other));
+        }
+    }
+
+    #[test]
+    fn every_movement_step_moves_matter() {
+        let column = [SAND, WATER, WATER, WATER];
+        let settings = |movement_steps| SandAppSettings {
+            movement_steps,
+            ..SandAppSettings::default()
+        };
+
+        let mut world = world_of(1, &column);
+        world.step(
+            &mut constants(SandPushConstants::BOUNDARY_WALL),
+            &settings(1),
+        );
+        assert_eq!(ids(&world), [2, 1, 2, 2]);
+
+        let mut world = world_of(1, &column);
+        world.step(
+            &mut constants(SandPushConstants::BOUNDARY_WALL),
+            &settings(3),
+        );
+        assert_eq!(ids(&world), [2, 2, 2, 1]);
+    }
+
+    #[test]
+    fn dispersion_alternates_between_movement_and_simulation_steps() {
+        let mut pc = SandPushConstants::default();
+        assert_eq!(pc.dispersion_dir_after(0), 1);
+        assert_eq!(pc.dispersion_dir_after(1), 0);
+        pc.sim_step = 1;
+        assert_eq!(pc.dispersion_dir_after(0), 0);
+        assert_eq!(pc.dispersion_dir_after(1), 1);
+    }
+}
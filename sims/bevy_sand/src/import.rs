@@ -0,0 +1,257 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    render::{
+        renderer::RenderDevice,
+        texture::{CompressedImageFormats, ImageType},
+    },
+};
+use serde::Deserialize;
+use sim_core::snapshot::SizeLimits;
+
+use crate::constants::AMBIENT_TEMPERATURE;
+use crate::pipeline_assets::Matter;
+use crate::registry::MatterRegistry;
+use crate::settings::SimSize;
+use crate::snapshot::{SandWorld, SandWorldUpload};
+
+/// Default image imported as the world, next to the executable.
+pub const DEFAULT_IMPORT_PATH: &str = "world.png";
+/// Default palette used by [`PaletteMapping::Palette`].
+pub const DEFAULT_PALETTE_PATH: &str = "assets/default.palette.ron";
+
+/// Pixels less opaque than this are left empty.
+const MIN_ALPHA: u8 = 128;
+
+pub struct ImportPlugin;
+impl Plugin for ImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImageImport>()
+            .add_event::<ImportImage>()
+            .add_system(import_image);
+    }
+}
+
+/// How the pixels of an imported image pick their matter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteMapping {
+    /// The matter of the registry with the nearest color.
+    #[default]
+    Nearest,
+    /// The matter of the [`ImportPalette`] entry with the nearest color, for images painted with
+    /// colors of their own.
+    Palette,
+}
+
+impl PaletteMapping {
+    pub const ALL: [PaletteMapping; 2] = [PaletteMapping::Nearest, PaletteMapping::Palette];
+}
+
+/// Files picked in the UI.
+#[derive(Resource, Debug, Clone)]
+pub struct ImageImport {
+    pub path: String,
+    pub mapping: PaletteMapping,
+    pub palette_path: String,
+    /// Why the last import failed, shown in the UI.
+    pub error: Option<String>,
+}
+
+impl Default for ImageImport {
+    fn default() -> Self {
+        Self {
+            path: DEFAULT_IMPORT_PATH.to_string(),
+            mapping: PaletteMapping::default(),
+            palette_path: DEFAULT_PALETTE_PATH.to_string(),
+            error: None,
+        }
+    }
+}
+
+/// Replaces the world with a PNG, one cell per pixel. The world is resized to fit the image.
+pub struct ImportImage {
+    pub path: PathBuf,
+    pub mapping: PaletteMapping,
+    pub palette_path: PathBuf,
+}
+
+/// Colors of an image and the matters they stand for, read from a RON file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportPalette {
+    pub colors: Vec<PaletteEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaletteEntry {
+    /// sRGB color, from `0` to `255`.
+    pub color: [u8; 3],
+    /// Name of the matter in the registry.
+    pub matter: String,
+}
+
+/// Colors the pixels are matched against, from `0.0` to `1.0` like the colors of the registry.
+struct ColorTable(Vec<([f32; 3], u32)>);
+
+impl ColorTable {
+    fn from_registry(registry: &MatterRegistry) -> Self {
+        let colors = registry.matters.iter().enumerate();
+        Self(
+            colors
+                .map(|(id, definition)| {
+                    let [r, g, b, _] = definition.color;
+                    ([r, g, b], id as u32)
+                })
+                .collect(),
+        )
+    }
+
+    fn from_palette(palette: &ImportPalette, registry: &MatterRegistry) -> Self {
+        let mut colors = Vec::with_capacity(palette.colors.len());
+        for entry in palette.colors.iter() {
+            match registry.id_of(&entry.matter) {
+                Some(id) => colors.push((entry.color.map(|channel| channel as f32 / 255.0), id)),
+                None => warn!("Unknown matter {} in the palette", entry.matter),
+            }
+        }
+        Self(colors)
+    }
+
+    fn nearest(&self, pixel: [u8; 4]) -> Option<u32> {
+        if pixel[3] < MIN_ALPHA {
+            return None;
+        }
+
+        let distance = |color: &[f32; 3]| -> f32 {
+            (0..3)
+                .map(|channel| (color[channel] - pixel[channel] as f32 / 255.0).powi(2))
+                .sum()
+        };
+        self.0
+            .iter()
+            .min_by(|(a, _), (b, _)| distance(a).total_cmp(&distance(b)))
+            .map(|(_, id)| *id)
+    }
+}
+
+/// Width and height of a PNG, read from its header without decoding it.
+fn png_size(data: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    // The IHDR chunk comes first, its width and height follow its length and type
+    if data.get(..8)? != SIGNATURE || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Decodes a PNG into rows of RGBA pixels, top row first.
+fn decode_png(data: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let image = Image::from_buffer(
+        data,
+        ImageType::Extension("png"),
+        CompressedImageFormats::NONE,
+        true,
+    )
+    .map_err(|error| error.to_string())?;
+    let rgba = image
+        .try_into_dynamic()
+        .map_err(|error| error.to_string())?
+        .to_rgba8();
+
+    Ok((rgba.width(), rgba.height(), rgba.into_raw()))
+}
+
+fn read_palette(path: &std::path::Path) -> Result<ImportPalette, String> {
+    let data = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    ron::from_str(&data).map_err(|error| error.to_string())
+}
+
+/// Reads the image of `import` as a world, checked against `limits` before it is decoded.
+fn read_world(
+    import: &ImportImage,
+    registry: &MatterRegistry,
+    limits: &SizeLimits,
+) -> Result<SandWorld, String> {
+    let colors = match import.mapping {
+        PaletteMapping::Nearest => ColorTable::from_registry(registry),
+        PaletteMapping::Palette => {
+            let palette = read_palette(&import.palette_path).map_err(|error| {
+                format!(
+                    "Failed to read {}: {}",
+                    import.palette_path.display(),
+                    error
+                )
+            })?;
+            ColorTable::from_palette(&palette, registry)
+        }
+    };
+
+    let data = std::fs::read(&import.path).map_err(|error| error.to_string())?;
+    let (width, height) = png_size(&data).ok_or("not a PNG")?;
+
+    // Sizes are whole chunks, the cells past the image are left empty
+    let size = SimSize::new(width, height);
+    limits
+        .check(
+            (size.width, size.height),
+            std::mem::size_of::<Matter>() as u64,
+        )
+        .map_err(|error| {
+            format!(
+                "{error}, the GPU fits worlds of up to {} cells per side",
+                limits.max_side
+            )
+        })?;
+
+    let (width, _, pixels) = decode_png(&data)?;
+    let mut world = SandWorld {
+        size,
+        matter: vec![Matter::EMPTY; size.num_of_cells()],
+        temperature: vec![AMBIENT_TEMPERATURE; size.num_of_cells()],
+    };
+    for (index, pixel) in pixels.chunks_exact(4).enumerate() {
+        let Some(id) = colors.nearest([pixel[0], pixel[1], pixel[2], pixel[3]]) else {
+            continue;
+        };
+
+        let cell = (index / width as usize) * size.width as usize + index % width as usize;
+        world.matter[cell] = registry.matter(id);
+        world.temperature[cell] = registry.matters[id as usize].temperature;
+    }
+    Ok(world)
+}
+
+fn import_image(
+    mut sim_size: ResMut<SimSize>,
+    mut upload: ResMut<SandWorldUpload>,
+    mut image_import: ResMut<ImageImport>,
+    registry: Option<Res<MatterRegistry>>,
+    device: Res<RenderDevice>,
+    mut import_events: EventReader<ImportImage>,
+) {
+    let Some(import) = import_events.iter().last() else {
+        return;
+    };
+    let Some(registry) = registry else {
+        warn!("Can't import {} before the matters", import.path.display());
+        return;
+    };
+
+    match read_world(import, &registry, &SizeLimits::of_device(&device)) {
+        Ok(world) => {
+            if *sim_size != world.size {
+                *sim_size = world.size;
+            }
+            upload.0 = Some(Arc::new(world));
+            image_import.error = None;
+            info!("Imported the world from {}", import.path.display());
+        }
+        Err(error) => {
+            warn!("Failed to import {}: {}", import.path.display(), error);
+            image_import.error = Some(error);
+        }
+    }
+}
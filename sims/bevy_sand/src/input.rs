@@ -1,14 +1,69 @@
-use std::sync::Arc;
-
 use bevy::{
+    ecs::system::SystemParam,
     input::{mouse::MouseButtonInput, ButtonState},
     prelude::*,
-    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
+    render::extract_resource::ExtractResource,
 };
 use bevy_egui::EguiContexts;
-use parking_lot::Mutex;
+use sim_core::brush::{Brush, FLOOD_READBACK};
+use sim_core::clock::SimClock;
+use sim_core::readback::ReadbackEvent;
+use sim_core::stamp::LoadedStamp;
+use sim_core::undo::StrokeHistory;
+
+use crate::{pipeline_assets::SandPushConstants, settings::SimSize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BrushKind {
+    /// Paints the selected matter.
+    #[default]
+    Matter,
+    /// Raises the temperature of the painted cells.
+    Heat,
+    /// Lowers the temperature of the painted cells.
+    Cool,
+    /// Paints wind blowing the way the mouse is dragged.
+    Wind,
+    /// Removes the wind from the painted cells.
+    Calm,
+}
+
+/// Cells the matter brush paints over.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PaintMode {
+    /// Only fills empty cells, erasing works anywhere.
+    #[default]
+    FillEmpty,
+    /// Paints over any matter.
+    ReplaceAll,
+    /// Only paints over the masked matter.
+    ReplaceMatter,
+    /// Only erases the masked matter, whatever matter is selected.
+    EraseMatter,
+}
 
-use crate::{pipeline_assets::Matter, settings::SandAppSettings};
+impl PaintMode {
+    pub const ALL: [PaintMode; 4] = [
+        PaintMode::FillEmpty,
+        PaintMode::ReplaceAll,
+        PaintMode::ReplaceMatter,
+        PaintMode::EraseMatter,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PaintMode::FillEmpty => "Fill Empty Only",
+            PaintMode::ReplaceAll => "Replace Everything",
+            PaintMode::ReplaceMatter => "Replace Only",
+            PaintMode::EraseMatter => "Erase Only",
+        }
+    }
+
+    /// Whether the mode only touches the masked matter.
+    pub fn is_masked(&self) -> bool {
+        matches!(self, PaintMode::ReplaceMatter | PaintMode::EraseMatter)
+    }
+}
 
 #[derive(Debug, Resource, Clone, ExtractResource)]
 pub struct AutomataParams {
@@ -18,8 +73,18 @@ pub struct AutomataParams {
     pub is_drawing: bool,
     pub prev_mouse_pos: Vec2,
     pub use_square_brush: bool,
-    pub selected_matter: Matter,
-    pub frame: Arc<Mutex<usize>>,
+    /// Tool painting with the selected brush.
+    pub brush: Brush,
+    /// Id of the painted matter in the [`MatterRegistry`](crate::registry::MatterRegistry).
+    pub selected_matter: u32,
+    pub brush_kind: BrushKind,
+    pub paint_mode: PaintMode,
+    /// Id of the matter the masked paint modes paint over.
+    pub mask_matter: u32,
+    /// Temperature change per frame of the heat and cool brushes.
+    pub heat_strength: f32,
+    /// Strength of the painted wind, from `0.0` to `1.0`.
+    pub wind_strength: f32,
 }
 
 impl Default for AutomataParams {
@@ -30,25 +95,103 @@ impl Default for AutomataParams {
             is_drawing: false,
             mouse_pos: Vec2::ZERO,
             use_square_brush: false,
+            brush: Brush::default(),
             prev_mouse_pos: Vec2::ZERO,
-            selected_matter: Matter::SAND,
-            frame: Arc::new(Mutex::new(0)),
+            selected_matter: 1,
+            brush_kind: BrushKind::Matter,
+            paint_mode: PaintMode::FillEmpty,
+            mask_matter: 1,
+            heat_strength: 50.0,
+            wind_strength: 0.5,
         }
     }
 }
 
 impl AutomataParams {
-    pub fn get_frame(&self) -> usize {
-        *self.frame.lock()
+    /// One of the `SandPushConstants::BRUSH_*` constants.
+    pub fn draw_brush(&self) -> u32 {
+        match self.brush_kind {
+            BrushKind::Matter => SandPushConstants::BRUSH_MATTER,
+            BrushKind::Heat | BrushKind::Cool => SandPushConstants::BRUSH_HEAT,
+            BrushKind::Wind => SandPushConstants::BRUSH_WIND,
+            BrushKind::Calm => SandPushConstants::BRUSH_CALM,
+        }
+    }
+
+    /// One of the `SandPushConstants::PAINT_*` constants.
+    pub fn draw_paint_mode(&self) -> u32 {
+        match self.paint_mode {
+            PaintMode::FillEmpty => SandPushConstants::PAINT_FILL_EMPTY,
+            PaintMode::ReplaceAll => SandPushConstants::PAINT_REPLACE_ALL,
+            PaintMode::ReplaceMatter => SandPushConstants::PAINT_REPLACE_MATTER,
+            PaintMode::EraseMatter => SandPushConstants::PAINT_ERASE_MATTER,
+        }
+    }
+
+    /// Temperature added per frame by the brush, `0.0` when not painting heat.
+    pub fn draw_heat(&self) -> f32 {
+        match self.brush_kind {
+            BrushKind::Heat => self.heat_strength,
+            BrushKind::Cool => -self.heat_strength,
+            _ => 0.0,
+        }
+    }
+
+    /// Wind painted by the brush, following the mouse movement.
+    pub fn draw_wind(&self) -> Vec2 {
+        (self.mouse_pos - self.prev_mouse_pos).normalize_or_zero() * self.wind_strength
     }
 }
 
 pub struct InputPlugin;
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<AutomataParams>()
-            .add_plugin(ExtractResourcePlugin::<AutomataParams>::default())
-            .add_system(update_input_state);
+        app.add_systems(
+            (
+                update_input_state,
+                finish_flood_fill,
+                update_brush,
+                track_strokes,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Keyboard shortcuts controlling the simulation.
+#[derive(SystemParam)]
+pub struct Shortcuts<'w> {
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    clock: ResMut<'w, SimClock>,
+    strokes: ResMut<'w, StrokeHistory>,
+}
+
+impl Shortcuts<'_> {
+    fn update(&mut self, is_drawing: bool) {
+        let keyboard_input = &self.keyboard_input;
+
+        // Pause the simulation
+        if keyboard_input.just_pressed(KeyCode::Space) {
+            self.clock.is_paused = !self.clock.is_paused;
+        }
+
+        // Advance a single tick while paused
+        if keyboard_input.just_pressed(KeyCode::Period) {
+            self.clock.request_step();
+        }
+
+        // Undo and redo brush strokes, not while painting one
+        let is_control = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+        let is_shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        if is_control && !is_drawing {
+            if keyboard_input.just_pressed(KeyCode::Y)
+                || (is_shift && keyboard_input.just_pressed(KeyCode::Z))
+            {
+                self.strokes.redo();
+            } else if keyboard_input.just_pressed(KeyCode::Z) {
+                self.strokes.undo();
+            }
+        }
     }
 }
 
@@ -56,14 +199,18 @@ pub fn update_input_state(
     mut contexts: EguiContexts,
     window_query: Query<&Window>,
     mut params: ResMut<AutomataParams>,
-    mut settings: ResMut<SandAppSettings>,
-    keyboard_input: Res<Input<KeyCode>>,
+    mut shortcuts: Shortcuts,
+    sim_size: Res<SimSize>,
     camera_q: Query<(&Camera, &GlobalTransform)>,
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
 ) {
-    let Ok(primary_window) = window_query.get_single() else { return };
+    let Ok(primary_window) = window_query.get_single() else {
+        return;
+    };
     // get the camera info and transform
-    let Ok((camera, camera_transform)) = camera_q.get_single() else { return };
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
 
     let ctx = contexts.ctx_mut();
     if ctx.wants_pointer_input()
@@ -86,10 +233,7 @@ pub fn update_input_state(
         }
     }
 
-    // Pause the simulation
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        settings.is_paused = !settings.is_paused;
-    }
+    shortcuts.update(params.is_drawing);
 
     if let Some(world_position) = primary_window
         .cursor_position()
@@ -98,6 +242,50 @@ pub fn update_input_state(
     {
         params.prev_mouse_pos = params.mouse_pos;
         params.mouse_pos =
-            crate::utils::world_pos_to_canvas_pos(world_position * Vec2::new(1.0, -1.0));
+            crate::utils::world_pos_to_canvas_pos(world_position * Vec2::new(1.0, -1.0), &sim_size);
+    }
+}
+
+/// Stops the flood fill once it has spread over every cell it can.
+pub fn finish_flood_fill(
+    mut params: ResMut<AutomataParams>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag == FLOOD_READBACK {
+            params.brush.finish_flood_fill(&readback.data);
+        }
     }
 }
+
+pub fn update_brush(
+    mut params: ResMut<AutomataParams>,
+    sim_size: Res<SimSize>,
+    stamp: Res<LoadedStamp>,
+) {
+    let (is_drawing, mouse_pos, prev_mouse_pos) =
+        (params.is_drawing, params.mouse_pos, params.prev_mouse_pos);
+    let transform = params.brush.stamp_transform;
+    params.brush.stamp_size = stamp
+        .size()
+        .map(|size| transform.apply_to_size(size).as_vec2());
+    params
+        .brush
+        .update(is_drawing, mouse_pos, prev_mouse_pos, sim_size.as_vec2());
+}
+
+/// Records the cells under each brush stroke, so it can be undone.
+pub fn track_strokes(
+    params: Res<AutomataParams>,
+    sim_size: Res<SimSize>,
+    mut strokes: ResMut<StrokeHistory>,
+) {
+    let (start, end) = params.brush.painted_bounds(sim_size.as_vec2());
+    strokes.track(
+        params.brush.is_painting(),
+        start,
+        end,
+        params.radius,
+        UVec2::new(sim_size.width, sim_size.height),
+    );
+}
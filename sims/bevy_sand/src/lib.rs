@@ -1,19 +1,54 @@
 mod camera;
 pub mod constants;
+pub mod cpu;
+pub mod import;
 mod input;
 mod pipeline;
-mod pipeline_assets;
-mod settings;
+pub mod pipeline_assets;
+pub mod reactions;
+pub mod registry;
+pub mod settings;
+pub mod snapshot;
+pub mod stamp;
 mod ui;
 mod utils;
 
+use bevy::render::extract_resource::ExtractResourcePlugin;
 use bevy_fn_plugin::bevy_plugin;
+use sim_core::clock::SimClockPlugin;
+use sim_core::headless::SimReadyPlugin;
+use sim_core::history::SimHistoryPlugin;
+use sim_core::inspect::CellInspectorPlugin;
+use sim_core::readback::ReadbackPlugin;
+use sim_core::recording::RecordingPlugin;
+use sim_core::undo::StrokeHistoryPlugin;
+
+/// The simulation without its window, camera and UI, as run by the headless runner.
+#[bevy_plugin]
+pub fn SandSimPlugin(app: &mut App) {
+    app.init_resource::<input::AutomataParams>()
+        .add_plugin(ExtractResourcePlugin::<input::AutomataParams>::default())
+        .add_plugin(SimClockPlugin)
+        .add_plugin(SimReadyPlugin)
+        .add_plugin(SimHistoryPlugin)
+        .add_plugin(StrokeHistoryPlugin)
+        .add_plugin(ReadbackPlugin)
+        .add_plugin(CellInspectorPlugin)
+        .add_plugin(sim_core::snapshot::SnapshotPlugin)
+        .add_plugin(RecordingPlugin::<pipeline_assets::SandPiplineImage>::default())
+        .add_plugin(snapshot::SnapshotPlugin)
+        .add_plugin(sim_core::stamp::StampPlugin { dir: "stamps/sand" })
+        .add_plugin(stamp::StampPlugin)
+        .add_plugin(import::ImportPlugin)
+        .add_plugin(settings::SettingsPlugin)
+        .add_plugin(registry::MatterRegistryPlugin)
+        .add_plugin(pipeline::PipelinesPlugin);
+}
 
 #[bevy_plugin]
 pub fn SandPlugin(app: &mut App) {
-    app.add_plugin(settings::SettingsPlugin)
+    app.add_plugin(SandSimPlugin)
         .add_plugin(input::InputPlugin)
         .add_plugin(camera::CameraPlugin)
-        .add_plugin(ui::SandUIPlugin)
-        .add_plugin(pipeline::PipelinesPlugin);
+        .add_plugin(ui::SandUIPlugin);
 }
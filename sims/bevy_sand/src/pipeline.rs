@@ -1,20 +1,34 @@
+use bevy::ecs::system::SystemParam;
 use bevy::log;
 use bevy::render::extract_resource::ExtractResourcePlugin;
 use bevy::render::render_asset::RenderAssets;
 use bevy::render::render_graph::{self, RenderGraph};
 use bevy::render::render_resource::FilterMode;
-use bevy::render::renderer::RenderDevice;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
 use bevy::render::{RenderApp, RenderSet};
 use bevy::{asset::load_internal_asset, prelude::*, render::render_resource::*};
 
 use crate::constants::{
-    GRID_H, GRID_W, NUM_OF_CELLS, SHADER_CORE, SHADER_DIRECTION, SHADER_MATTER, SHADER_QUERY,
-    SIM_SIZE,
+    SHADER_CORE, SHADER_DIRECTION, SHADER_MATTER, SHADER_QUERY, WORKGROUP_SIZE,
 };
 use crate::input::AutomataParams;
-use crate::pipeline_assets::{Matter, SandPipelineAssets, SandPiplineImage, SandPushConstants};
-use crate::settings::SandAppSettings;
+use crate::pipeline_assets::{
+    GpuChunk, GpuDispatchArgs, GpuMatterDefinition, GpuReaction, Matter, SandMatterTable,
+    SandPipelineAssets, SandPiplineImage, SandPushConstants, SandReactionTable,
+};
+use crate::registry::MatterRegistry;
+use crate::settings::{Boundary, Gravity, SandAppSettings, SimSize};
+use crate::snapshot::{SandWorldUpload, SAVE_READBACK};
+use crate::stamp::STAMP_READBACK;
 use crate::utils;
+use sim_core::brush::{BrushTool, FLOOD_PASSES_PER_FRAME, FLOOD_READBACK};
+use sim_core::clock::SimClock;
+use sim_core::headless::SimReady;
+use sim_core::history::{GpuHistory, HistoryCommand, SimHistory};
+use sim_core::inspect::{CellInspector, INSPECT_READBACK};
+use sim_core::readback::{GpuReadback, ReadbackRequests};
+use sim_core::stamp::{GpuStamp, LoadedStamp};
+use sim_core::undo::{GpuStrokeHistory, StrokeCommand, StrokeHistory};
 
 // ================================== Assets ================================== //
 
@@ -25,7 +39,8 @@ pub struct PipelinesPlugin;
 impl Plugin for PipelinesPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(ExtractResourcePlugin::<SandPiplineImage>::default())
-            .add_startup_system(setup_sand_pipeline);
+            .add_system(resize_sand_canvas.run_if(resource_changed::<SimSize>()))
+            .add_system(clear_history.run_if(resource_changed::<SimSize>()));
 
         load_internal_asset!(app, SHADER_CORE, "shaders/core.wgsl", Shader::from_wgsl);
         load_internal_asset!(app, SHADER_MATTER, "shaders/matter.wgsl", Shader::from_wgsl);
@@ -36,6 +51,18 @@ impl Plugin for PipelinesPlugin {
         render_app
             .init_resource::<SandPipelines>()
             .init_resource::<SandPipelineAssets>()
+            .add_system(prepare_matter_tables.in_set(RenderSet::Prepare))
+            .add_systems(
+                (
+                    prepare_pipeline_assets,
+                    prepare_world_upload,
+                    prepare_active_chunks,
+                    prepare_history,
+                    prepare_strokes,
+                )
+                    .chain()
+                    .in_set(RenderSet::Prepare),
+            )
             .add_system(queue_bind_groups.in_set(RenderSet::Queue));
 
         let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
@@ -46,30 +73,81 @@ impl Plugin for PipelinesPlugin {
 
 // ================================== SETUP ================================== //
 
-pub fn setup_sand_pipeline(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
-    let image = utils::create_texture_2d(SIM_SIZE, PIXELS_TARGET_FORMAT, FilterMode::Nearest);
+/// Marks the sprite showing the simulation.
+#[derive(Component)]
+pub struct SandCanvas;
+
+/// Creates the canvas the simulation is drawn to, and recreates it whenever the [`SimSize`]
+/// changes.
+pub fn resize_sand_canvas(
+    mut commands: Commands,
+    sim_size: Res<SimSize>,
+    mut images: ResMut<Assets<Image>>,
+    sand_image: Option<Res<SandPiplineImage>>,
+    mut canvas: Query<(&mut Sprite, &mut Handle<Image>), With<SandCanvas>>,
+) {
+    let image = utils::create_texture_2d(
+        sim_size.as_tuple(),
+        PIXELS_TARGET_FORMAT,
+        FilterMode::Nearest,
+    );
     let image = images.add(image);
 
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            custom_size: Some(Vec2::new(SIM_SIZE.0 as f32, SIM_SIZE.1 as f32)),
-            ..default()
-        },
-        texture: image.clone(),
-        ..default()
-    });
+    if let Some(sand_image) = sand_image {
+        images.remove(&sand_image.0);
+    }
+
+    if let Ok((mut sprite, mut texture)) = canvas.get_single_mut() {
+        sprite.custom_size = Some(sim_size.as_vec2());
+        *texture = image.clone();
+    } else {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(sim_size.as_vec2()),
+                    ..default()
+                },
+                texture: image.clone(),
+                ..default()
+            },
+            SandCanvas,
+        ));
+    }
 
     commands.insert_resource(SandPiplineImage(image));
 }
 
+/// The recorded states and strokes no longer fit the recreated buffers.
+fn clear_history(
+    sim_size: Res<SimSize>,
+    mut history: ResMut<SimHistory>,
+    mut strokes: ResMut<StrokeHistory>,
+) {
+    // A state is the matter and temperature of every cell
+    let cell_size = std::mem::size_of::<Matter>() + std::mem::size_of::<f32>();
+    history.clear();
+    history.set_state_size((sim_size.num_of_cells() * cell_size) as u64);
+    strokes.clear();
+    strokes.set_cell_size(cell_size as u64);
+}
+
 // ================================== Pipeline ================================== //
 
 #[derive(Resource)]
 pub struct SandPipelines {
     pub pipelines_bind_group_layout: BindGroupLayout,
+    /// Layout of the second bind group of the chunk activation pass.
+    pub chunk_dispatch_bind_group_layout: BindGroupLayout,
 
     pub draw_pipeline: CachedComputePipelineId,
+    pub flood_seed_pipeline: CachedComputePipelineId,
+    pub flood_fill_pipeline: CachedComputePipelineId,
+    pub stamp_pipeline: CachedComputePipelineId,
     pub color_pipeline: CachedComputePipelineId,
+    pub react_pipeline: CachedComputePipelineId,
+    pub exchange_heat_pipeline: CachedComputePipelineId,
+    pub activate_chunks_pipeline: CachedComputePipelineId,
+    pub settle_pipeline: CachedComputePipelineId,
 
     pub rise_swap_pipeline: CachedComputePipelineId,
     pub rise_empty_pipeline: CachedComputePipelineId,
@@ -85,10 +163,36 @@ pub struct SandPipelines {
 }
 
 impl SandPipelines {
+    /// Whether every pipeline compiled, so a step runs all of its passes.
+    fn is_ready(&self, cache: &PipelineCache) -> bool {
+        [
+            self.draw_pipeline,
+            self.flood_seed_pipeline,
+            self.flood_fill_pipeline,
+            self.stamp_pipeline,
+            self.color_pipeline,
+            self.react_pipeline,
+            self.exchange_heat_pipeline,
+            self.activate_chunks_pipeline,
+            self.settle_pipeline,
+            self.rise_swap_pipeline,
+            self.rise_empty_pipeline,
+            self.fall_swap_pipeline,
+            self.fall_empty_pipeline,
+            self.slide_down_swap_pipeline,
+            self.slide_down_empty_pipeline,
+            self.horizontal_empty_pipeline,
+            self.horizontal_swap_pipeline,
+        ]
+        .into_iter()
+        .all(|id| cache.get_compute_pipeline(id).is_some())
+    }
+
     fn dispatch<'a>(
         pass: &mut ComputePass<'a>,
         pipeline: &'a ComputePipeline,
         bind_group: &'a BindGroup,
+        grid: (u32, u32),
         push_constants: Option<&SandPushConstants>,
     ) {
         pass.set_pipeline(pipeline);
@@ -98,7 +202,21 @@ impl SandPipelines {
             pass.set_push_constants(0, push_constants.as_bytes());
         }
 
-        pass.dispatch_workgroups(GRID_W, GRID_H, 1);
+        pass.dispatch_workgroups(grid.0, grid.1, 1);
+    }
+
+    /// Dispatches the movement passes over the chunks listed by `activate_chunks.wgsl` only.
+    fn dispatch_active<'a>(
+        pass: &mut ComputePass<'a>,
+        pipeline: &'a ComputePipeline,
+        bind_group: &'a BindGroup,
+        chunk_dispatch: &'a Buffer,
+        push_constants: &SandPushConstants,
+    ) {
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.set_push_constants(0, push_constants.as_bytes());
+        pass.dispatch_workgroups_indirect(chunk_dispatch, 0);
     }
 
     fn move_once<'a>(
@@ -109,6 +227,11 @@ impl SandPipelines {
         push_constants: &mut SandPushConstants,
         move_step: u32,
     ) {
+        // Without gravity nothing falls, rises or slides
+        if push_constants.gravity_dir == SandPushConstants::NO_GRAVITY {
+            return;
+        }
+
         if let (
             Some(fall_empty_pipeline),
             Some(fall_swap_pipeline),
@@ -127,35 +250,51 @@ impl SandPipelines {
             push_constants.move_step = move_step;
 
             // Fall Pipelines
-            SandPipelines::dispatch(
+            SandPipelines::dispatch_active(
                 pass,
                 fall_empty_pipeline,
                 &bind_groups.bind_group_main,
-                None,
+                &bind_groups.chunk_dispatch,
+                push_constants,
+            );
+            SandPipelines::dispatch_active(
+                pass,
+                fall_swap_pipeline,
+                &bind_groups.bind_group_swap,
+                &bind_groups.chunk_dispatch,
+                push_constants,
             );
-            SandPipelines::dispatch(pass, fall_swap_pipeline, &bind_groups.bind_group_swap, None);
 
             // Risers
-            SandPipelines::dispatch(
+            SandPipelines::dispatch_active(
                 pass,
                 rise_empty_pipeline,
                 &bind_groups.bind_group_main,
-                None,
+                &bind_groups.chunk_dispatch,
+                push_constants,
+            );
+            SandPipelines::dispatch_active(
+                pass,
+                rise_swap_pipeline,
+                &bind_groups.bind_group_swap,
+                &bind_groups.chunk_dispatch,
+                push_constants,
             );
-            SandPipelines::dispatch(pass, rise_swap_pipeline, &bind_groups.bind_group_swap, None);
 
             // Sliders
-            SandPipelines::dispatch(
+            SandPipelines::dispatch_active(
                 pass,
                 slide_down_empty_pipeline,
                 &bind_groups.bind_group_main,
-                Some(push_constants),
+                &bind_groups.chunk_dispatch,
+                push_constants,
             );
-            SandPipelines::dispatch(
+            SandPipelines::dispatch_active(
                 pass,
                 slide_down_swap_pipeline,
                 &bind_groups.bind_group_swap,
-                Some(push_constants),
+                &bind_groups.chunk_dispatch,
+                push_constants,
             );
         }
     }
@@ -177,21 +316,107 @@ impl SandPipelines {
             for dispersion_step in 0..dispersion_steps {
                 push_constants.dispersion_step = dispersion_step;
 
-                SandPipelines::dispatch(
+                SandPipelines::dispatch_active(
                     pass,
                     horizontal_empty_pipeline,
                     &bind_groups.bind_group_main,
-                    Some(push_constants),
+                    &bind_groups.chunk_dispatch,
+                    push_constants,
                 );
-                SandPipelines::dispatch(
+                SandPipelines::dispatch_active(
                     pass,
                     horizontal_swap_pipeline,
                     &bind_groups.bind_group_swap,
-                    Some(push_constants),
+                    &bind_groups.chunk_dispatch,
+                    push_constants,
                 );
             }
         }
     }
+
+    /// Stops the remaining movement passes of the step when the last movement step moved nothing.
+    fn settle<'a>(
+        &self,
+        cache: &'a PipelineCache,
+        pass: &mut ComputePass<'a>,
+        bind_groups: &'a SandPipelineBindGroups,
+    ) {
+        if let Some(settle_pipeline) = cache.get_compute_pipeline(self.settle_pipeline) {
+            pass.set_pipeline(settle_pipeline);
+            pass.set_bind_group(0, &bind_groups.bind_group_main, &[]);
+            pass.set_bind_group(1, &bind_groups.chunk_dispatch_bind_group, &[]);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+    }
+
+    /// Runs one simulation step, `push_constants.sim_step` is the index of the step.
+    fn step<'a>(
+        &self,
+        cache: &'a PipelineCache,
+        pass: &mut ComputePass<'a>,
+        bind_groups: &'a SandPipelineBindGroups,
+        push_constants: &mut SandPushConstants,
+        settings: &SandAppSettings,
+    ) {
+        let grid = bind_groups.size.grid();
+
+        // ACTIVE CHUNKS
+        if let Some(activate_chunks_pipeline) =
+            cache.get_compute_pipeline(self.activate_chunks_pipeline)
+        {
+            pass.set_pipeline(activate_chunks_pipeline);
+            pass.set_bind_group(0, &bind_groups.bind_group_main, &[]);
+            pass.set_bind_group(1, &bind_groups.chunk_dispatch_bind_group, &[]);
+            pass.set_push_constants(0, push_constants.as_bytes());
+            let (chunks_w, chunks_h) = bind_groups.size.chunks();
+            pass.dispatch_workgroups(
+                chunks_w.div_ceil(WORKGROUP_SIZE),
+                chunks_h.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+
+        // MOVE PIPELINES
+        // The adaptive schedule can't tell how many movement steps will run, so all of them are
+        // recorded and the ones after the world settled are dispatched without workgroups
+        for move_step in 0..settings.movement_steps {
+            let direction = push_constants.dispersion_dir_after(move_step);
+            self.move_once(cache, pass, bind_groups, push_constants, move_step);
+            self.disperse(
+                cache,
+                pass,
+                bind_groups,
+                push_constants,
+                direction,
+                settings.dispersion_steps,
+            );
+
+            if settings.adaptive_movement {
+                self.settle(cache, pass, bind_groups);
+            }
+        }
+
+        // REACTIONS & HEAT
+        if let (Some(react_pipeline), Some(exchange_heat_pipeline)) = (
+            cache.get_compute_pipeline(self.react_pipeline),
+            cache.get_compute_pipeline(self.exchange_heat_pipeline),
+        ) {
+            SandPipelines::dispatch(
+                pass,
+                react_pipeline,
+                &bind_groups.bind_group_main,
+                grid,
+                Some(push_constants),
+            );
+            SandPipelines::dispatch(
+                pass,
+                exchange_heat_pipeline,
+                &bind_groups.bind_group_swap,
+                grid,
+                Some(push_constants),
+            );
+        }
+    }
 }
 
 impl FromWorld for SandPipelines {
@@ -209,9 +434,7 @@ impl FromWorld for SandPipelines {
                         ty: BindingType::Buffer {
                             ty: BufferBindingType::Storage { read_only: false },
                             has_dynamic_offset: false,
-                            min_binding_size: BufferSize::new(
-                                (std::mem::size_of::<Matter>() * NUM_OF_CELLS) as _,
-                            ),
+                            min_binding_size: BufferSize::new(std::mem::size_of::<Matter>() as _),
                         },
                     },
                     BindGroupLayoutEntry {
@@ -221,9 +444,7 @@ impl FromWorld for SandPipelines {
                         ty: BindingType::Buffer {
                             ty: BufferBindingType::Storage { read_only: false },
                             has_dynamic_offset: false,
-                            min_binding_size: BufferSize::new(
-                                (std::mem::size_of::<Matter>() * NUM_OF_CELLS) as _,
-                            ),
+                            min_binding_size: BufferSize::new(std::mem::size_of::<Matter>() as _),
                         },
                     },
                     // Sand texture.
@@ -237,9 +458,141 @@ impl FromWorld for SandPipelines {
                         },
                         count: None,
                     },
+                    // Matter table.
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(
+                                std::mem::size_of::<GpuMatterDefinition>() as _,
+                            ),
+                        },
+                    },
+                    // Temperature.
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<f32>() as _),
+                        },
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 5,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<f32>() as _),
+                        },
+                    },
+                    // Reaction table.
+                    BindGroupLayoutEntry {
+                        binding: 6,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(
+                                std::mem::size_of::<GpuReaction>() as _
+                            ),
+                        },
+                    },
+                    // Wind.
+                    BindGroupLayoutEntry {
+                        binding: 7,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<[f32; 2]>() as _),
+                        },
+                    },
+                    // Chunks.
+                    BindGroupLayoutEntry {
+                        binding: 8,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<GpuChunk>() as _),
+                        },
+                    },
+                    // Active chunks.
+                    BindGroupLayoutEntry {
+                        binding: 9,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<u32>() as _),
+                        },
+                    },
+                    // Moved counter.
+                    BindGroupLayoutEntry {
+                        binding: 10,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<u32>() as _),
+                        },
+                    },
+                    // Flood fill.
+                    BindGroupLayoutEntry {
+                        binding: 11,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(
+                                (2 * std::mem::size_of::<u32>()) as _,
+                            ),
+                        },
+                    },
+                    // Stamp.
+                    BindGroupLayoutEntry {
+                        binding: 12,
+                        count: None,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: BufferSize::new(std::mem::size_of::<u32>() as _),
+                        },
+                    },
                 ],
             });
 
+        let chunk_dispatch_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("chunk_dispatch_bind_group_layout"),
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(
+                            std::mem::size_of::<GpuDispatchArgs>() as _
+                        ),
+                    },
+                }],
+            });
+
         let (
             // fall
             shader_fall_empty,
@@ -256,6 +609,10 @@ impl FromWorld for SandPipelines {
             // misc
             shader_draw,
             shader_color,
+            shader_react,
+            shader_exchange_heat,
+            shader_activate_chunks,
+            shader_settle,
         ) = {
             let assets_server = world.resource::<AssetServer>();
             (
@@ -274,6 +631,10 @@ impl FromWorld for SandPipelines {
                 // misc
                 assets_server.load("shaders/draw.wgsl"),
                 assets_server.load("shaders/color.wgsl"),
+                assets_server.load("shaders/react.wgsl"),
+                assets_server.load("shaders/exchange_heat.wgsl"),
+                assets_server.load("shaders/activate_chunks.wgsl"),
+                assets_server.load("shaders/settle.wgsl"),
             )
         };
 
@@ -283,19 +644,27 @@ impl FromWorld for SandPipelines {
             pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
                 shader_defs: vec![],
                 shader: shader_fall_empty,
-                push_constant_ranges: vec![],
                 entry_point: PIPELINE_ENTRY.into(),
                 label: Some("fall_empty_pipeline".into()),
                 layout: vec![pipelines_bind_group_layout.clone()],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
             });
 
         let fall_swap_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             shader_defs: vec![],
             shader: shader_fall_swap,
-            push_constant_ranges: vec![],
             entry_point: PIPELINE_ENTRY.into(),
             label: Some("fall_swap_pipeline".into()),
             layout: vec![pipelines_bind_group_layout.clone()],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
         });
 
         let slide_down_empty_pipeline =
@@ -357,25 +726,33 @@ impl FromWorld for SandPipelines {
         let rise_empty_pipeline =
             pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
                 shader_defs: vec![],
-                push_constant_ranges: vec![],
                 shader: shader_rise_empty,
                 entry_point: PIPELINE_ENTRY.into(),
                 label: Some("rise_empty_pipeline".into()),
                 layout: vec![pipelines_bind_group_layout.clone()],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
             });
 
         let rise_swap_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             shader_defs: vec![],
-            push_constant_ranges: vec![],
             shader: shader_rise_swap,
             entry_point: PIPELINE_ENTRY.into(),
             label: Some("rise_swap_pipeline".into()),
             layout: vec![pipelines_bind_group_layout.clone()],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
         });
 
         let draw_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             shader_defs: vec![],
-            shader: shader_draw,
+            shader: shader_draw.clone(),
             entry_point: PIPELINE_ENTRY.into(),
             label: Some("draw_pipeline".into()),
             layout: vec![pipelines_bind_group_layout.clone()],
@@ -386,18 +763,130 @@ impl FromWorld for SandPipelines {
             .to_vec(),
         });
 
+        let flood_seed_pipeline =
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                shader_defs: vec![],
+                shader: shader_draw.clone(),
+                entry_point: "flood_seed".into(),
+                label: Some("flood_seed_pipeline".into()),
+                layout: vec![pipelines_bind_group_layout.clone()],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
+            });
+
+        let flood_fill_pipeline =
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                shader_defs: vec![],
+                shader: shader_draw.clone(),
+                entry_point: "flood_fill".into(),
+                label: Some("flood_fill_pipeline".into()),
+                layout: vec![pipelines_bind_group_layout.clone()],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
+            });
+
+        let stamp_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            shader_defs: vec![],
+            shader: shader_draw,
+            entry_point: "place_stamp".into(),
+            label: Some("stamp_pipeline".into()),
+            layout: vec![pipelines_bind_group_layout.clone()],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
+        });
+
         let color_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             shader_defs: vec![],
             shader: shader_color,
-            push_constant_ranges: vec![],
             entry_point: PIPELINE_ENTRY.into(),
             label: Some("color_pipeline".into()),
             layout: vec![pipelines_bind_group_layout.clone()],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
+        });
+
+        let react_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            shader_defs: vec![],
+            shader: shader_react,
+            entry_point: PIPELINE_ENTRY.into(),
+            label: Some("react_pipeline".into()),
+            layout: vec![pipelines_bind_group_layout.clone()],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
+        });
+
+        let exchange_heat_pipeline =
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                shader_defs: vec![],
+                shader: shader_exchange_heat,
+                entry_point: PIPELINE_ENTRY.into(),
+                label: Some("exchange_heat_pipeline".into()),
+                layout: vec![pipelines_bind_group_layout.clone()],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
+            });
+
+        let activate_chunks_pipeline =
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                shader_defs: vec![],
+                shader: shader_activate_chunks,
+                entry_point: PIPELINE_ENTRY.into(),
+                label: Some("activate_chunks_pipeline".into()),
+                layout: vec![
+                    pipelines_bind_group_layout.clone(),
+                    chunk_dispatch_bind_group_layout.clone(),
+                ],
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+                }]
+                .to_vec(),
+            });
+
+        let settle_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            shader_defs: vec![],
+            shader: shader_settle,
+            entry_point: PIPELINE_ENTRY.into(),
+            label: Some("settle_pipeline".into()),
+            layout: vec![
+                pipelines_bind_group_layout.clone(),
+                chunk_dispatch_bind_group_layout.clone(),
+            ],
+            push_constant_ranges: [PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..std::mem::size_of::<SandPushConstants>() as u32,
+            }]
+            .to_vec(),
         });
 
         SandPipelines {
             draw_pipeline,
+            flood_seed_pipeline,
+            flood_fill_pipeline,
+            stamp_pipeline,
             color_pipeline,
+            react_pipeline,
+            exchange_heat_pipeline,
+            activate_chunks_pipeline,
+            settle_pipeline,
 
             fall_swap_pipeline,
             fall_empty_pipeline,
@@ -412,37 +901,228 @@ impl FromWorld for SandPipelines {
             horizontal_empty_pipeline,
 
             pipelines_bind_group_layout,
+            chunk_dispatch_bind_group_layout,
         }
     }
 }
 
+// ================================== Matter Table ================================== //
+
+fn prepare_matter_tables(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    registry: Option<Res<MatterRegistry>>,
+) {
+    let Some(registry) = registry else { return };
+    if registry.is_changed() {
+        let matter_table = utils::create_storage_buffer_with_data(
+            &render_device,
+            &registry.gpu_table(),
+            Some("Matter Table"),
+        );
+        commands.insert_resource(SandMatterTable(matter_table));
+
+        let reaction_table = utils::create_storage_buffer_with_data(
+            &render_device,
+            &registry.reaction_table().gpu_data(),
+            Some("Reaction Table"),
+        );
+        commands.insert_resource(SandReactionTable(reaction_table));
+    }
+}
+
+// ================================== Buffers ================================== //
+
+fn prepare_pipeline_assets(
+    sim_size: Res<SimSize>,
+    render_device: Res<RenderDevice>,
+    mut sand_compute_assets: ResMut<SandPipelineAssets>,
+) {
+    if sand_compute_assets.size != *sim_size {
+        *sand_compute_assets = SandPipelineAssets::new(&render_device, *sim_size);
+    }
+}
+
+/// Writes a loaded world into both buffers, the wind is left as it is.
+fn prepare_world_upload(
+    render_queue: Res<RenderQueue>,
+    upload: Res<SandWorldUpload>,
+    sand_compute_assets: Res<SandPipelineAssets>,
+) {
+    let Some(world) = upload.0.as_ref() else {
+        return;
+    };
+    if world.size != sand_compute_assets.size {
+        return;
+    }
+
+    for matter in [
+        &sand_compute_assets.matter_in,
+        &sand_compute_assets.matter_out,
+    ] {
+        render_queue.write_buffer(matter, 0, bytemuck::cast_slice(&world.matter));
+    }
+    for temperature in [
+        &sand_compute_assets.temperature_in,
+        &sand_compute_assets.temperature_out,
+    ] {
+        render_queue.write_buffer(temperature, 0, bytemuck::cast_slice(&world.temperature));
+    }
+}
+
+// ================================== Chunks ================================== //
+
+/// Everything that replaces the cells of the world this frame.
+#[derive(SystemParam)]
+struct WorldRestores<'w> {
+    history: Res<'w, SimHistory>,
+    strokes: Res<'w, StrokeHistory>,
+    upload: Res<'w, SandWorldUpload>,
+}
+
+impl WorldRestores<'_> {
+    fn is_restoring(&self) -> bool {
+        matches!(self.history.command(), Some(HistoryCommand::Restore { .. }))
+            || matches!(self.strokes.command(), Some(StrokeCommand::Swap { .. }))
+            || self.upload.0.is_some()
+    }
+}
+
+fn prepare_active_chunks(
+    render_queue: Res<RenderQueue>,
+    sand_compute_assets: Res<SandPipelineAssets>,
+    clock: Res<SimClock>,
+    settings: Res<SandAppSettings>,
+    registry: Option<Res<MatterRegistry>>,
+    restores: WorldRestores,
+    mut woken_with: Local<Option<(Gravity, Boundary, bool)>>,
+) {
+    // Settled matter may move again with other settings, matters or cells, so every chunk is woken
+    // up for a few steps
+    let wake_with = Some((settings.gravity, settings.boundary, clock.is_paused));
+    let registry_changed = registry.is_some_and(|registry| registry.is_changed());
+    if *woken_with != wake_with || registry_changed || restores.is_restoring() {
+        *woken_with = wake_with;
+        let chunk = GpuChunk {
+            dirty: clock.step + 2,
+            awake: 1,
+        };
+        render_queue.write_buffer(
+            &sand_compute_assets.chunks,
+            0,
+            bytemuck::cast_slice(&vec![chunk; sand_compute_assets.size.num_of_chunks()]),
+        );
+    }
+}
+
+// ================================== History ================================== //
+
+/// Only the matter and the temperature are recorded, the wind is painted rather than simulated.
+fn prepare_history(
+    render_device: Res<RenderDevice>,
+    history: Res<SimHistory>,
+    sand_compute_assets: Res<SandPipelineAssets>,
+    mut gpu_history: ResMut<GpuHistory>,
+) {
+    gpu_history.prepare(
+        &render_device,
+        &history,
+        &[
+            sand_compute_assets.matter_in.size(),
+            sand_compute_assets.temperature_in.size(),
+        ],
+    );
+}
+
+/// Same buffers as the history, the wind painted by a stroke stays when it is undone.
+fn prepare_strokes(
+    render_device: Res<RenderDevice>,
+    strokes: Res<StrokeHistory>,
+    sand_compute_assets: Res<SandPipelineAssets>,
+    mut gpu_strokes: ResMut<GpuStrokeHistory>,
+) {
+    let size = sand_compute_assets.size;
+    let num_of_cells = size.num_of_cells() as u64;
+    gpu_strokes.prepare(
+        &render_device,
+        &strokes,
+        UVec2::new(size.width, size.height),
+        &[
+            sand_compute_assets.matter_in.size() / num_of_cells,
+            sand_compute_assets.temperature_in.size() / num_of_cells,
+        ],
+    );
+}
+
 // ================================== Bindgroups ================================== //
 
 #[derive(Resource)]
 pub struct SandPipelineBindGroups {
+    /// Size of the bound buffers and texture.
+    pub size: SimSize,
     pub bind_group_main: BindGroup,
     pub bind_group_swap: BindGroup,
+    pub chunk_dispatch_bind_group: BindGroup,
+    /// Indirect dispatch arguments of the movement passes.
+    pub chunk_dispatch: Buffer,
+    /// Cells changed by the movement passes, only read by the adaptive movement schedule.
+    pub moved: Buffer,
+    /// Cleared when a flood fill starts.
+    pub flood_fill: Buffer,
+}
+
+/// The canvas and tables bound next to the buffers of [`SandPipelineAssets`].
+#[derive(SystemParam)]
+struct BoundResources<'w> {
+    sand_image: Res<'w, SandPiplineImage>,
+    gpu_images: Res<'w, RenderAssets<Image>>,
+    stamp: Res<'w, GpuStamp>,
+    matter_table: Option<Res<'w, SandMatterTable>>,
+    reaction_table: Option<Res<'w, SandReactionTable>>,
 }
 
 fn queue_bind_groups(
     mut commands: Commands,
-    params: Res<AutomataParams>,
+    clock: Res<SimClock>,
     pipelines: Res<SandPipelines>,
     render_device: Res<RenderDevice>,
-    sand_image: Res<SandPiplineImage>,
-    gpu_images: Res<RenderAssets<Image>>,
     sand_compute_assets: Res<SandPipelineAssets>,
+    bound: BoundResources,
 ) {
-    let sand_view_image = &gpu_images[&sand_image];
-    let (buffer_src, buffer_dst) = if *params.frame.lock() % 2 == 0 {
+    let BoundResources {
+        sand_image,
+        gpu_images,
+        stamp,
+        matter_table,
+        reaction_table,
+    } = bound;
+    // The registry is loaded asynchronously, nothing can run until it has been uploaded.
+    let (Some(matter_table), Some(reaction_table)) = (matter_table, reaction_table) else {
+        return;
+    };
+
+    // Right after a resize the new texture may not be uploaded yet
+    let Some(sand_view_image) = gpu_images
+        .get(&sand_image)
+        .filter(|image| image.size == sand_compute_assets.size.as_vec2())
+    else {
+        commands.remove_resource::<SandPipelineBindGroups>();
+        return;
+    };
+    let (buffer_src, buffer_dst, temperature_src, temperature_dst) = if clock.step.is_multiple_of(2)
+    {
         (
             &sand_compute_assets.matter_in,
             &sand_compute_assets.matter_out,
+            &sand_compute_assets.temperature_in,
+            &sand_compute_assets.temperature_out,
         )
     } else {
         (
             &sand_compute_assets.matter_out,
             &sand_compute_assets.matter_in,
+            &sand_compute_assets.temperature_out,
+            &sand_compute_assets.temperature_in,
         )
     };
 
@@ -462,6 +1142,46 @@ fn queue_bind_groups(
                 binding: 2,
                 resource: BindingResource::TextureView(&sand_view_image.texture_view),
             },
+            BindGroupEntry {
+                binding: 3,
+                resource: matter_table.0.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: temperature_src.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: temperature_dst.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 6,
+                resource: reaction_table.0.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 7,
+                resource: sand_compute_assets.wind.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 8,
+                resource: sand_compute_assets.chunks.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 9,
+                resource: sand_compute_assets.active_chunks.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 10,
+                resource: sand_compute_assets.moved.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 11,
+                resource: sand_compute_assets.flood_fill.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 12,
+                resource: stamp.buffer.as_entire_binding(),
+            },
         ],
     });
 
@@ -481,12 +1201,66 @@ fn queue_bind_groups(
                 binding: 2,
                 resource: BindingResource::TextureView(&sand_view_image.texture_view),
             },
+            BindGroupEntry {
+                binding: 3,
+                resource: matter_table.0.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: temperature_dst.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: temperature_src.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 6,
+                resource: reaction_table.0.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 7,
+                resource: sand_compute_assets.wind.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 8,
+                resource: sand_compute_assets.chunks.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 9,
+                resource: sand_compute_assets.active_chunks.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 10,
+                resource: sand_compute_assets.moved.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 11,
+                resource: sand_compute_assets.flood_fill.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 12,
+                resource: stamp.buffer.as_entire_binding(),
+            },
         ],
     });
 
+    let chunk_dispatch_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+        label: "chunk_dispatch_bind_group".into(),
+        layout: &pipelines.chunk_dispatch_bind_group_layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: sand_compute_assets.chunk_dispatch.as_entire_binding(),
+        }],
+    });
+
     commands.insert_resource(SandPipelineBindGroups {
+        size: sand_compute_assets.size,
         bind_group_main,
         bind_group_swap,
+        chunk_dispatch_bind_group,
+        chunk_dispatch: sand_compute_assets.chunk_dispatch.clone(),
+        moved: sand_compute_assets.moved.clone(),
+        flood_fill: sand_compute_assets.flood_fill.clone(),
     });
 }
 
@@ -496,11 +1270,6 @@ fn queue_bind_groups(
 struct Sand2DNode;
 
 impl render_graph::Node for Sand2DNode {
-    fn update(&mut self, world: &mut World) {
-        let params = world.resource_mut::<AutomataParams>();
-        *params.frame.lock() += 1;
-    }
-
     fn run(
         &self,
         _: &mut render_graph::RenderGraphContext,
@@ -512,95 +1281,278 @@ impl render_graph::Node for Sand2DNode {
             let pipelines = world.resource::<SandPipelines>();
             let params = &world.resource::<AutomataParams>();
             let settings = &world.resource::<SandAppSettings>();
+            let clock = world.resource::<SimClock>();
+            let grid = pipeline_bind_groups.size.grid();
+
+            // HISTORY
+            // The color pass leaves the same cells in both buffers, so `matter_in` holds the
+            // input of this frame
+            let sand_compute_assets = world.resource::<SandPipelineAssets>();
+            let gpu_history = world.resource::<GpuHistory>();
+            match world.resource::<SimHistory>().command() {
+                Some(HistoryCommand::Capture { slot }) => gpu_history.capture(
+                    render_context.command_encoder(),
+                    slot,
+                    &[
+                        &sand_compute_assets.matter_in,
+                        &sand_compute_assets.temperature_in,
+                    ],
+                ),
+                Some(HistoryCommand::Restore { slot }) => {
+                    gpu_history.restore(
+                        render_context.command_encoder(),
+                        slot,
+                        &[
+                            &sand_compute_assets.matter_in,
+                            &sand_compute_assets.temperature_in,
+                        ],
+                    );
+                    gpu_history.restore(
+                        render_context.command_encoder(),
+                        slot,
+                        &[
+                            &sand_compute_assets.matter_out,
+                            &sand_compute_assets.temperature_out,
+                        ],
+                    );
+                }
+                None => {}
+            }
+
+            // UNDO
+            // Undone strokes are written to both buffers, like restored states
+            world.resource::<GpuStrokeHistory>().run(
+                render_context.command_encoder(),
+                world.resource::<StrokeHistory>(),
+                &[
+                    &sand_compute_assets.matter_in,
+                    &sand_compute_assets.temperature_in,
+                ],
+                &[
+                    &sand_compute_assets.matter_out,
+                    &sand_compute_assets.temperature_out,
+                ],
+            );
+
+            if pipelines.is_ready(pipeline_cache) {
+                world.resource::<SimReady>().mark();
+            }
+
+            // SNAPSHOT
+            let is_requested = |tag| {
+                world
+                    .get_resource::<ReadbackRequests>()
+                    .is_some_and(|requests| requests.is_requested(tag))
+            };
+            if is_requested(SAVE_READBACK) {
+                world.resource::<GpuReadback>().read_buffers(
+                    world.resource::<RenderDevice>(),
+                    render_context.command_encoder(),
+                    SAVE_READBACK,
+                    &[
+                        (
+                            &sand_compute_assets.matter_in,
+                            0..sand_compute_assets.matter_in.size(),
+                        ),
+                        (
+                            &sand_compute_assets.temperature_in,
+                            0..sand_compute_assets.temperature_in.size(),
+                        ),
+                    ],
+                );
+            }
+            if is_requested(STAMP_READBACK) {
+                world.resource::<GpuReadback>().read_buffers(
+                    world.resource::<RenderDevice>(),
+                    render_context.command_encoder(),
+                    STAMP_READBACK,
+                    &[(
+                        &sand_compute_assets.matter_in,
+                        0..sand_compute_assets.matter_in.size(),
+                    )],
+                );
+            }
+
+            // INSPECT
+            // The cell under the mouse followed by its temperature
+            let size = sand_compute_assets.size;
+            let inspected = world
+                .get_resource::<CellInspector>()
+                .and_then(|inspector| inspector.request())
+                .filter(|cell| cell.x < size.width && cell.y < size.height);
+            if let Some(cell) = inspected {
+                let index = (cell.y * size.width + cell.x) as u64;
+                let matter_size = std::mem::size_of::<Matter>() as u64;
+                let temperature_size = std::mem::size_of::<f32>() as u64;
+                world.resource::<GpuReadback>().read_buffers(
+                    world.resource::<RenderDevice>(),
+                    render_context.command_encoder(),
+                    INSPECT_READBACK,
+                    &[
+                        (
+                            &sand_compute_assets.matter_in,
+                            index * matter_size..(index + 1) * matter_size,
+                        ),
+                        (
+                            &sand_compute_assets.temperature_in,
+                            index * temperature_size..(index + 1) * temperature_size,
+                        ),
+                    ],
+                );
+            }
 
             if let (Some(draw_pipeline), Some(color_pipeline)) = (
                 pipeline_cache.get_compute_pipeline(pipelines.draw_pipeline),
                 pipeline_cache.get_compute_pipeline(pipelines.color_pipeline),
             ) {
-                let mut pass =
-                    render_context
-                        .command_encoder()
-                        .begin_compute_pass(&ComputePassDescriptor {
-                            label: Some("sand_2d"),
-                        });
-
+                let (draw_start, draw_end) = params.brush.segment();
+                let stamp_size = world
+                    .resource::<LoadedStamp>()
+                    .size()
+                    .unwrap_or(UVec2::ZERO);
                 let mut pc = SandPushConstants {
                     draw_radius: params.radius,
-                    sim_step: params.get_frame() as u32,
-                    draw_start: params.mouse_pos.to_array(),
-                    draw_end: params.prev_mouse_pos.to_array(),
+                    sim_step: clock.step,
+                    draw_start: draw_start.to_array(),
+                    draw_end: draw_end.to_array(),
                     draw_square: params.use_square_brush as u32,
-                    seed: settings.get_current_seed(),
-                    matter: params.selected_matter,
+                    seed: settings.seed,
+                    draw_matter: params.selected_matter,
+                    draw_heat: params.draw_heat(),
+                    draw_wind: params.draw_wind().to_array(),
+                    brush: params.draw_brush(),
+                    gravity_dir: settings.gravity as u32,
+                    boundary: settings.boundary as u32,
+                    tool: params.brush.tool as u32,
+                    filled: params.brush.filled as u32,
+                    spray_density: params.brush.spray_density,
+                    brush_seed: params.brush.seed(),
+                    paint_mode: params.draw_paint_mode(),
+                    mask_matter: params.mask_matter,
+                    stamp_size: stamp_size.to_array(),
+                    stamp_transform: params.brush.stamp_transform.bits(),
                     ..SandPushConstants::default()
                 };
 
                 // DRAW
-                if params.is_drawing {
-                    pass.set_pipeline(draw_pipeline);
-                    pass.set_bind_group(0, &pipeline_bind_groups.bind_group_main, &[]);
-                    pass.set_push_constants(0, pc.as_bytes());
-                    pass.dispatch_workgroups(GRID_W, GRID_H, 1);
+                if let Some(fill) = params.brush.flood_fill() {
+                    if let (Some(flood_seed_pipeline), Some(flood_fill_pipeline)) = (
+                        pipeline_cache.get_compute_pipeline(pipelines.flood_seed_pipeline),
+                        pipeline_cache.get_compute_pipeline(pipelines.flood_fill_pipeline),
+                    ) {
+                        // The cells reached are counted again every frame
+                        let u32_size = std::mem::size_of::<u32>() as u64;
+                        let (clear_start, clear_size) = match fill.frame {
+                            0 => (0, None),
+                            _ => (2 * u32_size, BufferSize::new(u32_size)),
+                        };
+                        render_context.command_encoder().clear_buffer(
+                            &pipeline_bind_groups.flood_fill,
+                            clear_start,
+                            clear_size,
+                        );
+
+                        let mut pass = render_context.command_encoder().begin_compute_pass(
+                            &ComputePassDescriptor {
+                                label: Some("sand_2d_flood_fill"),
+                            },
+                        );
+
+                        // The fill spreads over a few frames
+                        if fill.frame == 0 {
+                            SandPipelines::dispatch(
+                                &mut pass,
+                                flood_seed_pipeline,
+                                &pipeline_bind_groups.bind_group_main,
+                                (1, 1),
+                                Some(&pc),
+                            );
+                        }
+                        for _ in 0..FLOOD_PASSES_PER_FRAME {
+                            SandPipelines::dispatch(
+                                &mut pass,
+                                flood_fill_pipeline,
+                                &pipeline_bind_groups.bind_group_main,
+                                grid,
+                                Some(&pc),
+                            );
+                        }
+                        drop(pass);
+
+                        world.resource::<GpuReadback>().read_buffers(
+                            world.resource::<RenderDevice>(),
+                            render_context.command_encoder(),
+                            FLOOD_READBACK,
+                            &[(&pipeline_bind_groups.flood_fill, u32_size..3 * u32_size)],
+                        );
+                    }
+                } else if params.brush.is_painting() {
+                    let paint_pipeline = match params.brush.tool {
+                        BrushTool::Stamp => {
+                            pipeline_cache.get_compute_pipeline(pipelines.stamp_pipeline)
+                        }
+                        _ => Some(draw_pipeline),
+                    };
+                    if let Some(paint_pipeline) = paint_pipeline {
+                        let mut pass = render_context.command_encoder().begin_compute_pass(
+                            &ComputePassDescriptor {
+                                label: Some("sand_2d_draw"),
+                            },
+                        );
+
+                        pass.set_pipeline(paint_pipeline);
+                        pass.set_bind_group(0, &pipeline_bind_groups.bind_group_main, &[]);
+                        pass.set_push_constants(0, pc.as_bytes());
+                        pass.dispatch_workgroups(grid.0, grid.1, 1);
+                    }
                 }
 
-                if !settings.is_paused {
-                    // MOVE PIPELINES
-                    pipelines.move_once(
-                        pipeline_cache,
-                        &mut pass,
-                        pipeline_bind_groups,
-                        &mut pc,
+                for step in clock.steps() {
+                    pc.sim_step = step;
+
+                    // The active chunks are counted again by each step
+                    render_context.command_encoder().clear_buffer(
+                        &pipeline_bind_groups.chunk_dispatch,
                         0,
+                        BufferSize::new(std::mem::size_of::<u32>() as _),
                     );
-                    pipelines.disperse(
+                    // Drawing, reactions and heat don't count as movement
+                    render_context.command_encoder().clear_buffer(
+                        &pipeline_bind_groups.moved,
+                        0,
+                        None,
+                    );
+
+                    let mut pass = render_context.command_encoder().begin_compute_pass(
+                        &ComputePassDescriptor {
+                            label: Some("sand_2d_step"),
+                        },
+                    );
+
+                    pipelines.step(
                         pipeline_cache,
                         &mut pass,
                         pipeline_bind_groups,
                         &mut pc,
-                        (params.get_frame() % 2 == 0) as u32,
-                        settings.dispersion_steps,
+                        settings,
                     );
-
-                    let mut should_disperse = false;
-                    if settings.movement_steps > 1 {
-                        pipelines.move_once(
-                            pipeline_cache,
-                            &mut pass,
-                            pipeline_bind_groups,
-                            &mut pc,
-                            1,
-                        );
-                        should_disperse = true;
-                    }
-                    if settings.movement_steps > 2 {
-                        pipelines.move_once(
-                            pipeline_cache,
-                            &mut pass,
-                            pipeline_bind_groups,
-                            &mut pc,
-                            2,
-                        );
-                        should_disperse = true;
-                    }
-
-                    if should_disperse {
-                        pipelines.disperse(
-                            pipeline_cache,
-                            &mut pass,
-                            pipeline_bind_groups,
-                            &mut pc,
-                            (params.get_frame() % 2 != 0) as u32,
-                            settings.dispersion_steps,
-                        );
-                    }
                 }
 
                 // COLOR
+                // Also copies the cells to the buffers read as input by the next frame
+                let mut pass =
+                    render_context
+                        .command_encoder()
+                        .begin_compute_pass(&ComputePassDescriptor {
+                            label: Some("sand_2d_color"),
+                        });
+
                 SandPipelines::dispatch(
                     &mut pass,
                     color_pipeline,
                     &pipeline_bind_groups.bind_group_main,
-                    None,
+                    grid,
+                    Some(&pc),
                 );
             }
         } else {
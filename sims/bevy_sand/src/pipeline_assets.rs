@@ -1,55 +1,204 @@
-use crate::constants::NUM_OF_CELLS;
+use crate::constants::AMBIENT_TEMPERATURE;
+use crate::settings::SimSize;
 use bevy::{
     prelude::*,
-    render::{extract_resource::ExtractResource, render_resource::Buffer, renderer::RenderDevice},
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{Buffer, BufferInitDescriptor, BufferUsages},
+        renderer::RenderDevice,
+    },
 };
+use serde::Deserialize;
 
 #[derive(Resource, Clone, Deref, ExtractResource)]
 pub struct SandPiplineImage(pub Handle<Image>);
 
 // ================================== Matter ================================== //
 
+/// How a matter moves. The values match the `state_*` constants in `matter.wgsl`.
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MatterState {
+    #[default]
+    Empty = 0,
+    Powder = 1,
+    Liquid = 2,
+    Gas = 3,
+    SolidGravity = 4,
+    Solid = 5,
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Clone, bytemuck::Pod, bytemuck::Zeroable, Copy)]
 pub struct Matter {
+    /// Unique id of the material, its index in the `MatterRegistry`.
     pub id: u32,
+    /// The [`MatterState`] deciding how the material moves.
+    pub state: u32,
     pub weight: f32,
     pub dispersion: u32,
+    /// Ticks left before the matter decays, `0` until its first tick. Only used by matters with a
+    /// decay in the registry.
+    pub lifetime: u32,
     _pad: u32,
+    /// Cells per tick, only the vertical component is accumulated by gravity for now.
+    pub velocity: [f32; 2],
     pub color: [f32; 4],
 }
 
 #[allow(dead_code)]
 impl Matter {
-    pub const EMPTY: Matter = Matter::new(0, [0.0, 0.0, 0.0, 1.0], 1.0, 0);
-    pub const SAND: Matter = Matter::new(1, [0.76078, 0.69804, 0.50196, 1.0], 1.5, 0);
-    pub const WATER: Matter = Matter::new(2, [0.01961, 0.33333, 1., 1.0], 1.0, 10);
-    pub const GAS: Matter = Matter::new(3, [0.49804, 1., 0., 1.0], 0.1, 5);
+    pub const EMPTY: Matter = Matter::new(0, MatterState::Empty, [0.0, 0.0, 0.0, 1.0], 1.0, 0);
 
-    pub const fn new(id: u32, color: [f32; 4], weight: f32, dispersion: u32) -> Self {
+    pub const fn new(
+        id: u32,
+        state: MatterState,
+        color: [f32; 4],
+        weight: f32,
+        dispersion: u32,
+    ) -> Self {
         Self {
             id,
             color,
             weight,
-            _pad: 0,
             dispersion,
+            state: state as u32,
+            lifetime: 0,
+            _pad: 0,
+            velocity: [0.0; 2],
+        }
+    }
+}
+
+/// A [`Matter`] as it is stored in the GPU matter table, together with the values only needed when
+/// painting it, changing its temperature or decaying it.
+#[repr(C)]
+#[derive(Debug, Default, Clone, bytemuck::Pod, bytemuck::Zeroable, Copy)]
+pub struct GpuMatterDefinition {
+    pub matter: Matter,
+    pub color_variation: f32,
+    pub temperature: f32,
+    pub conductivity: f32,
+    pub heated_temperature: f32,
+    pub heated_into: u32,
+    pub cooled_temperature: f32,
+    pub cooled_into: u32,
+    pub min_lifetime: u32,
+    pub max_lifetime: u32,
+    pub decays_into: u32,
+    _pad: [u32; 2],
+}
+
+impl GpuMatterDefinition {
+    /// Used in `heated_into`, `cooled_into` and `decays_into` when the matter has no such
+    /// transition.
+    pub const NO_TRANSITION: u32 = u32::MAX;
+
+    pub const fn new(matter: Matter, color_variation: f32) -> Self {
+        Self {
+            matter,
+            color_variation,
+            temperature: AMBIENT_TEMPERATURE,
+            conductivity: 0.0,
+            heated_temperature: 0.0,
+            heated_into: Self::NO_TRANSITION,
+            cooled_temperature: 0.0,
+            cooled_into: Self::NO_TRANSITION,
+            min_lifetime: 0,
+            max_lifetime: 0,
+            decays_into: Self::NO_TRANSITION,
+            _pad: [0; 2],
         }
     }
 }
 
+// ================================== Reactions ================================== //
+
+/// A reaction as it is stored in the GPU reaction table, see `ReactionTable`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, bytemuck::Pod, bytemuck::Zeroable, Copy)]
+pub struct GpuReaction {
+    pub reactant_a: u32,
+    pub reactant_b: u32,
+    pub product_a: u32,
+    pub product_b: u32,
+    pub probability: f32,
+}
+
+impl GpuReaction {
+    /// Used as a reactant to match any matter that is neither empty nor the other reactant.
+    pub const ANY_MATTER: u32 = u32::MAX;
+}
+
+// ================================== Chunks ================================== //
+
+/// Activity of a chunk of the canvas, see `activate_chunks.wgsl`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, bytemuck::Pod, bytemuck::Zeroable, Copy)]
+pub struct GpuChunk {
+    /// Last step a cell of the chunk changed.
+    pub dirty: u32,
+    /// Whether the movement passes run on the chunk this step.
+    pub awake: u32,
+}
+
+/// Indirect dispatch arguments of the movement passes, counted by `activate_chunks.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, bytemuck::Pod, bytemuck::Zeroable, Copy)]
+pub struct GpuDispatchArgs {
+    pub workgroups_x: u32,
+    pub workgroups_y: u32,
+    pub workgroups_z: u32,
+}
+
+impl GpuDispatchArgs {
+    /// No workgroups, the active chunks are counted again every step.
+    pub const NONE: GpuDispatchArgs = GpuDispatchArgs {
+        workgroups_x: 0,
+        workgroups_y: 1,
+        workgroups_z: 1,
+    };
+}
+
 // ================================== Assets ================================== //
 
+/// Buffers of the simulation, recreated empty whenever the [`SimSize`] changes.
 #[derive(Resource)]
 pub struct SandPipelineAssets {
+    /// Size the buffers were created for.
+    pub size: SimSize,
     pub matter_in: Buffer,
     pub matter_out: Buffer,
+    /// Temperature of each cell. It belongs to the grid rather than to the matter, so it stays in
+    /// place when matter moves.
+    pub temperature_in: Buffer,
+    pub temperature_out: Buffer,
+    /// Wind painted on each cell, biasing dispersion. Only changed by the brush, so it isn't
+    /// swapped between frames.
+    pub wind: Buffer,
+    /// A [`GpuChunk`] per chunk.
+    pub chunks: Buffer,
+    /// Index of each active chunk, the first [`GpuDispatchArgs::workgroups_x`] ones are used.
+    pub active_chunks: Buffer,
+    /// [`GpuDispatchArgs`] of the movement passes.
+    pub chunk_dispatch: Buffer,
+    /// Number of cells changed by the movement passes since the last check of `settle.wgsl`.
+    pub moved: Buffer,
+    /// Matter id the flood fill spreads over, its id and the cells reached this frame, followed by
+    /// the state of each cell in the fill.
+    pub flood_fill: Buffer,
 }
 
 impl FromWorld for SandPipelineAssets {
     fn from_world(w: &mut World) -> Self {
-        let render_device = w.resource::<RenderDevice>();
+        let size = w.get_resource::<SimSize>().copied().unwrap_or_default();
+        Self::new(w.resource::<RenderDevice>(), size)
+    }
+}
 
-        let initial_data = vec![Matter::EMPTY; NUM_OF_CELLS];
+impl SandPipelineAssets {
+    pub fn new(render_device: &RenderDevice, size: SimSize) -> Self {
+        let initial_data = vec![Matter::EMPTY; size.num_of_cells()];
         let matter_in = crate::utils::create_storage_buffer_with_data(
             render_device,
             &initial_data,
@@ -61,17 +210,76 @@ impl FromWorld for SandPipelineAssets {
             Some("Buffer Out"),
         );
 
+        let initial_temperature = vec![AMBIENT_TEMPERATURE; size.num_of_cells()];
+        let temperature_in = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &initial_temperature,
+            Some("Temperature In"),
+        );
+        let temperature_out = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &initial_temperature,
+            Some("Temperature Out"),
+        );
+
+        let wind = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &vec![[0.0f32; 2]; size.num_of_cells()],
+            Some("Wind"),
+        );
+
+        let chunks = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &vec![GpuChunk::default(); size.num_of_chunks()],
+            Some("Chunks"),
+        );
+
+        let active_chunks = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &vec![0u32; size.num_of_chunks()],
+            Some("Active Chunks"),
+        );
+        let chunk_dispatch = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("Chunk Dispatch"),
+            contents: bytemuck::bytes_of(&GpuDispatchArgs::NONE),
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+        });
+        let moved =
+            crate::utils::create_storage_buffer_with_data(render_device, &[0u32], Some("Moved"));
+        let flood_fill = crate::utils::create_storage_buffer_with_data(
+            render_device,
+            &vec![0u32; 3 + size.num_of_cells()],
+            Some("Flood Fill"),
+        );
+
         Self {
+            size,
             matter_in,
             matter_out,
+            temperature_in,
+            temperature_out,
+            wind,
+            chunks,
+            active_chunks,
+            chunk_dispatch,
+            moved,
+            flood_fill,
         }
     }
 }
 
+/// The matter registry uploaded to the GPU, rebuilt whenever the registry changes.
+#[derive(Resource)]
+pub struct SandMatterTable(pub Buffer);
+
+/// The reactions of the matter registry uploaded to the GPU, rebuilt with [`SandMatterTable`].
+#[derive(Resource)]
+pub struct SandReactionTable(pub Buffer);
+
 // ================================== Constants ================================== //
 
 #[repr(C)]
-#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SandPushConstants {
     pub draw_start: [f32; 2],
     pub draw_end: [f32; 2],
@@ -81,13 +289,73 @@ pub struct SandPushConstants {
     pub move_step: u32,
     pub dispersion_dir: u32,
     pub dispersion_step: u32,
-    pub seed: f32,
+    pub seed: u32,
     pub draw_matter: u32,
-    pub matter: Matter,
+    /// Temperature added to each painted cell per frame by the heat brush.
+    pub draw_heat: f32,
+    /// A [`Gravity`](crate::settings::Gravity) direction.
+    pub gravity_dir: u32,
+    /// Wind painted by the wind brush.
+    pub draw_wind: [f32; 2],
+    /// One of the `BRUSH_*` constants.
+    pub brush: u32,
+    /// A [`Boundary`](crate::settings::Boundary).
+    pub boundary: u32,
+    /// A [`BrushTool`](sim_core::brush::BrushTool).
+    pub tool: u32,
+    /// Whether rectangles and ellipses are filled rather than outlined.
+    pub filled: u32,
+    /// Chance of each cell under the spray to be painted.
+    pub spray_density: f32,
+    /// Changes every frame, so the spray paints other cells.
+    pub brush_seed: u32,
+    /// One of the `PAINT_*` constants.
+    pub paint_mode: u32,
+    /// Matter id the masked paint modes paint over.
+    pub mask_matter: u32,
+    /// Size of the stamp placed by the stamp tool, before turning it.
+    pub stamp_size: [u32; 2],
+    /// A [`StampTransform`](sim_core::stamp::StampTransform) as bits.
+    pub stamp_transform: u32,
+    /// Keeps the size a multiple of the alignment of the vectors in WGSL.
+    pub padding: u32,
 }
 
 impl SandPushConstants {
+    pub const BRUSH_MATTER: u32 = 0;
+    pub const BRUSH_HEAT: u32 = 1;
+    pub const BRUSH_WIND: u32 = 2;
+    pub const BRUSH_CALM: u32 = 3;
+    /// `paint_mode` of each [`PaintMode`](crate::input::PaintMode).
+    pub const PAINT_FILL_EMPTY: u32 = 0;
+    pub const PAINT_REPLACE_ALL: u32 = 1;
+    pub const PAINT_REPLACE_MATTER: u32 = 2;
+    pub const PAINT_ERASE_MATTER: u32 = 3;
+    /// `boundary` of each [`Boundary`](crate::settings::Boundary).
+    pub const BOUNDARY_WALL: u32 = 0;
+    pub const BOUNDARY_WRAP: u32 = 1;
+    pub const BOUNDARY_VOID: u32 = 2;
+    /// `gravity_dir` of [`Gravity::Down`](crate::settings::Gravity::Down).
+    pub const DOWN_GRAVITY: u32 = 5;
+    /// `gravity_dir` of [`Gravity::Zero`](crate::settings::Gravity::Zero).
+    pub const NO_GRAVITY: u32 = 8;
+
     pub fn as_bytes(&self) -> &[u8] {
         bytemuck::cast_slice(bytemuck::bytes_of(self))
     }
+
+    /// `dispersion_dir` of the dispersion following movement step `move_step`, alternating between
+    /// movement steps and simulation steps so liquids don't drift to one side.
+    pub fn dispersion_dir_after(&self, move_step: u32) -> u32 {
+        self.sim_step.wrapping_add(move_step).is_multiple_of(2) as u32
+    }
+}
+
+impl Default for SandPushConstants {
+    fn default() -> Self {
+        Self {
+            gravity_dir: Self::DOWN_GRAVITY,
+            ..bytemuck::Zeroable::zeroed()
+        }
+    }
 }
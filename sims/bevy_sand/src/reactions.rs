@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{pipeline_assets::GpuReaction, registry::MatterRegistry};
+
+/// Reactant name matching any matter that is neither empty nor the other reactant.
+pub const ANY_MATTER: &str = "*";
+
+// ================================== Definition ================================== //
+
+/// Two adjacent matters turning into two others. The first product replaces the first reactant
+/// and the second product the second reactant.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReactionDefinition {
+    /// Matter names, either can be [`ANY_MATTER`].
+    pub reactants: (String, String),
+    pub products: (String, String),
+    /// Chance for the reaction to happen each tick the reactants are paired, from `0.0` to `1.0`.
+    pub probability: f32,
+}
+
+// ================================== Table ================================== //
+
+/// The reactions of a [`MatterRegistry`] with their matter names resolved to ids.
+#[derive(Debug, Clone, Default)]
+pub struct ReactionTable {
+    pub reactions: Vec<GpuReaction>,
+}
+
+impl ReactionTable {
+    pub fn new(registry: &MatterRegistry) -> Self {
+        let reactions = registry
+            .reactions
+            .iter()
+            .filter_map(|reaction| Self::resolve(registry, reaction))
+            .collect();
+        Self { reactions }
+    }
+
+    /// The table as uploaded to the GPU. Storage buffers can't be empty, so an empty table holds a
+    /// single reaction that never happens.
+    pub fn gpu_data(&self) -> Vec<GpuReaction> {
+        if self.reactions.is_empty() {
+            vec![GpuReaction::default()]
+        } else {
+            self.reactions.clone()
+        }
+    }
+
+    fn resolve(registry: &MatterRegistry, reaction: &ReactionDefinition) -> Option<GpuReaction> {
+        let reactant = |name: &str| {
+            if name == ANY_MATTER {
+                Some(GpuReaction::ANY_MATTER)
+            } else {
+                registry.id_of(name)
+            }
+        };
+
+        let (reactant_a, reactant_b) = &reaction.reactants;
+        let (product_a, product_b) = &reaction.products;
+        match (
+            reactant(reactant_a),
+            reactant(reactant_b),
+            registry.id_of(product_a),
+            registry.id_of(product_b),
+        ) {
+            (Some(reactant_a), Some(reactant_b), Some(product_a), Some(product_b)) => {
+                Some(GpuReaction {
+                    reactant_a,
+                    reactant_b,
+                    product_a,
+                    product_b,
+                    probability: reaction.probability.clamp(0.0, 1.0),
+                })
+            }
+            _ => {
+                warn!(
+                    "Reaction {} + {} uses an unknown matter",
+                    reactant_a, reactant_b
+                );
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::extract_resource::{ExtractResource, ExtractResourcePlugin},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    constants::AMBIENT_TEMPERATURE,
+    pipeline_assets::{GpuMatterDefinition, Matter, MatterState},
+    reactions::{ReactionDefinition, ReactionTable},
+};
+
+pub const MATTER_REGISTRY_PATH: &str = "default.matters.ron";
+
+pub struct MatterRegistryPlugin;
+impl Plugin for MatterRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<MatterRegistry>()
+            .init_asset_loader::<MatterRegistryLoader>()
+            .add_plugin(ExtractResourcePlugin::<MatterRegistry>::default())
+            .add_startup_system(load_matter_registry)
+            .add_system(update_matter_registry);
+    }
+}
+
+// ================================== Matter ================================== //
+
+/// Turns a matter into another one once its cell reaches `temperature`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PhaseTransition {
+    pub temperature: f32,
+    /// Name of the matter it turns into.
+    pub into: String,
+}
+
+/// Turns a matter into another one after a random number of ticks, like fire burning out.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Decay {
+    /// Minimum and maximum number of ticks, both included.
+    pub ticks: (u32, u32),
+    /// Name of the matter it turns into.
+    pub into: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatterDefinition {
+    pub name: String,
+    pub state: MatterState,
+    pub weight: f32,
+    #[serde(default)]
+    pub dispersion: u32,
+    pub color: [f32; 4],
+    /// Maximum amount added to or removed from each color channel when painting.
+    #[serde(default)]
+    pub color_variation: f32,
+    /// Temperature given to the cell when painting the matter.
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    /// How fast heat flows through the matter, from `0.0` (insulator) to `1.0`.
+    #[serde(default = "default_conductivity")]
+    pub conductivity: f32,
+    /// Melting or boiling, happens at or above the temperature.
+    #[serde(default)]
+    pub heated: Option<PhaseTransition>,
+    /// Freezing or condensing, happens at or below the temperature.
+    #[serde(default)]
+    pub cooled: Option<PhaseTransition>,
+    #[serde(default)]
+    pub decay: Option<Decay>,
+}
+
+fn default_temperature() -> f32 {
+    AMBIENT_TEMPERATURE
+}
+
+fn default_conductivity() -> f32 {
+    0.5
+}
+
+impl MatterDefinition {
+    pub fn matter(&self, id: u32) -> Matter {
+        Matter::new(id, self.state, self.color, self.weight, self.dispersion)
+    }
+}
+
+// ================================== Registry ================================== //
+
+/// Every matter that can be painted, in palette order, and how they react with each other. Index
+/// `0` is always the empty matter.
+#[derive(Debug, Clone, Deserialize, TypeUuid, Resource, ExtractResource)]
+#[uuid = "8f3b6c2e-5d1a-4f7e-9c0b-2a6e4d8f1b37"]
+pub struct MatterRegistry {
+    pub matters: Vec<MatterDefinition>,
+    #[serde(default)]
+    pub reactions: Vec<ReactionDefinition>,
+}
+
+impl MatterRegistry {
+    pub fn get(&self, index: u32) -> Option<&MatterDefinition> {
+        self.matters.get(index as usize)
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<u32> {
+        self.matters
+            .iter()
+            .position(|definition| definition.name == name)
+            .map(|id| id as u32)
+    }
+
+    /// The matter with the given id, which is its index in the registry.
+    pub fn matter(&self, id: u32) -> Matter {
+        self.get(id)
+            .map(|definition| definition.matter(id))
+            .unwrap_or(Matter::EMPTY)
+    }
+
+    /// The table uploaded to the GPU, indexed the same way as [`MatterRegistry::matters`].
+    pub fn gpu_table(&self) -> Vec<GpuMatterDefinition> {
+        self.matters
+            .iter()
+            .enumerate()
+            .map(|(id, definition)| {
+                let mut entry = GpuMatterDefinition::new(
+                    definition.matter(id as u32),
+                    definition.color_variation,
+                );
+                entry.temperature = definition.temperature;
+                entry.conductivity = definition.conductivity.clamp(0.0, 1.0);
+
+                if let Some((temperature, into)) =
+                    self.resolve_transition(definition, &definition.heated)
+                {
+                    entry.heated_temperature = temperature;
+                    entry.heated_into = into;
+                }
+                if let Some((temperature, into)) =
+                    self.resolve_transition(definition, &definition.cooled)
+                {
+                    entry.cooled_temperature = temperature;
+                    entry.cooled_into = into;
+                }
+                if let Some((ticks, into)) = self.resolve_decay(definition) {
+                    entry.min_lifetime = ticks.0.min(ticks.1).max(1);
+                    entry.max_lifetime = ticks.0.max(ticks.1).max(1);
+                    entry.decays_into = into;
+                }
+
+                entry
+            })
+            .collect()
+    }
+
+    pub fn reaction_table(&self) -> ReactionTable {
+        ReactionTable::new(self)
+    }
+
+    fn resolve_transition(
+        &self,
+        definition: &MatterDefinition,
+        transition: &Option<PhaseTransition>,
+    ) -> Option<(f32, u32)> {
+        let transition = transition.as_ref()?;
+        let into = self.resolve_into(definition, &transition.into)?;
+        Some((transition.temperature, into))
+    }
+
+    fn resolve_decay(&self, definition: &MatterDefinition) -> Option<((u32, u32), u32)> {
+        let decay = definition.decay.as_ref()?;
+        let into = self.resolve_into(definition, &decay.into)?;
+        Some((decay.ticks, into))
+    }
+
+    fn resolve_into(&self, definition: &MatterDefinition, into: &str) -> Option<u32> {
+        let id = self.id_of(into);
+        if id.is_none() {
+            warn!("{} turns into unknown matter {}", definition.name, into);
+        }
+        id
+    }
+}
+
+#[derive(Default)]
+pub struct MatterRegistryLoader;
+
+impl AssetLoader for MatterRegistryLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let registry = ron::de::from_bytes::<MatterRegistry>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(registry));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["matters.ron"]
+    }
+}
+
+// ================================== Systems ================================== //
+
+#[derive(Resource)]
+pub struct MatterRegistryHandle(pub Handle<MatterRegistry>);
+
+fn load_matter_registry(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MatterRegistryHandle(
+        asset_server.load(MATTER_REGISTRY_PATH),
+    ));
+}
+
+/// Copies the loaded registry into a resource so it can be read by the UI and extracted to the
+/// render world.
+fn update_matter_registry(
+    mut commands: Commands,
+    handle: Res<MatterRegistryHandle>,
+    registries: Res<Assets<MatterRegistry>>,
+    mut events: EventReader<AssetEvent<MatterRegistry>>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle: updated } | AssetEvent::Modified { handle: updated }
+                if *updated == handle.0 =>
+            {
+                if let Some(registry) = registries.get(updated) {
+                    commands.insert_resource(registry.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
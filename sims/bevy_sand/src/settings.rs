@@ -1,28 +1,162 @@
 use bevy::{
     prelude::*,
     render::extract_resource::{ExtractResource, ExtractResourcePlugin},
-    utils::Instant,
 };
 
+use crate::constants::{CHUNK_SIZE, DEFAULT_SIM_SIZE, WORKGROUP_SIZE};
+
 pub const INIT_MOVEMENT_STEPS: u32 = 3;
 pub const INIT_DISPERSION_STEPS: u32 = 10;
+/// Most movement steps in a simulation step, `move_step` must stay below 256 for `pass_id` in
+/// `core.wgsl`.
+pub const MAX_MOVEMENT_STEPS: u32 = 64;
 
 pub struct SettingsPlugin;
 impl Plugin for SettingsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SandAppSettings>()
-            .add_plugin(ExtractResourcePlugin::<SandAppSettings>::default());
+            .init_resource::<SimSize>()
+            .add_plugin(ExtractResourcePlugin::<SandAppSettings>::default())
+            .add_plugin(ExtractResourcePlugin::<SimSize>::default());
+    }
+}
+
+/// Direction matter falls towards, gases rise the opposite way. The values match the directions in
+/// `dir.wgsl`.
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Gravity {
+    UpLeft = 0,
+    Up = 1,
+    UpRight = 2,
+    Right = 3,
+    DownRight = 4,
+    #[default]
+    Down = 5,
+    DownLeft = 6,
+    Left = 7,
+    /// Nothing falls or rises, matter still disperses sideways.
+    Zero = 8,
+}
+
+impl Gravity {
+    pub const ALL: [Gravity; 9] = [
+        Gravity::Down,
+        Gravity::DownLeft,
+        Gravity::Left,
+        Gravity::UpLeft,
+        Gravity::Up,
+        Gravity::UpRight,
+        Gravity::Right,
+        Gravity::DownRight,
+        Gravity::Zero,
+    ];
+}
+
+/// What the edges of the canvas do to matter reaching them. The values match the `BOUNDARY_*`
+/// constants in `query.wgsl`.
+#[repr(u32)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    /// Matter stops at the edges.
+    #[default]
+    Wall = 0,
+    /// Matter leaving through an edge comes back through the opposite one.
+    Wrap = 1,
+    /// Matter leaving through an edge is deleted.
+    Void = 2,
+}
+
+impl Boundary {
+    pub const ALL: [Boundary; 3] = [Boundary::Wall, Boundary::Wrap, Boundary::Void];
+}
+
+/// Size of the simulation in cells. Insert it before adding the plugin to choose the starting size,
+/// changing it afterwards recreates the canvas and clears the simulation.
+#[derive(Resource, ExtractResource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for SimSize {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIM_SIZE.0, DEFAULT_SIM_SIZE.1)
+    }
+}
+
+impl std::fmt::Display for SimSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl SimSize {
+    /// Sizes offered in the UI.
+    pub const PRESETS: [SimSize; 5] = [
+        SimSize::new(256, 256),
+        SimSize::new(512, 512),
+        SimSize::new(1024, 512),
+        SimSize::new(1024, 1024),
+        SimSize::new(2048, 1024),
+    ];
+
+    /// Rounds the size up to whole chunks, at least one.
+    pub const fn new(width: u32, height: u32) -> Self {
+        const fn to_chunks(cells: u32) -> u32 {
+            if cells == 0 {
+                CHUNK_SIZE
+            } else {
+                cells.div_ceil(CHUNK_SIZE) * CHUNK_SIZE
+            }
+        }
+
+        Self {
+            width: to_chunks(width),
+            height: to_chunks(height),
+        }
+    }
+
+    pub fn as_tuple(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn as_vec2(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn num_of_cells(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    /// Workgroups dispatched by the passes running once per cell.
+    pub fn grid(&self) -> (u32, u32) {
+        (self.width / WORKGROUP_SIZE, self.height / WORKGROUP_SIZE)
+    }
+
+    pub fn chunks(&self) -> (u32, u32) {
+        (self.width / CHUNK_SIZE, self.height / CHUNK_SIZE)
+    }
+
+    pub fn num_of_chunks(&self) -> usize {
+        let (chunks_w, chunks_h) = self.chunks();
+        (chunks_w * chunks_h) as usize
     }
 }
 
 #[derive(Resource, ExtractResource, Clone, Copy)]
 pub struct SandAppSettings {
-    pub seed: f32,
-    pub start: Instant,
-    pub is_paused: bool,
+    /// Seed of every random choice, the same seed and inputs always give the same world.
+    pub seed: u32,
+    /// Movement steps in a simulation step, each followed by the dispersion steps. When adaptive,
+    /// the most movement steps to run.
     pub movement_steps: u32,
+    /// Stops running movement steps as soon as one of them moves nothing.
+    pub adaptive_movement: bool,
     pub dispersion_steps: u32,
     pub print_performance: bool,
+    pub gravity: Gravity,
+    pub boundary: Boundary,
 }
 
 impl Default for SandAppSettings {
@@ -36,16 +170,13 @@ impl SandAppSettings {
         let dispersion_steps = INIT_DISPERSION_STEPS;
         let movement_steps = INIT_MOVEMENT_STEPS;
         SandAppSettings {
-            seed: 0.0,
+            seed: 0,
             movement_steps,
-            is_paused: false,
+            adaptive_movement: false,
             dispersion_steps,
-            start: Instant::now(),
             print_performance: false,
+            gravity: Gravity::default(),
+            boundary: Boundary::default(),
         }
     }
-
-    pub fn get_current_seed(&self) -> f32 {
-        (Instant::now() - self.start).as_secs_f32()
-    }
 }
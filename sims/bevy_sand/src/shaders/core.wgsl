@@ -13,9 +13,55 @@ struct PushConstants {
     move_step: u32,
     dispersion_dir: u32,
     dispersion_step: u32,
-    seed: f32,
-    matter: Matter,
+    seed: u32,
+    draw_matter: u32,
+    draw_heat: f32,
+    gravity_dir: u32,
+    draw_wind: vec2<f32>,
+    brush: u32,
+    boundary: u32,
+    tool: u32,
+    filled: u32,
+    spray_density: f32,
+    brush_seed: u32,
+    paint_mode: u32,
+    mask_matter: u32,
+    stamp_size: vec2<u32>,
+    stamp_transform: u32,
+    padding: u32,
 }
+
+struct FloodFill {
+    kind: u32,
+    id: u32,
+    reached: atomic<u32>,
+    cells: array<atomic<u32>>,
+}
+
+struct Chunk {
+    // Last step a cell of the chunk changed
+    dirty: atomic<u32>,
+    // Whether the movement passes run on the chunk this step
+    awake: u32,
+}
+
+const BRUSH_MATTER: u32 = 0u;
+const BRUSH_HEAT: u32 = 1u;
+const BRUSH_WIND: u32 = 2u;
+const BRUSH_CALM: u32 = 3u;
+const PAINT_FILL_EMPTY: u32 = 0u;
+const PAINT_REPLACE_ALL: u32 = 1u;
+const PAINT_REPLACE_MATTER: u32 = 2u;
+const PAINT_ERASE_MATTER: u32 = 3u;
+const TOOL_FREEHAND: u32 = 0u;
+const TOOL_SPRAY: u32 = 1u;
+const TOOL_LINE: u32 = 2u;
+const TOOL_RECTANGLE: u32 = 3u;
+const TOOL_ELLIPSE: u32 = 4u;
+const TOOL_FLOOD_FILL: u32 = 5u;
+const TOOL_SELECT: u32 = 6u;
+const TOOL_STAMP: u32 = 7u;
+const NO_GRAVITY: u32 = 8u;
 var<push_constant> pc: PushConstants;
 
 @group(0) @binding(0) 
@@ -24,6 +70,31 @@ var<storage, read_write> matter_in : array<Matter>;
 var<storage, read_write> matter_out : array<Matter>;
 @group(0) @binding(2)
 var texture: texture_storage_2d<rgba8unorm, read_write>;
+@group(0) @binding(3)
+var<storage, read> matter_table : array<MatterDefinition>;
+@group(0) @binding(4)
+var<storage, read_write> temperature_in : array<f32>;
+@group(0) @binding(5)
+var<storage, read_write> temperature_out : array<f32>;
+@group(0) @binding(6)
+var<storage, read> reactions : array<Reaction>;
+@group(0) @binding(7)
+var<storage, read_write> wind : array<vec2<f32>>;
+@group(0) @binding(8)
+var<storage, read_write> chunks : array<Chunk>;
+// Chunks the movement passes run on this step
+@group(0) @binding(9)
+var<storage, read_write> active_chunks : array<u32>;
+// Cells changed by the movement passes since the last check, see `settle.wgsl`
+@group(0) @binding(10)
+var<storage, read_write> moved : atomic<u32>;
+// Matter id the flood fill spreads over, the fill and the cells its passes reached this frame, and a
+// `FLOOD_*` state per cell, see `draw.wgsl`
+@group(0) @binding(11)
+var<storage, read_write> flood : FloodFill;
+// A matter id per cell of the stamp placed by the stamp tool, see `draw.wgsl`
+@group(0) @binding(12)
+var<storage, read> stamp : array<u32>;
 
 fn sim_canvas_size() -> vec2<i32> {
     return vec2<i32>(textureDimensions(texture));
@@ -38,12 +109,120 @@ fn get_index(location: vec2<i32>) -> i32 {
     return location.y * dims.x + location.x;
 }
 
+/*
+CHUNKS
+The movement passes only run on active chunks, see `activate_chunks.wgsl`
+*/
+const CHUNK_SIZE: i32 = 32;
+const BLOCKS_PER_CHUNK_ROW: i32 = 4;
+const WORKGROUPS_PER_CHUNK: u32 = 16u;
+
+fn chunk_grid_size() -> vec2<i32> { return sim_canvas_size() / CHUNK_SIZE; }
+fn get_chunk_index(chunk: vec2<i32>) -> i32 { return chunk.y * chunk_grid_size().x + chunk.x; }
+fn get_chunk_of(pos: vec2<i32>) -> i32 { return get_chunk_index(pos / CHUNK_SIZE); }
+fn is_chunk_active(pos: vec2<i32>) -> bool { return chunks[get_chunk_of(pos)].awake != 0u; }
+fn wake_chunk(pos: vec2<i32>) { atomicMax(&chunks[get_chunk_of(pos)].dirty, pc.sim_steps); }
+fn count_moved() { atomicAdd(&moved, 1u); }
+
+// Position handled by an invocation of a pass dispatched over the active chunks, each chunk gets a
+// workgroup per block of 8x8 cells
+fn get_active_sim_pos(invocation_id: vec3<u32>) -> vec2<i32> {
+	let workgroup: u32 = invocation_id.x / 8u;
+	let chunk: i32 = i32(active_chunks[workgroup / WORKGROUPS_PER_CHUNK]);
+	let block: i32 = i32(workgroup % WORKGROUPS_PER_CHUNK);
+	let grid: vec2<i32> = chunk_grid_size();
+	return vec2<i32>(chunk % grid.x, chunk / grid.x) * CHUNK_SIZE
+		+ vec2<i32>(block % BLOCKS_PER_CHUNK_ROW, block / BLOCKS_PER_CHUNK_ROW) * 8
+		+ vec2<i32>(i32(invocation_id.x % 8u), i32(invocation_id.y));
+}
+
+fn has_changed(before: Matter, after: Matter) -> bool {
+	return before.id != after.id || any(before.velocity != after.velocity) || any(before.color != after.color);
+}
+
+// Writing a different matter wakes the chunk up
 fn read_matter(pos: vec2<i32>) -> Matter { return matter_in[get_index(pos)]; }
-fn write_matter(pos: vec2<i32>, matter: Matter)  { matter_out[get_index(pos)] = matter; } 
-fn write_matter_input(pos: vec2<i32>, matter: Matter)  { matter_in[get_index(pos)] = matter; }
+fn write_matter(pos: vec2<i32>, matter: Matter)  { 
+	if (has_changed(read_matter(pos), matter)) {
+		wake_chunk(pos);
+		count_moved();
+	}
+	matter_out[get_index(pos)] = matter; 
+} 
+fn write_matter_input(pos: vec2<i32>, matter: Matter)  { 
+	if (has_changed(read_matter(pos), matter)) {
+		wake_chunk(pos);
+	}
+	matter_in[get_index(pos)] = matter; 
+}
+
+fn read_temperature(pos: vec2<i32>) -> f32 { return temperature_in[get_index(pos)]; }
+fn write_temperature(pos: vec2<i32>, temperature: f32)  { temperature_out[get_index(pos)] = temperature; }
+fn write_temperature_input(pos: vec2<i32>, temperature: f32)  { temperature_in[get_index(pos)] = temperature; }
+
+fn read_wind(pos: vec2<i32>) -> vec2<f32> { return wind[get_index(pos)]; }
+fn write_wind(pos: vec2<i32>, w: vec2<f32>)  { 
+	wake_chunk(pos);
+	wind[get_index(pos)] = w; 
+}
+
+// The movement passes use directions relative to gravity, DOWN being the direction of gravity.
+// Without gravity they are left as is.
+fn rotate_dir(dir: i32) -> i32 {
+	if (pc.gravity_dir >= NO_GRAVITY) {
+		return dir;
+	}
+	return (dir + i32(pc.gravity_dir) - DOWN + 8) % 8;
+}
+
+fn get_pos_towards(pos: vec2<i32>, dir: i32) -> vec2<i32> {
+	return get_pos_at_dir(pos, rotate_dir(dir));
+}
+
+fn gravity_offset() -> vec2<i32> {
+	return get_pos_towards(vec2<i32>(0, 0), DOWN);
+}
+
+// PCG hash from "Hash Functions for GPU Rendering" (Jarzynski & Olano, 2020)
+fn pcg_hash(value: u32) -> u32 {
+	let state: u32 = value * 747796405u + 2891336453u;
+	let word: u32 = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+	return (word >> 22u) ^ word;
+}
+
+// Random number in [0, 1) only depending on the cell and `seed`
+fn rand_at(xy: vec2<i32>, seed: u32) -> f32 {
+	let hash: u32 = pcg_hash(pcg_hash(pcg_hash(seed) + bitcast<u32>(xy.x)) + bitcast<u32>(xy.y));
+	return f32(hash >> 8u) / 16777216.0;
+}
+
+// Identifies the pass being run, both halves of a main/swap pair share it
+fn pass_id() -> u32 {
+	return pc.move_step + 256u * (pc.dispersion_step + 256u * pc.dispersion_dir);
+}
+
+// Random number in [0, 1) for the cell, changing with the seed, the step and the pass
+fn rand(xy: vec2<i32>) -> f32 {
+	return rand_at(xy, pcg_hash(pcg_hash(pc.seed + pcg_hash(pc.sim_steps)) + pass_id()));
+}
+
+// Random number used to disperse from `pos` towards `dir`, lowered by wind blowing that way. Cells
+// in the void never disperse
+fn dispersion_chance(pos: vec2<i32>, dir: i32) -> f32 {
+	let cell_pos: vec2<i32> = boundary_pos(pos);
+	if (!is_inside_sim_canvas(cell_pos)) {
+		return 1.0;
+	}
+	let dir_offset: vec2<f32> = normalize(vec2<f32>(get_pos_towards(vec2<i32>(0, 0), dir)));
+	return rand(cell_pos) - 0.5 * dot(read_wind(cell_pos), dir_offset);
+}
 
-const PHI: f32 = 1.61803398874989484820459;
-fn rand(xy: vec2<i32>, seed: f32) -> f32 {
-	let pos: vec2<f32> = vec2<f32>(vec2<f32>(xy).x + 0.5, vec2<f32>(xy).y + 0.5);
-	return fract(tan(distance(pos * PHI, pos) * seed) * pos.x);
+fn vary_color_rgb(color: vec4<f32>, seed_pos: vec2<i32>, variation: f32) -> vec4<f32> {
+	let p: f32 = rand_at(seed_pos, pc.seed);
+	let offset: f32 = -variation + 2.0 * variation * p;
+    var c = color;
+	c.r = c.r + offset;
+	c.g = c.g + offset;
+	c.b = c.b + offset;
+	return c;
 }
\ No newline at end of file
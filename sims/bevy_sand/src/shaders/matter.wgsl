@@ -5,36 +5,84 @@ const state_powder: u32 = 1u;
 const state_liquid: u32 = 2u;
 const state_gas: u32 = 3u;
 const state_solid_gravity: u32 = 4u;
+const state_solid: u32 = 5u;
 
 struct Matter{
+    // Unique material id, the index in the matter table
     id: u32,
+    // One of the state_* constants
+    state: u32,
     weight: f32,
     dispersion: u32,
+    // Ticks left before decaying, 0 until the first tick
+    lifetime: u32,
+    // Cells per tick
+    velocity: vec2<f32>,
     color: vec4<f32>,
 }
 
+const NO_TRANSITION: u32 = 0xffffffffu;
+
+// Entry of the matter table uploaded from the Rust `MatterRegistry`
+struct MatterDefinition {
+    matter: Matter,
+    color_variation: f32,
+    // Temperature of the cell when the matter is painted
+    temperature: f32,
+    conductivity: f32,
+    // Becomes `heated_into` at or above `heated_temperature`
+    heated_temperature: f32,
+    heated_into: u32,
+    // Becomes `cooled_into` at or below `cooled_temperature`
+    cooled_temperature: f32,
+    cooled_into: u32,
+    // Becomes `decays_into` after a random number of ticks in [min_lifetime, max_lifetime]
+    min_lifetime: u32,
+    max_lifetime: u32,
+    decays_into: u32,
+}
+
+const ANY_MATTER: u32 = 0xffffffffu;
+
+// Entry of the reaction table uploaded from the Rust `ReactionTable`
+struct Reaction {
+    reactant_a: u32,
+    reactant_b: u32,
+    product_a: u32,
+    product_b: u32,
+    probability: f32,
+}
+
 const EMPTY_COLOR: vec4<f32> = vec4<f32>(0.0, 0.0, 0.0, 1.0);
-const SAND_COLOR: vec4<f32> = vec4<f32>(0.76078, 0.69804, 0.50196, 1.0);
-const WATER_COLOR: vec4<f32> = vec4<f32>(0.01961, 0.33333, 1., 1.0);
-
-const EMPTY_MATTER: Matter = Matter(empty_matter, 0.0, 0u, EMPTY_COLOR);
-const SAND: Matter = Matter(state_powder, 1.5, 0u, SAND_COLOR);
-const WATER: Matter = Matter(state_liquid, 1.0, 10u, WATER_COLOR);
-
-fn new_matter(id: u32) -> Matter {
-    var matter = EMPTY_MATTER;
-
-    switch id {
-        case 1u: {
-            matter = SAND;
-        }
-        case 2u: {
-            matter = WATER;
-        }
-        default {
-            matter = EMPTY_MATTER;
-        }
+const EMPTY_MATTER: Matter = Matter(empty_matter, empty_matter, 0.0, 0u, 0u, vec2<f32>(0.0, 0.0), EMPTY_COLOR);
+
+fn new_matter(index: u32) -> Matter {
+    if (index >= arrayLength(&matter_table)) {
+        return EMPTY_MATTER;
     }
 
-    return matter;
+    return matter_table[index].matter;
+}
+
+fn matter_color_variation(index: u32) -> f32 {
+    if (index >= arrayLength(&matter_table)) {
+        return 0.0;
+    }
+
+    return matter_table[index].color_variation;
+}
+
+fn matter_definition(index: u32) -> MatterDefinition {
+    if (index >= arrayLength(&matter_table)) {
+        return matter_table[0];
+    }
+
+    return matter_table[index];
+}
+
+// A new matter with its color varied by its position, like when it is painted
+fn new_matter_at(pos: vec2<i32>, index: u32) -> Matter {
+    var m = new_matter(index);
+    m.color = vary_color_rgb(m.color, pos, matter_color_variation(index));
+    return m;
 }
\ No newline at end of file
@@ -6,40 +6,131 @@ fn is_inside_sim_canvas(pos: vec2<i32>) -> bool {
 	return pos.x >= 0 && pos.x < sim_canvas_size.x && pos.y >= 0 && pos.y < sim_canvas_size.y;
 } 
 
+/*
+BOUNDARIES
+*/
+const BOUNDARY_WALL: u32 = 0u;
+const BOUNDARY_WRAP: u32 = 1u;
+const BOUNDARY_VOID: u32 = 2u;
+
+// The cell at `pos`, wrapped around the canvas with the wrap boundary
+fn boundary_pos(pos: vec2<i32>) -> vec2<i32> {
+	if (pc.boundary == BOUNDARY_WRAP) {
+		let size: vec2<i32> = sim_canvas_size();
+		return (pos % size + size) % size;
+	}
+	return pos;
+}
+
+fn is_inside_boundary(pos: vec2<i32>) -> bool { return is_inside_sim_canvas(boundary_pos(pos)); }
+
+// Cells of the chunks skipped by the movement passes act as solids, so nothing moves in or out of
+// them until they wake up
+const SLEEPING_MATTER: Matter = Matter(empty_matter, state_solid, 0.0, 0u, 0u, vec2<f32>(0.0, 0.0), EMPTY_COLOR);
+
+// Matter outside the canvas is empty. Walls keep matter from moving there, the void lets it move
+// there and it is gone
+fn read_matter_at(pos: vec2<i32>) -> Matter {
+	if (!is_inside_boundary(pos)) {
+		return EMPTY_MATTER;
+	}
+	let cell_pos: vec2<i32> = boundary_pos(pos);
+	if (!is_chunk_active(cell_pos)) {
+		return SLEEPING_MATTER;
+	}
+	return read_matter(cell_pos);
+}
+
 /*
 MATTER POSITION QUERIES
+Relative to gravity, the bottom border is the one gravity pulls towards. Only walls have borders
 */
-fn is_at_border_top(pos: vec2<i32>) -> bool { return pos.y == 0; } 
-fn is_at_border_left(pos: vec2<i32>) -> bool { return pos.x == 0; } 
-fn is_at_border_right(pos: vec2<i32>) -> bool { return pos.x == sim_canvas_size().x - 1; } 
-fn is_at_border_bottom(pos: vec2<i32>) -> bool { return pos.y == sim_canvas_size().y - 1; } 
+fn is_at_border(pos: vec2<i32>, dir: i32) -> bool { 
+	return pc.boundary == BOUNDARY_WALL && !is_inside_sim_canvas(get_pos_towards(pos, dir)); 
+}
+fn is_at_border_top(pos: vec2<i32>) -> bool { return is_at_border(pos, UP); } 
+fn is_at_border_left(pos: vec2<i32>) -> bool { return is_at_border(pos, LEFT); } 
+fn is_at_border_right(pos: vec2<i32>) -> bool { return is_at_border(pos, RIGHT); } 
+fn is_at_border_bottom(pos: vec2<i32>) -> bool { return is_at_border(pos, DOWN); } 
 
 // | 0 1 2 |
 // | 7 x 3 |
 // | 6 5 4 |
+// Relative to gravity
 fn get_neighbor(pos: vec2<i32>, dir: i32) -> Matter {
-	let neighbor_pos: vec2<i32> = get_pos_at_dir(pos, dir);
-	if (is_inside_sim_canvas(neighbor_pos)) {
-		return read_matter(neighbor_pos);
-	} else { 
-		return EMPTY_MATTER;
-	}
+	return read_matter_at(get_pos_towards(pos, dir));
 }
 
 /*
 MATTER STATE QUERIES
 */
-fn is_empty(matter: Matter) -> bool { return matter.id == empty_matter; }
-fn is_powder(matter: Matter) -> bool { return matter.id == state_powder; }
-fn is_liquid(matter: Matter) -> bool { return matter.id == state_liquid; }
-fn is_gas(matter: Matter) -> bool { return matter.id == state_gas; }
-fn is_solid_gravity(matter: Matter) -> bool { return matter.id == state_solid_gravity; } 
+fn is_empty(matter: Matter) -> bool { return matter.state == empty_matter; }
+fn is_powder(matter: Matter) -> bool { return matter.state == state_powder; }
+fn is_liquid(matter: Matter) -> bool { return matter.state == state_liquid; }
+fn is_gas(matter: Matter) -> bool { return matter.state == state_gas; }
+fn is_solid_gravity(matter: Matter) -> bool { return matter.state == state_solid_gravity; } 
+// Solids are never moved by any pass and no pass moves anything into them
+fn is_solid(matter: Matter) -> bool { return matter.state == state_solid; } 
 fn is_gravity(matter: Matter) -> bool { return is_powder(matter) || is_liquid(matter) || is_solid_gravity(matter); } 
 
 /*
 MATTER MOVEMENT QUERIES
 */
 
+/*
+================== Velocity ==================
+*/
+// Cells per tick added to the fall velocity each tick, and the highest fall velocity
+const GRAVITY: f32 = 0.5;
+const MAX_FALL_SPEED: i32 = 8;
+
+// Velocity along gravity, in cells per tick
+fn fall_speed(matter: Matter) -> f32 {
+	let g: vec2<f32> = vec2<f32>(gravity_offset());
+	return dot(matter.velocity, g) / dot(g, g);
+}
+
+fn wanted_fall_distance(matter: Matter) -> i32 {
+	return clamp(i32(fall_speed(matter)), 1, MAX_FALL_SPEED);
+}
+
+// How many cells the matter at `pos` falls this tick, stopping before the first non empty cell or
+// the wall
+fn fall_distance(pos: vec2<i32>, matter: Matter) -> i32 {
+	if (!is_gravity(matter)) {
+		return 0;
+	}
+
+	let wanted: i32 = wanted_fall_distance(matter);
+	var distance: i32 = 0;
+	for (var k: i32 = 1; k <= wanted; k++) {
+		let to_pos: vec2<i32> = pos + gravity_offset() * k;
+		let is_wall: bool = pc.boundary == BOUNDARY_WALL && !is_inside_sim_canvas(to_pos);
+		if (is_wall || !is_empty(read_matter_at(to_pos))) {
+			break;
+		}
+		distance = k;
+	}
+	return distance;
+}
+
+// Velocity of the matter at `pos` once it fell `distance` cells. Blocked matter takes the velocity
+// of what blocks it, so it stops on the ground but keeps up with matter falling ahead of it.
+fn velocity_after_fall(pos: vec2<i32>, matter: Matter, distance: i32) -> vec2<f32> {
+	var speed: f32 = fall_speed(matter);
+	if (distance == wanted_fall_distance(matter)) {
+		speed = min(speed + GRAVITY, f32(MAX_FALL_SPEED));
+	} else {
+		let blocker_pos: vec2<i32> = pos + gravity_offset() * (distance + 1);
+		if (is_inside_boundary(blocker_pos)) {
+			speed = min(speed, fall_speed(read_matter_at(blocker_pos)));
+		} else {
+			speed = 0.0;
+		}
+	}
+	return vec2<f32>(gravity_offset()) * speed;
+}
+
 /*
 ================== Empty ==================
 */
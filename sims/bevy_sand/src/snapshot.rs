@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        renderer::RenderDevice,
+    },
+};
+use sim_core::clock::SimClock;
+use sim_core::readback::{ReadbackEvent, ReadbackRequests};
+use sim_core::snapshot::{
+    LoadWorld, SaveWorld, SizeLimits, SnapshotError, SnapshotHeader, SnapshotReader, SnapshotWriter,
+};
+
+use crate::pipeline_assets::Matter;
+use crate::registry::MatterRegistry;
+use crate::settings::{SandAppSettings, SimSize};
+
+/// Start of the snapshots of sand worlds.
+pub const SAND_SNAPSHOT_MAGIC: [u8; 4] = *b"SAND";
+/// Tag of the readback of `matter_in` followed by `temperature_in`.
+pub const SAVE_READBACK: &str = "sand_save";
+
+pub struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingSave>()
+            .init_resource::<SandWorldUpload>()
+            .add_plugin(ExtractResourcePlugin::<SandWorldUpload>::default())
+            .add_system(clear_world_upload.in_base_set(CoreSet::First))
+            .add_systems((request_save, finish_save, load_world));
+    }
+}
+
+/// The cells and temperatures of a whole world.
+#[derive(Debug, Clone)]
+pub struct SandWorld {
+    pub size: SimSize,
+    pub matter: Vec<Matter>,
+    pub temperature: Vec<f32>,
+}
+
+impl SandWorld {
+    /// Splits the readback of [`SAVE_READBACK`].
+    fn from_readback(size: SimSize, data: &[u8]) -> Option<Self> {
+        let matter_len = size.num_of_cells() * std::mem::size_of::<Matter>();
+        let temperature_len = size.num_of_cells() * std::mem::size_of::<f32>();
+        if data.len() != matter_len + temperature_len {
+            return None;
+        }
+
+        let (matter, temperature) = data.split_at(matter_len);
+        Some(Self {
+            size,
+            matter: bytemuck::pod_collect_to_vec(matter),
+            temperature: bytemuck::pod_collect_to_vec(temperature),
+        })
+    }
+
+    /// Writes the world with the names of the matters, so it can be loaded after the registry
+    /// changed.
+    pub fn write(&self, registry: &MatterRegistry, seed: u32, step: u32) -> SnapshotWriter {
+        let header = SnapshotHeader::new(SAND_SNAPSHOT_MAGIC, self.size.as_tuple(), seed, step);
+        let mut writer = SnapshotWriter::new(&header);
+        write_matters(&mut writer, registry, &self.matter);
+        writer.cells(&self.temperature);
+        writer
+    }
+
+    /// Reads a world written by [`SandWorld::write`], if it fits in `limits`. Matters are matched
+    /// by name, the ones missing from `registry` are emptied.
+    pub fn read(
+        data: &[u8],
+        registry: &MatterRegistry,
+        limits: &SizeLimits,
+    ) -> Result<(SnapshotHeader, Self), SnapshotError> {
+        let mut reader = SnapshotReader::new(data, SAND_SNAPSHOT_MAGIC)?;
+        reader.check_size(limits, std::mem::size_of::<Matter>() as u64)?;
+        let header = *reader.header();
+
+        // Sizes are whole chunks, a file with any other size can't fill the buffers
+        let size = SimSize::new(header.width, header.height);
+        if size.as_tuple() != (header.width, header.height) {
+            return Err(SnapshotError::WrongCellCount {
+                expected: size.num_of_cells(),
+                found: header.num_of_cells(),
+            });
+        }
+
+        let matter = read_matters(&mut reader, registry)?;
+        let temperature = reader.cells::<f32>()?;
+
+        let world = Self {
+            size,
+            matter,
+            temperature,
+        };
+        Ok((header, world))
+    }
+}
+
+/// Writes the names of the matters followed by the cells, as stamps and snapshots store them.
+pub fn write_matters(writer: &mut SnapshotWriter, registry: &MatterRegistry, cells: &[Matter]) {
+    writer.u32(registry.matters.len() as u32);
+    for definition in registry.matters.iter() {
+        writer.string(&definition.name);
+    }
+    writer.cells(cells);
+}
+
+/// Reads the cells written by [`write_matters`]. Matters are matched by name, the ones missing from
+/// `registry` are emptied.
+pub fn read_matters(
+    reader: &mut SnapshotReader,
+    registry: &MatterRegistry,
+) -> Result<Vec<Matter>, SnapshotError> {
+    let matter_table = (0..reader.u32()?)
+        .map(|_| reader.string().map(|name| registry.id_of(&name)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let matter = reader
+        .cells::<Matter>()?
+        .into_iter()
+        .map(
+            |cell| match matter_table.get(cell.id as usize).copied().flatten() {
+                Some(id) => {
+                    // The definition may have changed, only the state of the cell is kept
+                    let mut matter = registry.matter(id);
+                    matter.lifetime = cell.lifetime;
+                    matter.velocity = cell.velocity;
+                    matter.color = cell.color;
+                    matter
+                }
+                None => Matter::EMPTY,
+            },
+        )
+        .collect();
+    Ok(matter)
+}
+
+// ================================== Save ================================== //
+
+/// Save waiting for the readback of the world.
+#[derive(Resource, Default)]
+struct PendingSave(Option<(SaveWorld, SimSize, u32)>);
+
+fn request_save(
+    clock: Res<SimClock>,
+    sim_size: Res<SimSize>,
+    mut pending: ResMut<PendingSave>,
+    mut requests: ResMut<ReadbackRequests>,
+    mut save_events: EventReader<SaveWorld>,
+) {
+    if let Some(save) = save_events.iter().last() {
+        requests.request(SAVE_READBACK);
+        pending.0 = Some((
+            SaveWorld {
+                path: save.path.clone(),
+            },
+            *sim_size,
+            clock.step,
+        ));
+    }
+}
+
+fn finish_save(
+    settings: Res<SandAppSettings>,
+    registry: Option<Res<MatterRegistry>>,
+    mut pending: ResMut<PendingSave>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag != SAVE_READBACK {
+            continue;
+        }
+        let (Some((save, size, step)), Some(registry)) = (pending.0.take(), registry.as_ref())
+        else {
+            continue;
+        };
+
+        let Some(world) = SandWorld::from_readback(size, &readback.data) else {
+            warn!("The world was resized before it could be saved");
+            continue;
+        };
+
+        match world.write(registry, settings.seed, step).save(&save.path) {
+            Ok(()) => info!("Saved the world to {}", save.path.display()),
+            Err(error) => warn!("Failed to save {}: {}", save.path.display(), error),
+        }
+    }
+}
+
+// ================================== Load ================================== //
+
+/// World written over the buffers of the simulation by the render world, only for the frame it
+/// was loaded in.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct SandWorldUpload(pub Option<Arc<SandWorld>>);
+
+fn clear_world_upload(mut upload: ResMut<SandWorldUpload>) {
+    if upload.0.is_some() {
+        upload.0 = None;
+    }
+}
+
+fn load_world(
+    mut clock: ResMut<SimClock>,
+    mut sim_size: ResMut<SimSize>,
+    mut settings: ResMut<SandAppSettings>,
+    mut upload: ResMut<SandWorldUpload>,
+    registry: Option<Res<MatterRegistry>>,
+    device: Res<RenderDevice>,
+    mut load_events: EventReader<LoadWorld>,
+) {
+    let Some(load) = load_events.iter().last() else {
+        return;
+    };
+    let Some(registry) = registry else {
+        warn!("Can't load {} before the matters", load.path.display());
+        return;
+    };
+
+    let loaded = std::fs::read(&load.path)
+        .map_err(SnapshotError::from)
+        .and_then(|data| SandWorld::read(&data, &registry, &SizeLimits::of_device(&device)));
+    let (header, world) = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            warn!("Failed to load {}: {}", load.path.display(), error);
+            return;
+        }
+    };
+
+    // The buffers are recreated for the new size in the same frame, before the upload
+    if *sim_size != world.size {
+        *sim_size = world.size;
+    }
+    settings.seed = header.seed;
+    clock.jump_to(header.step);
+    upload.0 = Some(Arc::new(world));
+    info!("Loaded the world from {}", load.path.display());
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, render::renderer::RenderDevice};
+use sim_core::readback::{ReadbackEvent, ReadbackRequests};
+use sim_core::snapshot::{
+    SizeLimits, SnapshotError, SnapshotHeader, SnapshotReader, SnapshotWriter,
+};
+use sim_core::stamp::{crop, read_stamp_file, LoadedStamp, SaveStamp, StampCells, StampLibrary};
+
+use crate::pipeline_assets::Matter;
+use crate::registry::MatterRegistry;
+use crate::settings::SimSize;
+use crate::snapshot::{read_matters, write_matters};
+
+/// Start of the stamp files of sand.
+pub const SAND_STAMP_MAGIC: [u8; 4] = *b"SSTP";
+/// Tag of the readback of `matter_in`, cropped to the saved stamp.
+pub const STAMP_READBACK: &str = "sand_stamp";
+
+pub struct StampPlugin;
+impl Plugin for StampPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingStamp>()
+            .add_systems((request_stamp, finish_stamp, load_stamp).chain());
+    }
+}
+
+/// The cells of a prefab structure. Temperatures aren't kept, placed cells start at the
+/// temperature of their matter like painted ones.
+#[derive(Debug, Clone)]
+pub struct SandStamp {
+    pub size: UVec2,
+    pub matter: Vec<Matter>,
+}
+
+impl SandStamp {
+    /// Writes the stamp with the names of the matters, like [`SandWorld`](crate::snapshot::SandWorld).
+    pub fn write(&self, registry: &MatterRegistry) -> SnapshotWriter {
+        let header = SnapshotHeader::new(SAND_STAMP_MAGIC, self.size.into(), 0, 0);
+        let mut writer = SnapshotWriter::new(&header);
+        write_matters(&mut writer, registry, &self.matter);
+        writer
+    }
+
+    /// Reads a stamp written by [`SandStamp::write`] if it fits in `limits`, matching the matters
+    /// by name.
+    pub fn read(
+        data: &[u8],
+        registry: &MatterRegistry,
+        limits: &SizeLimits,
+    ) -> Result<Self, SnapshotError> {
+        let mut reader = SnapshotReader::new(data, SAND_STAMP_MAGIC)?;
+        // Placed as the matter id of each cell
+        reader.check_size(limits, std::mem::size_of::<u32>() as u64)?;
+        let size = UVec2::new(reader.header().width, reader.header().height);
+        Ok(Self {
+            size,
+            matter: read_matters(&mut reader, registry)?,
+        })
+    }
+
+    /// The matter id of each cell, as placed by the stamp tool.
+    fn to_stamp_cells(&self, name: &str) -> StampCells {
+        StampCells {
+            name: name.to_string(),
+            size: self.size,
+            cells: self.matter.iter().map(|matter| matter.id).collect(),
+        }
+    }
+}
+
+// ================================== Save ================================== //
+
+/// Stamp waiting for the readback of the world.
+#[derive(Resource, Default)]
+struct PendingStamp(Option<(SaveStamp, SimSize)>);
+
+fn request_stamp(
+    sim_size: Res<SimSize>,
+    mut pending: ResMut<PendingStamp>,
+    mut requests: ResMut<ReadbackRequests>,
+    mut save_events: EventReader<SaveStamp>,
+) {
+    if let Some(save) = save_events.iter().last() {
+        requests.request(STAMP_READBACK);
+        pending.0 = Some((save.clone(), *sim_size));
+    }
+}
+
+fn finish_stamp(
+    registry: Option<Res<MatterRegistry>>,
+    mut library: ResMut<StampLibrary>,
+    mut loaded: ResMut<LoadedStamp>,
+    mut pending: ResMut<PendingStamp>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag != STAMP_READBACK {
+            continue;
+        }
+        let (Some((save, size)), Some(registry)) = (pending.0.take(), registry.as_ref()) else {
+            continue;
+        };
+
+        if readback.data.len() != size.num_of_cells() * std::mem::size_of::<Matter>() {
+            warn!("The world was resized before the stamp could be saved");
+            continue;
+        }
+
+        let matter: Vec<Matter> = bytemuck::pod_collect_to_vec(&readback.data);
+        let stamp = SandStamp {
+            size: save.max - save.min,
+            matter: crop(&matter, size.width, save.min, save.max),
+        };
+        match library.save(&save.name, &stamp.write(registry).finish()) {
+            Ok(path) => {
+                // Loaded again when saved over the selected stamp
+                library.selected = Some(save.name);
+                loaded.0 = None;
+                info!("Saved the stamp to {}", path.display());
+            }
+            Err(error) => warn!("Failed to save the stamp {}: {}", save.name, error),
+        }
+    }
+}
+
+// ================================== Load ================================== //
+
+/// Loads the stamp selected in the library, and unselects the ones that can't be loaded. Stamps
+/// are loaded again when the matters change, as their ids may have.
+fn load_stamp(
+    registry: Option<Res<MatterRegistry>>,
+    device: Res<RenderDevice>,
+    mut library: ResMut<StampLibrary>,
+    mut loaded: ResMut<LoadedStamp>,
+) {
+    let Some(registry) = registry else {
+        return;
+    };
+    if !loaded.is_outdated(&library) && !registry.is_changed() {
+        return;
+    }
+    let Some(name) = library.selected.clone() else {
+        loaded.0 = None;
+        return;
+    };
+
+    let limits = SizeLimits::of_device(&device);
+    match read_stamp_file(&library, &name, |data| {
+        SandStamp::read(data, &registry, &limits)
+    }) {
+        Ok(stamp) => loaded.0 = Some(Arc::new(stamp.to_stamp_cells(&name))),
+        Err(error) => {
+            warn!("Failed to load the stamp {}", error);
+            library.selected = None;
+            loaded.0 = None;
+        }
+    }
+}
@@ -1,15 +1,24 @@
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, Ui},
     EguiContexts, EguiPlugin,
 };
 use bevy_fn_plugin::bevy_plugin;
+use sim_core::brush::brush_ui;
+use sim_core::clock::{clock_ui, SimClock};
+use sim_core::history::{history_ui, SimHistory};
+use sim_core::inspect::{hovered_cell, inspector_tooltip, CellInspector};
+use sim_core::recording::{recording_ui, SimRecording};
+use sim_core::snapshot::{snapshot_ui, LoadWorld, SaveWorld, SnapshotPath};
+use sim_core::stamp::{stamp_ui, SaveStamp, StampLibrary};
 
-use crate::constants::SIM_SIZE;
-use crate::input::AutomataParams;
+use crate::import::{ImageImport, ImportImage, PaletteMapping};
+use crate::input::{AutomataParams, BrushKind, PaintMode};
 use crate::pipeline_assets::Matter;
-use crate::settings::SandAppSettings;
+use crate::registry::MatterRegistry;
+use crate::settings::{Boundary, Gravity, SandAppSettings, SimSize, MAX_MOVEMENT_STEPS};
 
 const SPACING: f32 = 10.0;
 const TEXT_SIZE: f32 = 15.0;
@@ -17,7 +26,8 @@ const HEADING_SIZE: f32 = 20.0;
 
 #[bevy_plugin]
 pub fn SandUIPlugin(app: &mut App) {
-    app.add_plugin(EguiPlugin).add_system(user_interface);
+    app.add_plugin(EguiPlugin)
+        .add_systems((user_interface, stamps_window, cell_tooltip));
 }
 
 /// Give our text a custom size
@@ -38,13 +48,51 @@ fn heading(ui: &mut Ui, text: impl Into<String>) {
     );
 }
 
+/// Playback, settings and size of the simulation.
+#[derive(SystemParam)]
+pub struct SimControls<'w> {
+    clock: ResMut<'w, SimClock>,
+    history: ResMut<'w, SimHistory>,
+    settings: ResMut<'w, SandAppSettings>,
+    sim_size: ResMut<'w, SimSize>,
+}
+
+/// Saving, loading, recording and importing the world.
+#[derive(SystemParam)]
+pub struct WorldControls<'w> {
+    recording: ResMut<'w, SimRecording>,
+    snapshot_path: ResMut<'w, SnapshotPath>,
+    save_events: EventWriter<'w, SaveWorld>,
+    load_events: EventWriter<'w, LoadWorld>,
+    image_import: ResMut<'w, ImageImport>,
+    import_events: EventWriter<'w, ImportImage>,
+}
+
 /// System to generate user interface with egui
 pub fn user_interface(
     mut contexts: EguiContexts,
     diagnostics: Res<Diagnostics>,
     mut params: ResMut<AutomataParams>,
-    mut settings: ResMut<SandAppSettings>,
+    mut inspector: ResMut<CellInspector>,
+    registry: Option<Res<MatterRegistry>>,
+    sim: SimControls,
+    world: WorldControls,
 ) {
+    let SimControls {
+        mut clock,
+        mut history,
+        mut settings,
+        mut sim_size,
+    } = sim;
+    let WorldControls {
+        mut recording,
+        mut snapshot_path,
+        mut save_events,
+        mut load_events,
+        mut image_import,
+        mut import_events,
+    } = world;
+
     egui::Window::new("Automata")
         .constrain(true)
         .fixed_pos(egui::pos2(10.0, 10.0))
@@ -60,64 +108,238 @@ pub fn user_interface(
                 }
             }
 
-            sized_text(ui, format!("Grid size: {:?}", SIM_SIZE));
+            sized_text(ui, format!("Grid size: {}", *sim_size));
 
             sized_text(
                 ui,
-                (if settings.is_paused {
-                    "Paused"
-                } else {
-                    "Playing"
-                })
-                .to_string(),
+                (if clock.is_paused { "Paused" } else { "Playing" }).to_string(),
             );
 
             ui.add_space(SPACING);
             heading(ui, "Settings");
             ui.add_space(SPACING);
 
+            brush_ui(ui, &mut params.brush);
             ui.checkbox(&mut params.use_square_brush, "Square Brush");
             ui.add(egui::Slider::new(&mut params.radius, 0.5..=200.0).text("Brush Size"));
+            ui.add(egui::Slider::new(&mut params.heat_strength, 1.0..=500.0).text("Heat Strength"));
+            ui.add(egui::Slider::new(&mut params.wind_strength, 0.05..=1.0).text("Wind Strength"));
+            ui.checkbox(&mut inspector.is_enabled, "Inspect Cells")
+                .on_hover_text("Show the state of the cell under the mouse");
 
             ui.add_space(SPACING);
 
+            clock_ui(ui, &mut clock);
+            history_ui(ui, &mut history, &mut clock);
             ui.add(
-                egui::Slider::new(&mut settings.movement_steps, 1..=3)
+                egui::Slider::new(&mut settings.movement_steps, 1..=MAX_MOVEMENT_STEPS)
                     .text("Simulation Movement Steps"),
             );
+            ui.checkbox(&mut settings.adaptive_movement, "Adaptive Movement")
+                .on_hover_text("Stop the movement steps early once nothing moves");
             ui.add(
                 egui::Slider::new(&mut settings.dispersion_steps, 1..=10)
                     .text("Simulation Dispersion Steps"),
             );
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut settings.seed));
+                ui.label("Seed");
+            });
+            egui::ComboBox::from_label("Gravity")
+                .selected_text(format!("{:?}", settings.gravity))
+                .show_ui(ui, |ui| {
+                    for gravity in Gravity::ALL {
+                        ui.selectable_value(
+                            &mut settings.gravity,
+                            gravity,
+                            format!("{:?}", gravity),
+                        );
+                    }
+                });
+            egui::ComboBox::from_label("Boundary")
+                .selected_text(format!("{:?}", settings.boundary))
+                .show_ui(ui, |ui| {
+                    for boundary in Boundary::ALL {
+                        ui.selectable_value(
+                            &mut settings.boundary,
+                            boundary,
+                            format!("{:?}", boundary),
+                        );
+                    }
+                });
+
+            // Only touch the size when picking another one, changing it clears the simulation
+            let mut size = *sim_size;
+            egui::ComboBox::from_label("Grid Size")
+                .selected_text(size.to_string())
+                .show_ui(ui, |ui| {
+                    for preset in SimSize::PRESETS {
+                        ui.selectable_value(&mut size, preset, preset.to_string());
+                    }
+                });
+            if size != *sim_size {
+                *sim_size = size;
+            }
+
+            ui.add_space(SPACING);
+            heading(ui, "World");
+            ui.add_space(SPACING);
+
+            snapshot_ui(ui, &mut snapshot_path, &mut save_events, &mut load_events);
+            recording_ui(ui, &mut recording);
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut image_import.path);
+                if ui.button("Import PNG").clicked() {
+                    import_events.send(ImportImage {
+                        path: image_import.path.clone().into(),
+                        mapping: image_import.mapping,
+                        palette_path: image_import.palette_path.clone().into(),
+                    });
+                }
+            });
+            egui::ComboBox::from_label("Colors")
+                .selected_text(format!("{:?}", image_import.mapping))
+                .show_ui(ui, |ui| {
+                    for mapping in PaletteMapping::ALL {
+                        ui.selectable_value(
+                            &mut image_import.mapping,
+                            mapping,
+                            format!("{:?}", mapping),
+                        );
+                    }
+                })
+                .response
+                .on_hover_text("Match the pixels against the matters, or against a palette file");
+            if image_import.mapping == PaletteMapping::Palette {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut image_import.palette_path);
+                    ui.label("Palette");
+                });
+            }
+            if let Some(error) = &image_import.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
         });
 
     egui::Window::new("Matters")
         .constrain(true)
         .show(contexts.ctx_mut(), |ui| {
-            if ui
-                .selectable_label(params.selected_matter.id == 0, "Empty")
-                .clicked()
-            {
-                params.selected_matter = Matter::EMPTY;
-            }
-            if ui
-                .selectable_label(params.selected_matter.id == 1, "Sand")
-                .clicked()
-            {
-                params.selected_matter = Matter::SAND;
-            }
-            if ui
-                .selectable_label(params.selected_matter.id == 2, "Water")
-                .clicked()
-            {
-                params.selected_matter = Matter::WATER;
+            let Some(registry) = registry else { return };
+
+            for (id, definition) in registry.matters.iter().enumerate() {
+                let id = id as u32;
+                let is_selected =
+                    params.brush_kind == BrushKind::Matter && params.selected_matter == id;
+                if ui
+                    .selectable_label(is_selected, definition.name.as_str())
+                    .on_hover_text(format!("{:?}", definition.state))
+                    .clicked()
+                {
+                    params.brush_kind = BrushKind::Matter;
+                    params.selected_matter = id;
+                }
             }
 
-            if ui
-                .selectable_label(params.selected_matter.id == 3, "Gas")
-                .clicked()
-            {
-                params.selected_matter = Matter::GAS;
+            ui.separator();
+            egui::ComboBox::from_label("Paint")
+                .selected_text(params.paint_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in PaintMode::ALL {
+                        ui.selectable_value(&mut params.paint_mode, mode, mode.name());
+                    }
+                });
+            if params.paint_mode.is_masked() {
+                let mask_name = registry
+                    .matters
+                    .get(params.mask_matter as usize)
+                    .map_or("", |definition| definition.name.as_str());
+                egui::ComboBox::from_label("Only")
+                    .selected_text(mask_name)
+                    .show_ui(ui, |ui| {
+                        for (id, definition) in registry.matters.iter().enumerate() {
+                            ui.selectable_value(
+                                &mut params.mask_matter,
+                                id as u32,
+                                definition.name.as_str(),
+                            );
+                        }
+                    });
             }
+
+            ui.separator();
+            ui.selectable_value(&mut params.brush_kind, BrushKind::Heat, "Heat");
+            ui.selectable_value(&mut params.brush_kind, BrushKind::Cool, "Cool");
+            ui.selectable_value(&mut params.brush_kind, BrushKind::Wind, "Wind")
+                .on_hover_text("Drag to paint wind blowing that way");
+            ui.selectable_value(&mut params.brush_kind, BrushKind::Calm, "Calm");
+        });
+}
+
+/// Stamps saved from the world, placed as prefab structures with the stamp tool.
+fn stamps_window(
+    mut contexts: EguiContexts,
+    sim_size: Res<SimSize>,
+    mut params: ResMut<AutomataParams>,
+    mut library: ResMut<StampLibrary>,
+    mut save_events: EventWriter<SaveStamp>,
+) {
+    egui::Window::new("Stamps")
+        .constrain(true)
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let size = UVec2::new(sim_size.width, sim_size.height);
+            stamp_ui(ui, &mut library, &mut params.brush, size, &mut save_events);
         });
 }
+
+/// Tooltip with the state of the cell under the mouse, read back from the GPU.
+fn cell_tooltip(
+    mut contexts: EguiContexts,
+    window_query: Query<&Window>,
+    params: Res<AutomataParams>,
+    sim_size: Res<SimSize>,
+    registry: Option<Res<MatterRegistry>>,
+    mut inspector: ResMut<CellInspector>,
+) {
+    let ctx = contexts.ctx_mut();
+    let is_over_canvas = !ctx.is_pointer_over_area()
+        && window_query
+            .get_single()
+            .is_ok_and(|window| window.cursor_position().is_some());
+    let size = UVec2::new(sim_size.width, sim_size.height);
+    inspector.hover(
+        is_over_canvas
+            .then(|| hovered_cell(params.mouse_pos, size))
+            .flatten(),
+    );
+
+    if let Some(cell) = inspector.inspected() {
+        inspector_tooltip(ctx, cell, &describe_cell(&cell.data, registry.as_deref()));
+    }
+}
+
+/// Lines describing a cell read back as its [`Matter`] followed by its temperature.
+fn describe_cell(data: &[u8], registry: Option<&MatterRegistry>) -> Vec<String> {
+    let matter_size = std::mem::size_of::<Matter>();
+    if data.len() != matter_size + std::mem::size_of::<f32>() {
+        return Vec::new();
+    }
+    let matter: Matter = bytemuck::pod_read_unaligned(&data[..matter_size]);
+    let temperature: f32 = bytemuck::pod_read_unaligned(&data[matter_size..]);
+
+    let name = registry
+        .and_then(|registry| registry.get(matter.id))
+        .map_or("Unknown", |definition| definition.name.as_str());
+    let [r, g, b, a] = matter.color;
+    let [vx, vy] = matter.velocity;
+    vec![
+        format!("Matter: {} ({})", name, matter.id),
+        format!("Weight: {:.2}", matter.weight),
+        format!("Dispersion: {}", matter.dispersion),
+        format!("Color: {:.2}, {:.2}, {:.2}, {:.2}", r, g, b, a),
+        format!("Velocity: {:.2}, {:.2}", vx, vy),
+        format!("Lifetime: {}", matter.lifetime),
+        format!("Temperature: {:.1}", temperature),
+    ]
+}
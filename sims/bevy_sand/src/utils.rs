@@ -4,7 +4,7 @@ use bevy::render::renderer::RenderDevice;
 use bevy::render::texture::ImageSampler;
 use bevy::{prelude::*, render::render_resource::*};
 
-use crate::constants::SIM_SIZE;
+use crate::settings::SimSize;
 
 // ================================== Render Utils ================================== //
 
@@ -27,7 +27,10 @@ pub fn create_texture_2d(size: (u32, u32), format: TextureFormat, filter: Filter
     );
 
     image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+        TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::STORAGE_BINDING
+        | TextureUsages::TEXTURE_BINDING;
 
     image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
         mag_filter: filter,
@@ -49,12 +52,12 @@ pub fn create_storage_buffer_with_data<T: bytemuck::Pod + bytemuck::Zeroable>(
     device.create_buffer_with_data(&BufferInitDescriptor {
         label,
         contents: bytemuck::cast_slice(data),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
     })
 }
 
 // ================================== Camera ================================== //
 
-pub fn world_pos_to_canvas_pos(world_pos: Vec2) -> Vec2 {
-    world_pos + Vec2::new(SIM_SIZE.0 as f32 / 2.0, SIM_SIZE.1 as f32 / 2.0)
+pub fn world_pos_to_canvas_pos(world_pos: Vec2, sim_size: &SimSize) -> Vec2 {
+    world_pos + sim_size.as_vec2() / 2.0
 }
@@ -5,6 +5,20 @@ var<uniform> size : vec2<u32>; // width, height
 @group(0) @binding(1) 
 var<storage, read_write> aliveSrc : array<Cell>;
 
+struct FloodFill {
+    kind: u32,
+    id: u32,
+    reached: atomic<u32>,
+    cells: array<atomic<u32>>,
+}
+// `alive` value the flood fill spreads over, the fill and the cells its passes reached this frame,
+// and a `FLOOD_*` state per cell
+@group(0) @binding(2)
+var<storage, read_write> flood : FloodFill;
+// An `alive` value per cell of the stamp
+@group(0) @binding(3)
+var<storage, read> stamp : array<u32>;
+
 // Line v->w, point p
 // https://stackoverflow.com/questions/849211/shortest-distance-between-a-point-and-a-line-segment
 fn closest_point_on_line(v: vec2<f32>, w: vec2<f32>, p: vec2<f32>) -> vec2<f32> {
@@ -19,7 +33,7 @@ fn closest_point_on_line(v: vec2<f32>, w: vec2<f32>, p: vec2<f32>) -> vec2<f32>
     return projection;
 }
 
-fn draw_particle_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32) {
+fn is_in_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32) -> bool {
     let y_start = draw_pos.y - radius;
     let y_end = draw_pos.y + radius;
     let x_start = draw_pos.x - radius;
@@ -27,20 +41,78 @@ fn draw_particle_circle(pos: vec2<f32>, draw_pos: vec2<f32>, radius: f32) {
     if (pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end) {
         let diff = pos - draw_pos;
         let dist = length(diff);
-        if (round(dist) <= radius) {
-            aliveSrc[idx(vec2<i32>(pos))] = new_cell(true);
-        }
+        return round(dist) <= radius;
     }
+    return false;
 }
 
-fn draw_particle_square(pos: vec2<f32>, draw_pos: vec2<f32>, size: f32)  {
+fn is_in_square(pos: vec2<f32>, draw_pos: vec2<f32>, size: f32) -> bool {
 	let y_start = draw_pos.y - size / 2.;
 	let y_end = draw_pos.y + size / 2.;
 	let x_start = draw_pos.x - size / 2.;
 	let x_end = draw_pos.x + size / 2.;
-	if (pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end) {
-		aliveSrc[idx(vec2<i32>(pos))] = new_cell(true);
-	}
+	return pos.x >= x_start && pos.x <= x_end && pos.y >= y_start && pos.y <= y_end;
+}
+
+// Shapes are outlined as thick as the brush size, at least a cell
+fn is_in_rectangle(pos: vec2<f32>, corner_a: vec2<f32>, corner_b: vec2<f32>) -> bool {
+    let low = min(corner_a, corner_b);
+    let high = max(corner_a, corner_b);
+    if (any(pos < low) || any(pos > high)) {
+        return false;
+    }
+    let edge = min(pos - low, high - pos);
+    return bool(pc.filled) || min(edge.x, edge.y) < max(pc.draw_radius, 1.0);
+}
+
+fn is_in_ellipse(pos: vec2<f32>, corner_a: vec2<f32>, corner_b: vec2<f32>) -> bool {
+    let center = (corner_a + corner_b) / 2.0;
+    let radii = max(abs(corner_b - corner_a) / 2.0, vec2<f32>(0.5, 0.5));
+    let dist = length((pos - center) / radii);
+    if (dist > 1.0) {
+        return false;
+    }
+    return bool(pc.filled) || (1.0 - dist) * min(radii.x, radii.y) < max(pc.draw_radius, 1.0);
+}
+
+// Same as `hash` in `game_of_life.wgsl`
+fn hash(value: u32) -> u32 {
+    var state = value;
+    state = state ^ 2747636419u;
+    state = state * 2654435769u;
+    state = state ^ state >> 16u;
+    state = state * 2654435769u;
+    state = state ^ state >> 16u;
+    state = state * 2654435769u;
+    return state;
+}
+
+fn spray_chance(pos: vec2<f32>) -> f32 {
+    let cell = u32(idx(vec2<i32>(pos)));
+    return f32(hash(hash(pc.brush_seed) ^ cell)) / 4294967295.0;
+}
+
+// Whether the brush covers the cell at `pos` this frame
+fn is_brushed(pos: vec2<f32>) -> bool {
+    if (pc.tool == TOOL_RECTANGLE) {
+        return is_in_rectangle(pos, pc.draw_start, pc.draw_end);
+    }
+    if (pc.tool == TOOL_ELLIPSE) {
+        return is_in_ellipse(pos, pc.draw_start, pc.draw_end);
+    }
+
+    // Freehand, spray and lines stamp the brush along the segment
+    let point_on_line = closest_point_on_line(pc.draw_start, pc.draw_end, pos);
+    var is_stamped: bool;
+    if (bool(pc.draw_square)) {
+        is_stamped = is_in_square(pos, point_on_line, pc.draw_radius);
+    } else {
+        is_stamped = is_in_circle(pos, point_on_line, pc.draw_radius);
+    }
+    if (pc.tool == TOOL_SPRAY) {
+        return is_stamped && spray_chance(pos) < pc.spray_density;
+    }
+    return is_stamped;
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -53,12 +125,108 @@ fn draw(@builtin(global_invocation_id) invocation_id: vec3<u32>)
 
     if (pc.draw_radius > 0.0) {
         let pos = vec2<f32>(pixel);
-        let point_on_line = closest_point_on_line(pc.draw_start, pc.draw_end, pos);
+        if (is_brushed(pos)) {
+            aliveSrc[idx(vec2<i32>(pos))] = new_cell(true);
+        }
+    }
+}
+
+/*
+FLOOD FILL
+Spreads over the cells as alive as the clicked one, a cell at a time per pass. Each cell is painted
+once, on the pass it is reached. The cells reached are counted, the fill is done once a frame of
+passes reaches none.
+*/
+const FLOOD_UNVISITED: u32 = 0u;
+const FLOOD_REACHED: u32 = 1u;
+const FLOOD_PAINTED: u32 = 2u;
+
+fn is_flooded(pos: vec2<i32>) -> bool {
+    if (any(pos < vec2<i32>(0, 0)) || any(pos >= vec2<i32>(size))) {
+        return false;
+    }
+    return atomicLoad(&flood.cells[idx(pos)]) != FLOOD_UNVISITED;
+}
+
+// Run once on the cleared states, before the first pass
+@compute @workgroup_size(1, 1, 1)
+fn flood_seed() {
+    flood.id = pc.brush_seed;
+    let seed = vec2<i32>(pc.draw_start);
+    if (any(seed < vec2<i32>(0, 0)) || any(seed >= vec2<i32>(size))) {
+        return;
+    }
+
+    flood.kind = aliveSrc[idx(seed)].alive;
+    atomicStore(&flood.cells[idx(seed)], FLOOD_REACHED);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn flood_fill(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let pos = vec2<i32>(invocation_id.xy);
+    if (pos.x >= i32(size.x) || pos.y >= i32(size.y)) {
+        return;
+    }
 
-        if (bool(pc.draw_square)){
-            draw_particle_square(pos, point_on_line, pc.draw_radius);
-        }else{
-            draw_particle_circle(pos, point_on_line, pc.draw_radius);
+    let index = idx(pos);
+    var state = atomicLoad(&flood.cells[index]);
+    if (state == FLOOD_UNVISITED && aliveSrc[index].alive == flood.kind) {
+        let is_reached = is_flooded(pos + vec2<i32>(1, 0))
+            || is_flooded(pos - vec2<i32>(1, 0))
+            || is_flooded(pos + vec2<i32>(0, 1))
+            || is_flooded(pos - vec2<i32>(0, 1));
+        if (is_reached) {
+            state = FLOOD_REACHED;
+            atomicAdd(&flood.reached, 1u);
         }
     }
-}
\ No newline at end of file
+
+    if (state == FLOOD_REACHED) {
+        aliveSrc[index] = new_cell(true);
+        atomicStore(&flood.cells[index], FLOOD_PAINTED);
+    }
+}
+
+/*
+STAMP
+Places the stamp centered on `draw_start`, mirrored then turned as `stamp_transform` tells.
+*/
+
+// Index in the stamp of the cell placed at `pos`, or -1 outside of it
+fn stamp_index(pos: vec2<i32>) -> i32 {
+    let quarter_turns = pc.stamp_transform & 3u;
+    var turned_size = vec2<i32>(pc.stamp_size);
+    if (quarter_turns % 2u == 1u) {
+        turned_size = turned_size.yx;
+    }
+
+    var cell = pos - (vec2<i32>(floor(pc.draw_start)) - turned_size / 2);
+    if (any(cell < vec2<i32>(0, 0)) || any(cell >= turned_size)) {
+        return -1;
+    }
+
+    // Undo the turns one at a time, then the mirroring
+    var cell_size = turned_size;
+    for (var i = 0u; i < quarter_turns; i = i + 1u) {
+        cell = vec2<i32>(cell.y, cell_size.x - 1 - cell.x);
+        cell_size = cell_size.yx;
+    }
+    if ((pc.stamp_transform & 4u) != 0u) {
+        cell.x = cell_size.x - 1 - cell.x;
+    }
+    return cell.y * i32(pc.stamp_size.x) + cell.x;
+}
+
+// Dead cells are placed too, so patterns evolve as they were saved
+@compute @workgroup_size(8, 8, 1)
+fn place_stamp(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let pos = vec2<i32>(invocation_id.xy);
+    if (pos.x >= i32(size.x) || pos.y >= i32(size.y)) {
+        return;
+    }
+
+    let index = stamp_index(pos);
+    if (index >= 0) {
+        aliveSrc[idx(pos)] = new_cell(stamp[index] != 0u);
+    }
+}
@@ -0,0 +1,142 @@
+//! Runs the Game of Life without a window: loads a world, advances it and saves the result. Runs
+//! on the GPU when there is an adapter, otherwise on the [`CpuLifeWorld`].
+
+use std::path::Path;
+use std::time::Instant;
+
+use bevy::prelude::*;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use game_of_life_sim::cpu::CpuLifeWorld;
+use game_of_life_sim::snapshot::LifeWorld;
+use game_of_life_sim::{GameOfLifeSimPlugin, SimSize};
+use sim_core::headless::{run_on_gpu, write_stats, HeadlessArgs, HEADLESS_USAGE};
+use sim_core::snapshot::SizeLimits;
+
+fn main() {
+    let parsed = HeadlessArgs::parse(std::env::args().skip(1)).and_then(|args| match args.seed {
+        // Only the starting grid is random, and neither stepper seeds it
+        Some(_) => Err("--seed isn't supported by the Game of Life".to_string()),
+        None => Ok(args),
+    });
+    let args = match parsed {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n\n{HEADLESS_USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if args.use_gpu() {
+        run_gpu(args);
+    } else {
+        let started = Instant::now();
+        let result = run_cpu(&args);
+        finish(&args, "cpu", started, result);
+    }
+}
+
+/// Saves the stats and exits, with an error code if anything failed.
+fn finish(args: &HeadlessArgs, backend: &str, started: Instant, result: Result<LifeWorld, String>) {
+    let world = match result {
+        Ok(world) => world,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(stats_path) = args.stats.as_ref() {
+        let alive = world.cells.iter().filter(|[alive, _]| *alive != 0).count();
+        let stats = [
+            ("backend", backend.to_string()),
+            ("ticks", args.ticks.to_string()),
+            ("size", world.size.to_string()),
+            ("seconds", format!("{:.3}", started.elapsed().as_secs_f32())),
+            ("alive", alive.to_string()),
+        ]
+        .map(|(name, value)| (name.to_string(), value));
+
+        if let Err(error) = write_stats(stats_path, &stats) {
+            eprintln!("Failed to write {}: {}", stats_path.display(), error);
+            std::process::exit(1);
+        }
+    }
+
+    println!(
+        "Ran {} ticks on the {backend}, saved to {}",
+        args.ticks,
+        args.out.display()
+    );
+}
+
+fn read_world(path: &Path) -> Result<(u32, LifeWorld), String> {
+    std::fs::read(path)
+        .map_err(|error| error.to_string())
+        .and_then(|data| {
+            LifeWorld::read(&data, &SizeLimits::default()).map_err(|error| error.to_string())
+        })
+        .map(|(header, world)| (header.step, world))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+// ================================== GPU ================================== //
+
+fn run_gpu(args: HeadlessArgs) {
+    let mut app = App::new();
+    if let Some((width, height)) = args.size {
+        app.insert_resource(SimSize::new(width, height));
+    }
+
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            })
+            .disable::<WinitPlugin>(),
+    )
+    .add_plugin(GameOfLifeSimPlugin);
+
+    app.set_runner(move |mut app| {
+        let started = Instant::now();
+        let result = run_on_gpu(&mut app, &args)
+            .and_then(|()| read_world(&args.out))
+            .map(|(_, world)| world);
+        finish(&args, "gpu", started, result);
+    })
+    .run();
+}
+
+// ================================== CPU ================================== //
+
+fn run_cpu(args: &HeadlessArgs) -> Result<LifeWorld, String> {
+    let (first_step, mut cpu_world) = match args.world.as_ref() {
+        Some(path) => {
+            let (step, world) = read_world(path)?;
+            (step, CpuLifeWorld::new(world.size, world.cells))
+        }
+        None => {
+            let size = args
+                .size
+                .map(|(width, height)| SimSize::new(width, height))
+                .unwrap_or_default();
+            (0, CpuLifeWorld::random(size))
+        }
+    };
+
+    for _ in 0..args.ticks {
+        cpu_world.step();
+    }
+
+    let world = LifeWorld {
+        size: cpu_world.size(),
+        cells: cpu_world.cells().to_vec(),
+    };
+    world
+        .write(first_step.wrapping_add(args.ticks))
+        .save(&args.out)
+        .map_err(|error| format!("{}: {}", args.out.display(), error))?;
+    Ok(world)
+}
@@ -0,0 +1,149 @@
+use crate::{SimSize, WORKGROUP_SIZE};
+
+/// Same as `hash` in `game_of_life.wgsl`.
+fn hash(value: u32) -> u32 {
+    let mut state = value;
+    state ^= 2747636419;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state.wrapping_mul(2654435769)
+}
+
+fn random_float(value: u32) -> f32 {
+    hash(value) as f32 / 4294967295.0
+}
+
+/// A GPU-free copy of the Game of Life, with the same rules as `game_of_life.wgsl`. Cells are the
+/// `alive` and `heat` values stored in the buffers.
+#[derive(Debug, Clone)]
+pub struct CpuLifeWorld {
+    size: SimSize,
+    cells: Vec<[u32; 2]>,
+    scratch: Vec<[u32; 2]>,
+}
+
+impl CpuLifeWorld {
+    pub fn new(size: SimSize, cells: Vec<[u32; 2]>) -> Self {
+        assert_eq!(cells.len(), size.num_of_cells());
+        Self {
+            size,
+            scratch: cells.clone(),
+            cells,
+        }
+    }
+
+    /// Seeded the same way as the `init` pass.
+    pub fn random(size: SimSize) -> Self {
+        let workgroups_x = size.width / WORKGROUP_SIZE;
+        let cells = (0..size.height)
+            .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let alive = random_float(y * workgroups_x + x) > 0.9;
+                [alive as u32, 0]
+            })
+            .collect();
+        Self::new(size, cells)
+    }
+
+    pub fn size(&self) -> SimSize {
+        self.size
+    }
+
+    pub fn cells(&self) -> &[[u32; 2]] {
+        &self.cells
+    }
+
+    /// Neighbours wrap around the edges.
+    fn count_neighbors(&self, x: u32, y: u32) -> u32 {
+        let (width, height) = (self.size.width, self.size.height);
+        let mut count = 0;
+        for offset_y in [height - 1, 0, 1] {
+            for offset_x in [width - 1, 0, 1] {
+                if offset_x == 0 && offset_y == 0 {
+                    continue;
+                }
+
+                let neighbor_x = (x + offset_x) % width;
+                let neighbor_y = (y + offset_y) % height;
+                count += self.cells[(neighbor_y * width + neighbor_x) as usize][0];
+            }
+        }
+        count
+    }
+
+    /// Same as the `update` pass.
+    pub fn step(&mut self) {
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let index = (y * self.size.width + x) as usize;
+                let [alive, heat] = self.cells[index];
+                let neighbors = self.count_neighbors(x, y);
+
+                let is_alive = neighbors == 3 || (alive != 0 && neighbors == 2);
+                let heat = if is_alive {
+                    255
+                } else {
+                    heat.saturating_sub(1)
+                };
+                self.scratch[index] = [is_alive as u32, heat];
+            }
+        }
+        std::mem::swap(&mut self.cells, &mut self.scratch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world of `width` by `height` cells, with the `alive` ones freshly born.
+    fn world_of(width: u32, height: u32, alive: &[(u32, u32)]) -> CpuLifeWorld {
+        let size = SimSize { width, height };
+        let mut cells = vec![[0, 0]; size.num_of_cells()];
+        for (x, y) in alive {
+            cells[(y * width + x) as usize] = [1, 255];
+        }
+        CpuLifeWorld::new(size, cells)
+    }
+
+    fn alive(world: &CpuLifeWorld) -> Vec<(u32, u32)> {
+        let width = world.size().width;
+        (0..world.cells().len() as u32)
+            .filter(|index| world.cells()[*index as usize][0] != 0)
+            .map(|index| (index % width, index / width))
+            .collect()
+    }
+
+    #[test]
+    fn blinkers_oscillate() {
+        let horizontal = [(1, 2), (2, 2), (3, 2)];
+        let mut world = world_of(5, 5, &horizontal);
+
+        world.step();
+        assert_eq!(alive(&world), [(2, 1), (2, 2), (2, 3)]);
+        world.step();
+        assert_eq!(alive(&world), horizontal);
+    }
+
+    #[test]
+    fn neighbours_wrap_around_the_edges() {
+        let mut world = world_of(5, 5, &[(4, 2), (0, 2), (1, 2)]);
+
+        world.step();
+        assert_eq!(alive(&world), [(0, 1), (0, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn dead_cells_cool_down() {
+        let mut world = world_of(5, 5, &[(2, 2)]);
+
+        world.step();
+        assert_eq!(world.cells()[12], [0, 254]);
+        world.step();
+        assert_eq!(world.cells()[12], [0, 253]);
+        // Cells that never lived stay cold
+        assert_eq!(world.cells()[0], [0, 0]);
+    }
+}
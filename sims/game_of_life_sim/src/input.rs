@@ -1,38 +1,41 @@
-use std::sync::Arc;
-
 use bevy::{
+    ecs::system::SystemParam,
     input::{mouse::MouseButtonInput, ButtonState},
     prelude::*,
     render::extract_resource::ExtractResource,
 };
 use bevy_egui::EguiContexts;
-use parking_lot::Mutex;
+use sim_core::brush::{Brush, FLOOD_READBACK};
+use sim_core::clock::SimClock;
+use sim_core::readback::ReadbackEvent;
+use sim_core::stamp::LoadedStamp;
+use sim_core::undo::StrokeHistory;
+
+use crate::SimSize;
 
 #[derive(Debug, Resource, Clone, ExtractResource)]
 pub struct AutomataParams {
-    pub is_paused: bool,
     pub is_drawing: bool,
     pub can_scroll: bool,
     pub use_square_brush: bool,
+    pub brush: Brush,
 
     pub radius: f32,
     pub mouse_pos: Vec2,
     pub prev_mouse_pos: Vec2,
-    pub frame: Arc<Mutex<usize>>,
 }
 
 impl Default for AutomataParams {
     fn default() -> Self {
         Self {
-            is_paused: false,
             can_scroll: true,
             is_drawing: false,
             use_square_brush: true,
+            brush: Brush::default(),
 
             radius: 4.0,
             mouse_pos: Vec2::ZERO,
             prev_mouse_pos: Vec2::ZERO,
-            frame: Arc::new(Mutex::new(0)),
         }
     }
 }
@@ -40,8 +43,52 @@ impl Default for AutomataParams {
 pub struct InputPlugin;
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<AutomataParams>()
-            .add_system(update_input_state);
+        app.add_systems(
+            (
+                update_input_state,
+                finish_flood_fill,
+                update_brush,
+                track_strokes,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Keyboard shortcuts controlling the simulation.
+#[derive(SystemParam)]
+pub struct Shortcuts<'w> {
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    clock: ResMut<'w, SimClock>,
+    strokes: ResMut<'w, StrokeHistory>,
+}
+
+impl Shortcuts<'_> {
+    fn update(&mut self, is_drawing: bool) {
+        let keyboard_input = &self.keyboard_input;
+
+        // Pause the simulation
+        if keyboard_input.just_pressed(KeyCode::Space) {
+            self.clock.is_paused = !self.clock.is_paused;
+        }
+
+        // Advance a single tick while paused
+        if keyboard_input.just_pressed(KeyCode::Period) {
+            self.clock.request_step();
+        }
+
+        // Undo and redo brush strokes, not while painting one
+        let is_control = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+        let is_shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        if is_control && !is_drawing {
+            if keyboard_input.just_pressed(KeyCode::Y)
+                || (is_shift && keyboard_input.just_pressed(KeyCode::Z))
+            {
+                self.strokes.redo();
+            } else if keyboard_input.just_pressed(KeyCode::Z) {
+                self.strokes.undo();
+            }
+        }
     }
 }
 
@@ -49,13 +96,18 @@ pub fn update_input_state(
     mut contexts: EguiContexts,
     window_query: Query<&Window>,
     mut params: ResMut<AutomataParams>,
-    keyboard_input: Res<Input<KeyCode>>,
+    mut shortcuts: Shortcuts,
+    sim_size: Res<SimSize>,
     camera_q: Query<(&Camera, &GlobalTransform)>,
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
 ) {
-    let Ok(primary_window) = window_query.get_single() else { return };
+    let Ok(primary_window) = window_query.get_single() else {
+        return;
+    };
     // get the camera info and transform
-    let Ok((camera, camera_transform)) = camera_q.get_single() else { return };
+    let Ok((camera, camera_transform)) = camera_q.get_single() else {
+        return;
+    };
 
     let ctx = contexts.ctx_mut();
     if ctx.wants_pointer_input()
@@ -78,10 +130,7 @@ pub fn update_input_state(
         }
     }
 
-    // Pause the simulation
-    if keyboard_input.just_pressed(KeyCode::Space) {
-        params.is_paused = !params.is_paused;
-    }
+    shortcuts.update(params.is_drawing);
 
     if let Some(world_position) = primary_window
         .cursor_position()
@@ -90,6 +139,50 @@ pub fn update_input_state(
     {
         params.prev_mouse_pos = params.mouse_pos;
         params.mouse_pos =
-            crate::utils::world_pos_to_canvas_pos(world_position * Vec2::new(1.0, -1.0));
+            crate::utils::world_pos_to_canvas_pos(world_position * Vec2::new(1.0, -1.0), &sim_size);
+    }
+}
+
+/// Stops the flood fill once it has spread over every cell it can.
+pub fn finish_flood_fill(
+    mut params: ResMut<AutomataParams>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag == FLOOD_READBACK {
+            params.brush.finish_flood_fill(&readback.data);
+        }
     }
 }
+
+pub fn update_brush(
+    mut params: ResMut<AutomataParams>,
+    sim_size: Res<SimSize>,
+    stamp: Res<LoadedStamp>,
+) {
+    let (is_drawing, mouse_pos, prev_mouse_pos) =
+        (params.is_drawing, params.mouse_pos, params.prev_mouse_pos);
+    let transform = params.brush.stamp_transform;
+    params.brush.stamp_size = stamp
+        .size()
+        .map(|size| transform.apply_to_size(size).as_vec2());
+    params
+        .brush
+        .update(is_drawing, mouse_pos, prev_mouse_pos, sim_size.as_vec2());
+}
+
+/// Records the cells under each brush stroke, so it can be undone.
+pub fn track_strokes(
+    params: Res<AutomataParams>,
+    sim_size: Res<SimSize>,
+    mut strokes: ResMut<StrokeHistory>,
+) {
+    let (start, end) = params.brush.painted_bounds(sim_size.as_vec2());
+    strokes.track(
+        params.brush.is_painting(),
+        start,
+        end,
+        params.radius,
+        UVec2::new(sim_size.width, sim_size.height),
+    );
+}
@@ -1,21 +1,90 @@
 mod camera;
+pub mod cpu;
 mod input;
 mod pipeline;
+pub mod snapshot;
+pub mod stamp;
 mod ui;
 mod utils;
 
 use bevy::prelude::*;
 use bevy::render::extract_resource::ExtractResourcePlugin;
-use bevy::{app::App, render::renderer::RenderDevice};
+use bevy::{app::App, ecs::system::SystemParam, render::renderer::RenderDevice};
 use input::AutomataParams;
 use pipeline::automata::{GameOfLifeBuffers, GameOfLifeImage};
+use sim_core::clock::SimClockPlugin;
+use sim_core::headless::SimReadyPlugin;
+use sim_core::history::{SimHistory, SimHistoryPlugin};
+use sim_core::inspect::CellInspectorPlugin;
+use sim_core::readback::ReadbackPlugin;
+use sim_core::recording::RecordingPlugin;
+use sim_core::undo::{StrokeHistory, StrokeHistoryPlugin};
 
 const WORKGROUP_SIZE: u32 = 8;
-const SIM_SIZE: (u32, u32) = (1280, 720);
-const NUM_OF_CELLS: usize = (SIM_SIZE.0 * SIM_SIZE.1) as usize;
+const DEFAULT_SIM_SIZE: (u32, u32) = (1280, 720);
 
-pub struct ShaderPlaygroundPlugin;
-impl Plugin for ShaderPlaygroundPlugin {
+/// Size of the simulation in cells. Insert it before adding the plugin to choose the starting size,
+/// changing it afterwards recreates the canvas and buffers with a new random grid.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for SimSize {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIM_SIZE.0, DEFAULT_SIM_SIZE.1)
+    }
+}
+
+impl std::fmt::Display for SimSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl SimSize {
+    /// Sizes offered in the UI.
+    pub const PRESETS: [SimSize; 4] = [
+        SimSize::new(640, 360),
+        SimSize::new(1280, 720),
+        SimSize::new(1920, 1080),
+        SimSize::new(2560, 1440),
+    ];
+
+    /// Rounds the size up to whole workgroups, at least one.
+    pub const fn new(width: u32, height: u32) -> Self {
+        const fn to_workgroups(cells: u32) -> u32 {
+            if cells == 0 {
+                WORKGROUP_SIZE
+            } else {
+                cells.div_ceil(WORKGROUP_SIZE) * WORKGROUP_SIZE
+            }
+        }
+
+        Self {
+            width: to_workgroups(width),
+            height: to_workgroups(height),
+        }
+    }
+
+    pub fn as_vec2(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn num_of_cells(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    /// Workgroups dispatched by the passes running once per cell.
+    pub fn grid(&self) -> (u32, u32) {
+        (self.width / WORKGROUP_SIZE, self.height / WORKGROUP_SIZE)
+    }
+}
+
+/// The simulation without its window, camera and UI, as run by the headless runner.
+pub struct GameOfLifeSimPlugin;
+impl Plugin for GameOfLifeSimPlugin {
     fn build(&self, app: &mut App) {
         app
             // Extract the game of life image resource from the main world into the render world
@@ -23,29 +92,89 @@ impl Plugin for ShaderPlaygroundPlugin {
             .add_plugin(ExtractResourcePlugin::<GameOfLifeImage>::default())
             .add_plugin(ExtractResourcePlugin::<GameOfLifeBuffers>::default())
             .add_plugin(ExtractResourcePlugin::<AutomataParams>::default())
+            .init_resource::<AutomataParams>()
+            .add_plugin(SimClockPlugin)
+            .add_plugin(SimReadyPlugin)
+            .add_plugin(SimHistoryPlugin)
+            .add_plugin(StrokeHistoryPlugin)
+            .add_plugin(ReadbackPlugin)
+            .add_plugin(CellInspectorPlugin)
+            .add_plugin(sim_core::snapshot::SnapshotPlugin)
+            .add_plugin(RecordingPlugin::<GameOfLifeImage>::default())
+            .add_plugin(snapshot::SnapshotPlugin)
+            .add_plugin(sim_core::stamp::StampPlugin {
+                dir: "stamps/game_of_life",
+            })
+            .add_plugin(stamp::StampPlugin)
+            .init_resource::<SimSize>()
+            .add_plugin(pipeline::PipelinesPlugin)
+            .add_system(resize_simulation.run_if(resource_changed::<SimSize>()));
+    }
+}
+
+pub struct ShaderPlaygroundPlugin;
+impl Plugin for ShaderPlaygroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(GameOfLifeSimPlugin)
             .add_plugin(camera::CameraPlugin)
             .add_plugin(input::InputPlugin)
-            .add_plugin(pipeline::PipelinesPlugin)
             .add_plugin(ui::UIPlugin)
             .add_startup_system(setup);
     }
 }
 
-fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, device: Res<RenderDevice>) {
-    let image = utils::create_image(SIM_SIZE.0, SIM_SIZE.1);
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Marks the sprite showing the simulation.
+#[derive(Component)]
+struct GameOfLifeCanvas;
+
+/// The states and strokes recorded to be restored.
+#[derive(SystemParam)]
+struct RecordedChanges<'w> {
+    history: ResMut<'w, SimHistory>,
+    strokes: ResMut<'w, StrokeHistory>,
+}
+
+/// Creates the canvas and buffers of the simulation, and recreates them whenever the [`SimSize`]
+/// changes.
+fn resize_simulation(
+    mut commands: Commands,
+    sim_size: Res<SimSize>,
+    device: Res<RenderDevice>,
+    mut recorded: RecordedChanges,
+    mut images: ResMut<Assets<Image>>,
+    gol_image: Option<Res<GameOfLifeImage>>,
+    mut canvas: Query<(&mut Sprite, &mut Handle<Image>), With<GameOfLifeCanvas>>,
+) {
+    let image = utils::create_image(sim_size.width, sim_size.height);
     let image = images.add(image);
 
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            custom_size: Some(Vec2::new(SIM_SIZE.0 as f32, SIM_SIZE.1 as f32)),
-            ..default()
-        },
-        texture: image.clone(),
-        ..default()
-    });
+    if let Some(gol_image) = gol_image {
+        images.remove(&gol_image.0);
+    }
+
+    if let Ok((mut sprite, mut texture)) = canvas.get_single_mut() {
+        sprite.custom_size = Some(sim_size.as_vec2());
+        *texture = image.clone();
+    } else {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(sim_size.as_vec2()),
+                    ..default()
+                },
+                texture: image.clone(),
+                ..default()
+            },
+            GameOfLifeCanvas,
+        ));
+    }
 
     // We multiply by 2 because we need to store `alive` and `heat` data for each cell.
-    let initial_life_data = vec![0u32; 2 * NUM_OF_CELLS];
+    let initial_life_data = vec![0u32; 2 * sim_size.num_of_cells()];
     let buffers = (0..2)
         .map(|i| {
             utils::create_storage_buffer_with_data(
@@ -58,14 +187,29 @@ fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, device: Res<
 
     let uniform_size_buffer = utils::create_uniform_buffer(
         &device,
-        &[SIM_SIZE.0, SIM_SIZE.1],
+        &[sim_size.width, sim_size.height],
         Some("Simulation Size Uniform"),
     );
 
-    commands.spawn(Camera2dBundle::default());
+    let flood_fill = utils::create_storage_buffer_with_data(
+        &device,
+        &vec![0u32; 3 + sim_size.num_of_cells()],
+        Some("Flood Fill"),
+    );
+
+    // The recorded states and strokes no longer fit the new buffers
+    recorded.history.clear();
+    recorded.history.set_state_size(buffers[0].size());
+    recorded.strokes.clear();
+    recorded
+        .strokes
+        .set_cell_size(buffers[0].size() / sim_size.num_of_cells() as u64);
+
     commands.insert_resource(GameOfLifeImage(image));
     commands.insert_resource(GameOfLifeBuffers {
+        size: *sim_size,
         in_out_buffers: buffers,
         uniform_buffer: uniform_size_buffer,
+        flood_fill,
     });
 }
@@ -1,6 +1,7 @@
 pub mod automata;
 pub mod color;
 pub mod draw;
+pub mod history;
 
 use bevy::{
     asset::load_internal_asset,
@@ -19,19 +20,23 @@ impl Plugin for PipelinesPlugin {
 
         let render_app = app.sub_app_mut(RenderApp);
         render_app
+            .add_plugin(history::AutomataHistoryPlugin)
             .add_plugin(draw::AutomataDrawPipelinePlugin)
             .add_plugin(automata::AutomataPipelinePlugin)
             .add_plugin(color::AutomataColorPipelinePlugin);
 
         let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        let history_id =
+            render_graph.add_node("game_of_life_history", history::AutomataHistoryNode);
         let gol_id = render_graph.add_node("game_of_life", automata::AutomataNode::default());
         let draw_id = render_graph.add_node("game_of_life_draw", draw::AutomataDrawNode::default());
         let color_id =
             render_graph.add_node("game_of_life_color", color::AutomataColorNode::default());
 
         /*
-         * Draw Pipeline => Automata Pipeline => Color Pipeline => Camera Driver
+         * History => Draw Pipeline => Automata Pipeline => Color Pipeline => Camera Driver
          */
+        render_graph.add_node_edge(history_id, draw_id);
         render_graph.add_node_edge(draw_id, gol_id);
         render_graph.add_node_edge(gol_id, color_id);
         render_graph.add_node_edge(color_id, bevy::render::main_graph::node::CAMERA_DRIVER);
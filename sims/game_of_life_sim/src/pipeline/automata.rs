@@ -6,15 +6,23 @@ use bevy::{
 };
 use std::borrow::Cow;
 
-use crate::{input::AutomataParams, NUM_OF_CELLS, SIM_SIZE, WORKGROUP_SIZE};
+use crate::snapshot::LifeWorldUpload;
+use crate::SimSize;
+use sim_core::clock::SimClock;
+use sim_core::headless::SimReady;
 
 #[derive(Resource, Clone, Deref, ExtractResource)]
 pub struct GameOfLifeImage(pub Handle<Image>);
 
 #[derive(Resource, Clone, ExtractResource)]
 pub struct GameOfLifeBuffers {
+    /// Size the buffers were created for.
+    pub size: SimSize,
     pub uniform_buffer: Buffer,
     pub in_out_buffers: Vec<Buffer>,
+    /// The `alive` value the flood fill spreads over, its id and the cells reached this frame,
+    /// followed by the state of each cell in the fill.
+    pub flood_fill: Buffer,
 }
 
 pub struct AutomataPipelinePlugin;
@@ -22,10 +30,29 @@ impl Plugin for AutomataPipelinePlugin {
     fn build(&self, render_app: &mut App) {
         render_app
             .init_resource::<AutomataPipeline>()
+            .add_system(prepare_world_upload.in_set(RenderSet::Prepare))
             .add_system(queue_automata_bind_group.in_set(RenderSet::Queue));
     }
 }
 
+/// Writes a loaded world into both buffers, the first step of the frame reads either of them.
+fn prepare_world_upload(
+    render_queue: Res<RenderQueue>,
+    upload: Res<LifeWorldUpload>,
+    buffers: Res<GameOfLifeBuffers>,
+) {
+    let Some(world) = upload.0.as_ref() else {
+        return;
+    };
+    if world.size != buffers.size {
+        return;
+    }
+
+    for buffer in buffers.in_out_buffers.iter() {
+        render_queue.write_buffer(buffer, 0, bytemuck::cast_slice(&world.cells));
+    }
+}
+
 // ================================== Pipeline ================================== //
 
 #[derive(Resource)]
@@ -62,8 +89,9 @@ impl FromWorld for AutomataPipeline {
                             ty: BindingType::Buffer {
                                 ty: BufferBindingType::Storage { read_only: false },
                                 has_dynamic_offset: false,
+                                // A cell is an `alive` and a `heat` value
                                 min_binding_size: BufferSize::new(
-                                    (2 * NUM_OF_CELLS * std::mem::size_of::<u32>()) as _,
+                                    (2 * std::mem::size_of::<u32>()) as _,
                                 ),
                             },
                         },
@@ -74,8 +102,9 @@ impl FromWorld for AutomataPipeline {
                             ty: BindingType::Buffer {
                                 ty: BufferBindingType::Storage { read_only: false },
                                 has_dynamic_offset: false,
+                                // A cell is an `alive` and a `heat` value
                                 min_binding_size: BufferSize::new(
-                                    (2 * NUM_OF_CELLS * std::mem::size_of::<u32>()) as _,
+                                    (2 * std::mem::size_of::<u32>()) as _,
                                 ),
                             },
                         },
@@ -115,42 +144,38 @@ impl FromWorld for AutomataPipeline {
 
 // ================================== BindGroup ================================== //
 
+/// Bind groups of the update, the one at `step % 2` reads the input of that step.
 #[derive(Resource)]
-pub struct AutomataTextureBindGroup(pub BindGroup);
+pub struct AutomataTextureBindGroup(pub [BindGroup; 2]);
 
 pub fn queue_automata_bind_group(
     mut commands: Commands,
     render_device: Res<RenderDevice>,
 
-    params: Res<AutomataParams>,
     buffers: Res<GameOfLifeBuffers>,
     pipeline: Res<AutomataPipeline>,
 ) {
-    let (buffer_src, buffer_dst) = if *params.frame.lock() % 2 == 0 {
-        (&buffers.in_out_buffers[0], &buffers.in_out_buffers[1])
-    } else {
-        (&buffers.in_out_buffers[1], &buffers.in_out_buffers[0])
-    };
-
-    let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Game of Life Bind Group"),
-        layout: &pipeline.texture_bind_group_layout,
-        entries: &[
-            BindGroupEntry {
-                binding: 0,
-                resource: buffers.uniform_buffer.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 1,
-                resource: buffer_src.as_entire_binding(),
-            },
-            BindGroupEntry {
-                binding: 2,
-                resource: buffer_dst.as_entire_binding(),
-            },
-        ],
+    let bind_groups = [0, 1].map(|src| {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Game of Life Bind Group"),
+            layout: &pipeline.texture_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.in_out_buffers[src].as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffers.in_out_buffers[1 - src].as_entire_binding(),
+                },
+            ],
+        })
     });
-    commands.insert_resource(AutomataTextureBindGroup(bind_group));
+    commands.insert_resource(AutomataTextureBindGroup(bind_groups));
 }
 
 // ================================== Nodes ================================== //
@@ -162,12 +187,15 @@ pub enum AutomataState {
 
 pub struct AutomataNode {
     state: AutomataState,
+    /// Size of the buffers seeded by the init pipeline.
+    size: Option<SimSize>,
 }
 
 impl Default for AutomataNode {
     fn default() -> Self {
         Self {
             state: AutomataState::Loading,
+            size: None,
         }
     }
 }
@@ -191,12 +219,22 @@ impl render_graph::Node for AutomataNode {
                     pipeline_cache.get_compute_pipeline_state(pipeline.update_pipeline)
                 {
                     self.state = AutomataState::Update;
+                    self.size = Some(world.resource::<GameOfLifeBuffers>().size);
                 }
             }
             AutomataState::Update => {
-                let params = world.resource_mut::<AutomataParams>();
-                if !params.is_paused {
-                    *params.frame.lock() += 1;
+                // The buffers were recreated for another size, seed them again unless a loaded
+                // world fills them
+                let size = world.resource::<GameOfLifeBuffers>().size;
+                let is_loaded = world
+                    .resource::<LifeWorldUpload>()
+                    .0
+                    .as_ref()
+                    .is_some_and(|loaded| loaded.size == size);
+                if is_loaded {
+                    self.size = Some(size);
+                } else if self.size != Some(size) {
+                    self.state = AutomataState::Init;
                 }
             }
         }
@@ -208,44 +246,40 @@ impl render_graph::Node for AutomataNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
-        let is_paused = &world.resource::<AutomataParams>().is_paused;
-
-        if !is_paused {
-            let automata_bind_group = &world.resource::<AutomataTextureBindGroup>().0;
-            let pipeline_cache = world.resource::<PipelineCache>();
-            let pipeline = world.resource::<AutomataPipeline>();
-
-            let mut pass = render_context
-                .command_encoder()
-                .begin_compute_pass(&ComputePassDescriptor::default());
-
-            pass.set_bind_group(0, automata_bind_group, &[]);
-
-            // select the pipeline based on the current state
-            match self.state {
-                AutomataState::Loading => {}
-                AutomataState::Init => {
-                    let init_pipeline = pipeline_cache
-                        .get_compute_pipeline(pipeline.init_pipeline)
-                        .unwrap();
-                    pass.set_pipeline(init_pipeline);
-                    pass.dispatch_workgroups(
-                        SIM_SIZE.0 / WORKGROUP_SIZE,
-                        SIM_SIZE.1 / WORKGROUP_SIZE,
-                        1,
-                    );
-                }
-                AutomataState::Update => {
-                    let update_pipeline = pipeline_cache
-                        .get_compute_pipeline(pipeline.update_pipeline)
-                        .unwrap();
-
-                    pass.set_pipeline(update_pipeline);
-                    pass.dispatch_workgroups(
-                        SIM_SIZE.0 / WORKGROUP_SIZE,
-                        SIM_SIZE.1 / WORKGROUP_SIZE,
-                        1,
-                    );
+        let clock = world.resource::<SimClock>();
+        let automata_bind_groups = &world.resource::<AutomataTextureBindGroup>().0;
+        let grid = world.resource::<GameOfLifeBuffers>().size.grid();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<AutomataPipeline>();
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor::default());
+
+        // select the pipeline based on the current state
+        match self.state {
+            AutomataState::Loading => {}
+            AutomataState::Init => {
+                let init_pipeline = pipeline_cache
+                    .get_compute_pipeline(pipeline.init_pipeline)
+                    .unwrap();
+
+                // Seed the buffer the next frame starts from
+                let next_step = clock.next_step() as usize;
+                pass.set_pipeline(init_pipeline);
+                pass.set_bind_group(0, &automata_bind_groups[next_step % 2], &[]);
+                pass.dispatch_workgroups(grid.0, grid.1, 1);
+            }
+            AutomataState::Update => {
+                let update_pipeline = pipeline_cache
+                    .get_compute_pipeline(pipeline.update_pipeline)
+                    .unwrap();
+
+                world.resource::<SimReady>().mark();
+                pass.set_pipeline(update_pipeline);
+                for step in clock.steps() {
+                    pass.set_bind_group(0, &automata_bind_groups[step as usize % 2], &[]);
+                    pass.dispatch_workgroups(grid.0, grid.1, 1);
                 }
             }
         }
@@ -6,9 +6,9 @@ use bevy::{
 };
 use std::borrow::Cow;
 
-use crate::{input::AutomataParams, NUM_OF_CELLS, SIM_SIZE, WORKGROUP_SIZE};
+use sim_core::clock::SimClock;
 
-use super::automata::{AutomataTextureBindGroup, GameOfLifeBuffers, GameOfLifeImage};
+use super::automata::{GameOfLifeBuffers, GameOfLifeImage};
 
 pub struct AutomataColorPipelinePlugin;
 impl Plugin for AutomataColorPipelinePlugin {
@@ -56,8 +56,9 @@ impl FromWorld for AutomataColorPipeline {
                             ty: BindingType::Buffer {
                                 ty: BufferBindingType::Storage { read_only: false },
                                 has_dynamic_offset: false,
+                                // A cell is an `alive` and a `heat` value
                                 min_binding_size: BufferSize::new(
-                                    (2 * NUM_OF_CELLS * std::mem::size_of::<u32>()) as _,
+                                    (2 * std::mem::size_of::<u32>()) as _,
                                 ),
                             },
                         },
@@ -104,7 +105,7 @@ pub fn queue_color_bind_group(
     pipeline: Res<AutomataColorPipeline>,
     gpu_images: Res<RenderAssets<Image>>,
     game_of_life_image: Res<GameOfLifeImage>,
-    params: Res<AutomataParams>,
+    clock: Res<SimClock>,
 ) {
     let view = &gpu_images[&game_of_life_image.0];
     let color_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
@@ -117,7 +118,9 @@ pub fn queue_color_bind_group(
             },
             BindGroupEntry {
                 binding: 1,
-                resource: buffers.in_out_buffers[*params.frame.lock() % 2].as_entire_binding(),
+                // The output of the last step of this frame
+                resource: buffers.in_out_buffers[clock.next_step() as usize % 2]
+                    .as_entire_binding(),
             },
             BindGroupEntry {
                 binding: 2,
@@ -170,8 +173,8 @@ impl render_graph::Node for AutomataColorNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
-        let texture_bind_group = &world.resource::<AutomataTextureBindGroup>().0;
         let color_bind_group = &world.resource::<AutomataColorBindGroup>().0;
+        let grid = world.resource::<GameOfLifeBuffers>().size.grid();
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<AutomataColorPipeline>();
 
@@ -179,8 +182,6 @@ impl render_graph::Node for AutomataColorNode {
             .command_encoder()
             .begin_compute_pass(&ComputePassDescriptor::default());
 
-        pass.set_bind_group(0, texture_bind_group, &[]);
-
         // select the pipeline based on the current state
         match self.state {
             AutomataColorState::Loading => {}
@@ -191,11 +192,7 @@ impl render_graph::Node for AutomataColorNode {
 
                 pass.set_pipeline(color_pipeline);
                 pass.set_bind_group(0, color_bind_group, &[]);
-                pass.dispatch_workgroups(
-                    SIM_SIZE.0 / WORKGROUP_SIZE,
-                    SIM_SIZE.1 / WORKGROUP_SIZE,
-                    1,
-                );
+                pass.dispatch_workgroups(grid.0, grid.1, 1);
             }
         }
 
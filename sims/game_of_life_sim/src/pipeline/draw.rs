@@ -4,9 +4,13 @@ use bevy::{
 };
 use std::borrow::Cow;
 
-use crate::{input::AutomataParams, NUM_OF_CELLS, SIM_SIZE, WORKGROUP_SIZE};
+use crate::input::AutomataParams;
+use sim_core::brush::{Brush, BrushTool, FLOOD_PASSES_PER_FRAME, FLOOD_READBACK};
+use sim_core::clock::SimClock;
+use sim_core::readback::GpuReadback;
+use sim_core::stamp::{GpuStamp, LoadedStamp};
 
-use super::automata::{AutomataTextureBindGroup, GameOfLifeBuffers};
+use super::automata::GameOfLifeBuffers;
 
 pub struct AutomataDrawPipelinePlugin;
 impl Plugin for AutomataDrawPipelinePlugin {
@@ -26,15 +30,30 @@ pub struct AutomataPushConstants {
     draw_end: [f32; 2],
     draw_radius: f32,
     draw_square: u32,
+    tool: u32,
+    filled: u32,
+    spray_density: f32,
+    brush_seed: u32,
+    stamp_size: [u32; 2],
+    stamp_transform: u32,
+    padding: u32,
 }
 
 impl AutomataPushConstants {
-    pub fn new(draw_start: Vec2, draw_end: Vec2, draw_radius: f32, draw_square: bool) -> Self {
+    pub fn new(brush: &Brush, draw_radius: f32, draw_square: bool, stamp_size: UVec2) -> Self {
+        let (draw_start, draw_end) = brush.segment();
         Self {
             draw_radius,
             draw_end: draw_end.to_array(),
             draw_square: draw_square as u32,
             draw_start: draw_start.to_array(),
+            tool: brush.tool as u32,
+            filled: brush.filled as u32,
+            spray_density: brush.spray_density,
+            brush_seed: brush.seed(),
+            stamp_size: stamp_size.to_array(),
+            stamp_transform: brush.stamp_transform.bits(),
+            padding: 0,
         }
     }
 }
@@ -44,6 +63,9 @@ impl AutomataPushConstants {
 #[derive(Resource)]
 pub struct AutomataDrawPipeline {
     draw_pipeline: CachedComputePipelineId,
+    flood_seed_pipeline: CachedComputePipelineId,
+    flood_fill_pipeline: CachedComputePipelineId,
+    stamp_pipeline: CachedComputePipelineId,
     draw_bind_group_layout: BindGroupLayout,
 }
 
@@ -76,31 +98,65 @@ impl FromWorld for AutomataDrawPipeline {
                             ty: BindingType::Buffer {
                                 ty: BufferBindingType::Storage { read_only: false },
                                 has_dynamic_offset: false,
+                                // A cell is an `alive` and a `heat` value
                                 min_binding_size: BufferSize::new(
-                                    (2 * NUM_OF_CELLS * std::mem::size_of::<u32>()) as _,
+                                    (2 * std::mem::size_of::<u32>()) as _,
+                                ),
+                            },
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 2,
+                            count: None,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: false },
+                                has_dynamic_offset: false,
+                                // The spread `alive` value and the state of a cell
+                                min_binding_size: BufferSize::new(
+                                    (2 * std::mem::size_of::<u32>()) as _,
                                 ),
                             },
                         },
+                        BindGroupLayoutEntry {
+                            binding: 3,
+                            count: None,
+                            visibility: ShaderStages::COMPUTE,
+                            ty: BindingType::Buffer {
+                                ty: BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                // An `alive` value per cell of the stamp
+                                min_binding_size: BufferSize::new(std::mem::size_of::<u32>() as _),
+                            },
+                        },
                     ],
                 });
 
         let brush_shader = world.resource::<AssetServer>().load("shaders/draw.wgsl");
 
-        let draw_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            shader: brush_shader,
-            shader_defs: vec![],
-            entry_point: Cow::from("draw"),
-            layout: vec![draw_bind_group_layout.clone()],
-            label: Some(std::borrow::Cow::Borrowed("Game of Life Draw Pipeline")),
-            push_constant_ranges: [PushConstantRange {
-                stages: ShaderStages::COMPUTE,
-                range: 0..std::mem::size_of::<AutomataPushConstants>() as u32,
-            }]
-            .to_vec(),
-        });
+        let queue_pipeline = |entry_point: &'static str, label: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                shader: brush_shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+                layout: vec![draw_bind_group_layout.clone()],
+                label: Some(Cow::Borrowed(label)),
+                push_constant_ranges: [PushConstantRange {
+                    stages: ShaderStages::COMPUTE,
+                    range: 0..std::mem::size_of::<AutomataPushConstants>() as u32,
+                }]
+                .to_vec(),
+            })
+        };
+        let draw_pipeline = queue_pipeline("draw", "Game of Life Draw Pipeline");
+        let flood_seed_pipeline = queue_pipeline("flood_seed", "Game of Life Flood Seed Pipeline");
+        let flood_fill_pipeline = queue_pipeline("flood_fill", "Game of Life Flood Fill Pipeline");
+        let stamp_pipeline = queue_pipeline("place_stamp", "Game of Life Stamp Pipeline");
 
         AutomataDrawPipeline {
             draw_pipeline,
+            flood_seed_pipeline,
+            flood_fill_pipeline,
+            stamp_pipeline,
             draw_bind_group_layout,
         }
     }
@@ -116,7 +172,8 @@ pub fn queue_draw_bind_group(
     render_device: Res<RenderDevice>,
     buffers: Res<GameOfLifeBuffers>,
     pipeline: Res<AutomataDrawPipeline>,
-    params: Res<AutomataParams>,
+    stamp: Res<GpuStamp>,
+    clock: Res<SimClock>,
 ) {
     let draw_bind_group = render_device.create_bind_group(&BindGroupDescriptor {
         label: Some("Game of Life Draw Bind Group"),
@@ -128,7 +185,16 @@ pub fn queue_draw_bind_group(
             },
             BindGroupEntry {
                 binding: 1,
-                resource: buffers.in_out_buffers[*params.frame.lock() % 2].as_entire_binding(),
+                // The input of the first step of this frame
+                resource: buffers.in_out_buffers[clock.step as usize % 2].as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: buffers.flood_fill.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: stamp.buffer.as_entire_binding(),
             },
         ],
     });
@@ -158,12 +224,23 @@ impl render_graph::Node for AutomataDrawNode {
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<AutomataDrawPipeline>();
 
-        // if the corresponding pipeline has loaded, transition to the next stage
+        // if the corresponding pipelines have loaded, transition to the next stage
         match self.state {
             AutomataDrawState::Loading => {
-                if let CachedPipelineState::Ok(_) =
-                    pipeline_cache.get_compute_pipeline_state(pipeline.draw_pipeline)
-                {
+                let is_loaded = [
+                    pipeline.draw_pipeline,
+                    pipeline.flood_seed_pipeline,
+                    pipeline.flood_fill_pipeline,
+                    pipeline.stamp_pipeline,
+                ]
+                .into_iter()
+                .all(|id| {
+                    matches!(
+                        pipeline_cache.get_compute_pipeline_state(id),
+                        CachedPipelineState::Ok(_)
+                    )
+                });
+                if is_loaded {
                     self.state = AutomataDrawState::Update;
                 }
             }
@@ -179,41 +256,76 @@ impl render_graph::Node for AutomataDrawNode {
     ) -> Result<(), render_graph::NodeRunError> {
         let params = &world.resource::<AutomataParams>();
 
-        if params.is_drawing {
-            let texture_bind_group = &world.resource::<AutomataTextureBindGroup>().0;
+        if params.brush.is_painting() {
+            let buffers = world.resource::<GameOfLifeBuffers>();
             let draw_bind_group = &world.resource::<AutomataDrawBindGroup>().0;
+            let grid = buffers.size.grid();
             let pipeline_cache = world.resource::<PipelineCache>();
             let pipeline = world.resource::<AutomataDrawPipeline>();
 
-            let mut pass = render_context
-                .command_encoder()
-                .begin_compute_pass(&ComputePassDescriptor::default());
-
-            pass.set_bind_group(0, texture_bind_group, &[]);
-
             // select the pipeline based on the current state
             match self.state {
                 AutomataDrawState::Loading => {}
                 AutomataDrawState::Update => {
-                    let draw_pipeline = pipeline_cache
-                        .get_compute_pipeline(pipeline.draw_pipeline)
-                        .unwrap();
-
+                    let get_pipeline = |id| pipeline_cache.get_compute_pipeline(id).unwrap();
+                    let stamp_size = world.resource::<LoadedStamp>().size();
                     let pc = AutomataPushConstants::new(
-                        params.mouse_pos,
-                        params.prev_mouse_pos,
+                        &params.brush,
                         params.radius,
                         params.use_square_brush,
+                        stamp_size.unwrap_or(UVec2::ZERO),
                     );
 
-                    pass.set_pipeline(draw_pipeline);
-                    pass.set_bind_group(0, draw_bind_group, &[]);
-                    pass.set_push_constants(0, bytemuck::cast_slice(&[pc]));
-                    pass.dispatch_workgroups(
-                        SIM_SIZE.0 / WORKGROUP_SIZE,
-                        SIM_SIZE.1 / WORKGROUP_SIZE,
-                        1,
-                    );
+                    // The cells reached by a fill are counted again every frame
+                    let fill = params.brush.flood_fill();
+                    let u32_size = std::mem::size_of::<u32>() as u64;
+                    match fill {
+                        Some(fill) if fill.frame == 0 => render_context
+                            .command_encoder()
+                            .clear_buffer(&buffers.flood_fill, 0, None),
+                        Some(_) => render_context.command_encoder().clear_buffer(
+                            &buffers.flood_fill,
+                            2 * u32_size,
+                            BufferSize::new(u32_size),
+                        ),
+                        None => {}
+                    }
+
+                    let mut pass = render_context
+                        .command_encoder()
+                        .begin_compute_pass(&ComputePassDescriptor::default());
+                    let mut dispatch = |id, workgroups: (u32, u32), count| {
+                        pass.set_pipeline(get_pipeline(id));
+                        pass.set_bind_group(0, draw_bind_group, &[]);
+                        pass.set_push_constants(0, bytemuck::cast_slice(&[pc]));
+                        for _ in 0..count {
+                            pass.dispatch_workgroups(workgroups.0, workgroups.1, 1);
+                        }
+                    };
+
+                    match fill {
+                        // The fill spreads over a few frames
+                        Some(fill) => {
+                            if fill.frame == 0 {
+                                dispatch(pipeline.flood_seed_pipeline, (1, 1), 1);
+                            }
+                            dispatch(pipeline.flood_fill_pipeline, grid, FLOOD_PASSES_PER_FRAME);
+                        }
+                        None if params.brush.tool == BrushTool::Stamp => {
+                            dispatch(pipeline.stamp_pipeline, grid, 1);
+                        }
+                        None => dispatch(pipeline.draw_pipeline, grid, 1),
+                    }
+                    drop(pass);
+
+                    if fill.is_some() {
+                        world.resource::<GpuReadback>().read_buffers(
+                            world.resource::<RenderDevice>(),
+                            render_context.command_encoder(),
+                            FLOOD_READBACK,
+                            &[(&buffers.flood_fill, u32_size..3 * u32_size)],
+                        );
+                    }
                 }
             }
         }
@@ -0,0 +1,119 @@
+use bevy::{
+    prelude::*,
+    render::{render_graph, renderer::*, RenderSet},
+};
+
+use sim_core::clock::SimClock;
+use sim_core::history::{GpuHistory, HistoryCommand, SimHistory};
+use sim_core::inspect::{CellInspector, INSPECT_READBACK};
+use sim_core::readback::{GpuReadback, ReadbackRequests};
+use sim_core::undo::{GpuStrokeHistory, StrokeHistory};
+
+use super::automata::GameOfLifeBuffers;
+use crate::snapshot::SAVE_READBACK;
+use crate::stamp::STAMP_READBACK;
+
+pub struct AutomataHistoryPlugin;
+impl Plugin for AutomataHistoryPlugin {
+    fn build(&self, render_app: &mut App) {
+        render_app.add_systems((prepare_history, prepare_strokes).in_set(RenderSet::Prepare));
+    }
+}
+
+fn prepare_history(
+    render_device: Res<RenderDevice>,
+    history: Res<SimHistory>,
+    buffers: Res<GameOfLifeBuffers>,
+    mut gpu_history: ResMut<GpuHistory>,
+) {
+    gpu_history.prepare(
+        &render_device,
+        &history,
+        &[buffers.in_out_buffers[0].size()],
+    );
+}
+
+fn prepare_strokes(
+    render_device: Res<RenderDevice>,
+    strokes: Res<StrokeHistory>,
+    buffers: Res<GameOfLifeBuffers>,
+    mut gpu_strokes: ResMut<GpuStrokeHistory>,
+) {
+    let cell_size = buffers.in_out_buffers[0].size() / buffers.size.num_of_cells() as u64;
+    gpu_strokes.prepare(
+        &render_device,
+        &strokes,
+        UVec2::new(buffers.size.width, buffers.size.height),
+        &[cell_size],
+    );
+}
+
+// ================================== Nodes ================================== //
+
+/// Records or brings back the input of the first step of the frame, before anything draws on it.
+/// Brush strokes are copied aside or undone on the same input, and saved worlds, stamps and the
+/// inspected cell are read back from it.
+pub struct AutomataHistoryNode;
+
+impl render_graph::Node for AutomataHistoryNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let clock = world.resource::<SimClock>();
+        let buffers = world.resource::<GameOfLifeBuffers>();
+        let gpu_history = world.resource::<GpuHistory>();
+        let input = &buffers.in_out_buffers[clock.step as usize % 2];
+
+        match world.resource::<SimHistory>().command() {
+            Some(HistoryCommand::Capture { slot }) => {
+                gpu_history.capture(render_context.command_encoder(), slot, &[input]);
+            }
+            Some(HistoryCommand::Restore { slot }) => {
+                gpu_history.restore(render_context.command_encoder(), slot, &[input]);
+            }
+            None => {}
+        }
+
+        world.resource::<GpuStrokeHistory>().run(
+            render_context.command_encoder(),
+            world.resource::<StrokeHistory>(),
+            &[input],
+            &[],
+        );
+
+        for tag in [SAVE_READBACK, STAMP_READBACK] {
+            let is_requested = world
+                .get_resource::<ReadbackRequests>()
+                .is_some_and(|requests| requests.is_requested(tag));
+            if is_requested {
+                world.resource::<GpuReadback>().read_buffers(
+                    world.resource::<RenderDevice>(),
+                    render_context.command_encoder(),
+                    tag,
+                    &[(input, 0..input.size())],
+                );
+            }
+        }
+
+        let size = buffers.size;
+        let inspected = world
+            .get_resource::<CellInspector>()
+            .and_then(|inspector| inspector.request())
+            .filter(|cell| cell.x < size.width && cell.y < size.height);
+        if let Some(cell) = inspected {
+            let cell_size = input.size() / size.num_of_cells() as u64;
+            let index = (cell.y * size.width + cell.x) as u64;
+            world.resource::<GpuReadback>().read_buffers(
+                world.resource::<RenderDevice>(),
+                render_context.command_encoder(),
+                INSPECT_READBACK,
+                &[(input, index * cell_size..(index + 1) * cell_size)],
+            );
+        }
+
+        Ok(())
+    }
+}
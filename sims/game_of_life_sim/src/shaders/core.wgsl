@@ -10,9 +10,25 @@ struct PushConstants {
     draw_end: vec2<f32>,
     draw_radius: f32,
     draw_square: u32,
+    tool: u32,
+    filled: u32,
+    spray_density: f32,
+    brush_seed: u32,
+    stamp_size: vec2<u32>,
+    stamp_transform: u32,
+    padding: u32,
 }
 var<push_constant> pc: PushConstants;
 
+const TOOL_FREEHAND: u32 = 0u;
+const TOOL_SPRAY: u32 = 1u;
+const TOOL_LINE: u32 = 2u;
+const TOOL_RECTANGLE: u32 = 3u;
+const TOOL_ELLIPSE: u32 = 4u;
+const TOOL_FLOOD_FILL: u32 = 5u;
+const TOOL_SELECT: u32 = 6u;
+const TOOL_STAMP: u32 = 7u;
+
 fn idx(location: vec2<i32>) -> i32 {
     return location.y * i32(size.x) + location.x;
 }
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        renderer::RenderDevice,
+    },
+};
+use sim_core::clock::SimClock;
+use sim_core::readback::{ReadbackEvent, ReadbackRequests};
+use sim_core::snapshot::{
+    LoadWorld, SaveWorld, SizeLimits, SnapshotError, SnapshotHeader, SnapshotReader, SnapshotWriter,
+};
+
+use crate::SimSize;
+
+/// Start of the snapshots of Game of Life worlds.
+pub const LIFE_SNAPSHOT_MAGIC: [u8; 4] = *b"LIFE";
+/// Tag of the readback of the input buffer of the frame.
+pub const SAVE_READBACK: &str = "life_save";
+
+pub struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingSave>()
+            .init_resource::<LifeWorldUpload>()
+            .add_plugin(ExtractResourcePlugin::<LifeWorldUpload>::default())
+            .add_system(clear_world_upload.in_base_set(CoreSet::First))
+            .add_systems((request_save, finish_save))
+            .add_system(load_world.before(crate::resize_simulation));
+    }
+}
+
+/// The `alive` and `heat` values of every cell.
+#[derive(Debug, Clone)]
+pub struct LifeWorld {
+    pub size: SimSize,
+    pub cells: Vec<[u32; 2]>,
+}
+
+impl LifeWorld {
+    pub fn write(&self, step: u32) -> SnapshotWriter {
+        let size = (self.size.width, self.size.height);
+        let mut writer =
+            SnapshotWriter::new(&SnapshotHeader::new(LIFE_SNAPSHOT_MAGIC, size, 0, step));
+        writer.cells(&self.cells);
+        writer
+    }
+
+    /// Reads a world written by [`LifeWorld::write`], if it fits in `limits`.
+    pub fn read(data: &[u8], limits: &SizeLimits) -> Result<(SnapshotHeader, Self), SnapshotError> {
+        let mut reader = SnapshotReader::new(data, LIFE_SNAPSHOT_MAGIC)?;
+        reader.check_size(limits, std::mem::size_of::<[u32; 2]>() as u64)?;
+        let header = *reader.header();
+
+        // Sizes are whole workgroups, a file with any other size can't fill the buffers
+        let size = SimSize::new(header.width, header.height);
+        if (size.width, size.height) != (header.width, header.height) {
+            return Err(SnapshotError::WrongCellCount {
+                expected: size.num_of_cells(),
+                found: header.num_of_cells(),
+            });
+        }
+
+        let world = Self {
+            size,
+            cells: reader.cells()?,
+        };
+        Ok((header, world))
+    }
+}
+
+// ================================== Save ================================== //
+
+/// Save waiting for the readback of the world.
+#[derive(Resource, Default)]
+struct PendingSave(Option<(SaveWorld, SimSize, u32)>);
+
+fn request_save(
+    clock: Res<SimClock>,
+    sim_size: Res<SimSize>,
+    mut pending: ResMut<PendingSave>,
+    mut requests: ResMut<ReadbackRequests>,
+    mut save_events: EventReader<SaveWorld>,
+) {
+    if let Some(save) = save_events.iter().last() {
+        requests.request(SAVE_READBACK);
+        pending.0 = Some((
+            SaveWorld {
+                path: save.path.clone(),
+            },
+            *sim_size,
+            clock.step,
+        ));
+    }
+}
+
+fn finish_save(mut pending: ResMut<PendingSave>, mut readback_events: EventReader<ReadbackEvent>) {
+    for readback in readback_events.iter() {
+        if readback.tag != SAVE_READBACK {
+            continue;
+        }
+        let Some((save, size, step)) = pending.0.take() else {
+            continue;
+        };
+
+        if readback.data.len() != size.num_of_cells() * std::mem::size_of::<[u32; 2]>() {
+            warn!("The world was resized before it could be saved");
+            continue;
+        }
+
+        let world = LifeWorld {
+            size,
+            cells: bytemuck::pod_collect_to_vec(&readback.data),
+        };
+        match world.write(step).save(&save.path) {
+            Ok(()) => info!("Saved the world to {}", save.path.display()),
+            Err(error) => warn!("Failed to save {}: {}", save.path.display(), error),
+        }
+    }
+}
+
+// ================================== Load ================================== //
+
+/// World written over the buffers of the simulation by the render world, only for the frame it
+/// was loaded in. The buffers it fits aren't seeded.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct LifeWorldUpload(pub Option<Arc<LifeWorld>>);
+
+fn clear_world_upload(mut upload: ResMut<LifeWorldUpload>) {
+    if upload.0.is_some() {
+        upload.0 = None;
+    }
+}
+
+fn load_world(
+    mut clock: ResMut<SimClock>,
+    mut sim_size: ResMut<SimSize>,
+    mut upload: ResMut<LifeWorldUpload>,
+    device: Res<RenderDevice>,
+    mut load_events: EventReader<LoadWorld>,
+) {
+    let Some(load) = load_events.iter().last() else {
+        return;
+    };
+
+    let loaded = std::fs::read(&load.path)
+        .map_err(SnapshotError::from)
+        .and_then(|data| LifeWorld::read(&data, &SizeLimits::of_device(&device)));
+    let (header, world) = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            warn!("Failed to load {}: {}", load.path.display(), error);
+            return;
+        }
+    };
+
+    // The buffers are recreated for the new size in the same frame, before the upload
+    if *sim_size != world.size {
+        *sim_size = world.size;
+    }
+    clock.jump_to(header.step);
+    upload.0 = Some(Arc::new(world));
+    info!("Loaded the world from {}", load.path.display());
+}
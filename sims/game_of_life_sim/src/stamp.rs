@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, render::renderer::RenderDevice};
+use sim_core::readback::{ReadbackEvent, ReadbackRequests};
+use sim_core::snapshot::{
+    SizeLimits, SnapshotError, SnapshotHeader, SnapshotReader, SnapshotWriter,
+};
+use sim_core::stamp::{crop, read_stamp_file, LoadedStamp, SaveStamp, StampCells, StampLibrary};
+
+use crate::SimSize;
+
+/// Start of the stamp files of Game of Life.
+pub const LIFE_STAMP_MAGIC: [u8; 4] = *b"LSTP";
+/// Tag of the readback of the input buffer of the frame, cropped to the saved stamp.
+pub const STAMP_READBACK: &str = "life_stamp";
+
+pub struct StampPlugin;
+impl Plugin for StampPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingStamp>()
+            .add_systems((request_stamp, finish_stamp, load_stamp).chain());
+    }
+}
+
+/// The `alive` and `heat` values of the cells of a pattern.
+#[derive(Debug, Clone)]
+pub struct LifeStamp {
+    pub size: UVec2,
+    pub cells: Vec<[u32; 2]>,
+}
+
+impl LifeStamp {
+    pub fn write(&self) -> SnapshotWriter {
+        let header = SnapshotHeader::new(LIFE_STAMP_MAGIC, self.size.into(), 0, 0);
+        let mut writer = SnapshotWriter::new(&header);
+        writer.cells(&self.cells);
+        writer
+    }
+
+    /// Reads a stamp written by [`LifeStamp::write`], if it fits in `limits`.
+    pub fn read(data: &[u8], limits: &SizeLimits) -> Result<Self, SnapshotError> {
+        let mut reader = SnapshotReader::new(data, LIFE_STAMP_MAGIC)?;
+        // Placed as whether each cell is alive
+        reader.check_size(limits, std::mem::size_of::<u32>() as u64)?;
+        let size = UVec2::new(reader.header().width, reader.header().height);
+        Ok(Self {
+            size,
+            cells: reader.cells()?,
+        })
+    }
+
+    /// Whether each cell is alive, as placed by the stamp tool.
+    fn to_stamp_cells(&self, name: &str) -> StampCells {
+        StampCells {
+            name: name.to_string(),
+            size: self.size,
+            cells: self.cells.iter().map(|[alive, _]| *alive).collect(),
+        }
+    }
+}
+
+// ================================== Save ================================== //
+
+/// Stamp waiting for the readback of the world.
+#[derive(Resource, Default)]
+struct PendingStamp(Option<(SaveStamp, SimSize)>);
+
+fn request_stamp(
+    sim_size: Res<SimSize>,
+    mut pending: ResMut<PendingStamp>,
+    mut requests: ResMut<ReadbackRequests>,
+    mut save_events: EventReader<SaveStamp>,
+) {
+    if let Some(save) = save_events.iter().last() {
+        requests.request(STAMP_READBACK);
+        pending.0 = Some((save.clone(), *sim_size));
+    }
+}
+
+fn finish_stamp(
+    mut library: ResMut<StampLibrary>,
+    mut loaded: ResMut<LoadedStamp>,
+    mut pending: ResMut<PendingStamp>,
+    mut readback_events: EventReader<ReadbackEvent>,
+) {
+    for readback in readback_events.iter() {
+        if readback.tag != STAMP_READBACK {
+            continue;
+        }
+        let Some((save, size)) = pending.0.take() else {
+            continue;
+        };
+
+        if readback.data.len() != size.num_of_cells() * std::mem::size_of::<[u32; 2]>() {
+            warn!("The world was resized before the stamp could be saved");
+            continue;
+        }
+
+        let cells: Vec<[u32; 2]> = bytemuck::pod_collect_to_vec(&readback.data);
+        let stamp = LifeStamp {
+            size: save.max - save.min,
+            cells: crop(&cells, size.width, save.min, save.max),
+        };
+        match library.save(&save.name, &stamp.write().finish()) {
+            Ok(path) => {
+                // Loaded again when saved over the selected stamp
+                library.selected = Some(save.name);
+                loaded.0 = None;
+                info!("Saved the stamp to {}", path.display());
+            }
+            Err(error) => warn!("Failed to save the stamp {}: {}", save.name, error),
+        }
+    }
+}
+
+// ================================== Load ================================== //
+
+/// Loads the stamp selected in the library, and unselects the ones that can't be loaded.
+fn load_stamp(
+    device: Res<RenderDevice>,
+    mut library: ResMut<StampLibrary>,
+    mut loaded: ResMut<LoadedStamp>,
+) {
+    if !loaded.is_outdated(&library) {
+        return;
+    }
+    let Some(name) = library.selected.clone() else {
+        loaded.0 = None;
+        return;
+    };
+
+    let limits = SizeLimits::of_device(&device);
+    match read_stamp_file(&library, &name, |data| LifeStamp::read(data, &limits)) {
+        Ok(stamp) => loaded.0 = Some(Arc::new(stamp.to_stamp_cells(&name))),
+        Err(error) => {
+            warn!("Failed to load the stamp {}", error);
+            library.selected = None;
+            loaded.0 = None;
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, Ui},
     EguiContexts, EguiPlugin,
 };
 use bevy_fn_plugin::bevy_plugin;
+use sim_core::brush::brush_ui;
+use sim_core::clock::{clock_ui, SimClock};
+use sim_core::history::{history_ui, SimHistory};
+use sim_core::inspect::{hovered_cell, inspector_tooltip, CellInspector};
+use sim_core::recording::{recording_ui, SimRecording};
+use sim_core::snapshot::{snapshot_ui, LoadWorld, SaveWorld, SnapshotPath};
+use sim_core::stamp::{stamp_ui, SaveStamp, StampLibrary};
 
 use crate::input::AutomataParams;
-use crate::SIM_SIZE;
+use crate::SimSize;
 
 const SPACING: f32 = 10.0;
 const TEXT_SIZE: f32 = 15.0;
@@ -15,7 +23,8 @@ const HEADING_SIZE: f32 = 20.0;
 
 #[bevy_plugin]
 pub fn UIPlugin(app: &mut App) {
-    app.add_plugin(EguiPlugin).add_system(user_interface);
+    app.add_plugin(EguiPlugin)
+        .add_systems((user_interface, stamps_window, cell_tooltip));
 }
 
 /// Give our text a custom size
@@ -36,12 +45,44 @@ fn heading(ui: &mut Ui, text: impl Into<String>) {
     );
 }
 
+/// Playback and size of the simulation.
+#[derive(SystemParam)]
+pub struct SimControls<'w> {
+    clock: ResMut<'w, SimClock>,
+    history: ResMut<'w, SimHistory>,
+    sim_size: ResMut<'w, SimSize>,
+}
+
+/// Saving, loading and recording the world.
+#[derive(SystemParam)]
+pub struct WorldControls<'w> {
+    recording: ResMut<'w, SimRecording>,
+    snapshot_path: ResMut<'w, SnapshotPath>,
+    save_events: EventWriter<'w, SaveWorld>,
+    load_events: EventWriter<'w, LoadWorld>,
+}
+
 /// System to generate user interface with egui
 pub fn user_interface(
     mut contexts: EguiContexts,
     diagnostics: Res<Diagnostics>,
     mut params: ResMut<AutomataParams>,
+    mut inspector: ResMut<CellInspector>,
+    sim: SimControls,
+    world: WorldControls,
 ) {
+    let SimControls {
+        mut clock,
+        mut history,
+        mut sim_size,
+    } = sim;
+    let WorldControls {
+        mut recording,
+        mut snapshot_path,
+        mut save_events,
+        mut load_events,
+    } = world;
+
     egui::Window::new("Automata")
         .constrain(true)
         .fixed_pos(egui::pos2(10.0, 10.0))
@@ -57,23 +98,93 @@ pub fn user_interface(
                 }
             }
 
-            sized_text(ui, format!("Grid size: ({},{})", SIM_SIZE.0, SIM_SIZE.1));
+            sized_text(ui, format!("Grid size: {}", *sim_size));
 
             sized_text(
                 ui,
-                (if params.is_paused {
-                    "Paused"
-                } else {
-                    "Playing"
-                })
-                .to_string(),
+                (if clock.is_paused { "Paused" } else { "Playing" }).to_string(),
             );
 
             ui.add_space(SPACING);
             heading(ui, "Settings");
             ui.add_space(SPACING);
 
+            clock_ui(ui, &mut clock);
+            history_ui(ui, &mut history, &mut clock);
+            ui.add_space(SPACING);
+
+            brush_ui(ui, &mut params.brush);
             ui.checkbox(&mut params.use_square_brush, "Square Brush");
             ui.add(egui::Slider::new(&mut params.radius, 0.5..=200.0).text("Brush Size"));
+            ui.checkbox(&mut inspector.is_enabled, "Inspect Cells")
+                .on_hover_text("Show the state of the cell under the mouse");
+
+            // Only touch the size when picking another one, changing it restarts the simulation
+            let mut size = *sim_size;
+            egui::ComboBox::from_label("Grid Size")
+                .selected_text(size.to_string())
+                .show_ui(ui, |ui| {
+                    for preset in SimSize::PRESETS {
+                        ui.selectable_value(&mut size, preset, preset.to_string());
+                    }
+                });
+            if size != *sim_size {
+                *sim_size = size;
+            }
+
+            ui.add_space(SPACING);
+            heading(ui, "World");
+            ui.add_space(SPACING);
+
+            snapshot_ui(ui, &mut snapshot_path, &mut save_events, &mut load_events);
+            recording_ui(ui, &mut recording);
         });
 }
+
+/// Stamps saved from the world, placed as patterns with the stamp tool.
+fn stamps_window(
+    mut contexts: EguiContexts,
+    sim_size: Res<SimSize>,
+    mut params: ResMut<AutomataParams>,
+    mut library: ResMut<StampLibrary>,
+    mut save_events: EventWriter<SaveStamp>,
+) {
+    egui::Window::new("Stamps")
+        .constrain(true)
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let size = UVec2::new(sim_size.width, sim_size.height);
+            stamp_ui(ui, &mut library, &mut params.brush, size, &mut save_events);
+        });
+}
+
+/// Tooltip with the state of the cell under the mouse, read back from the GPU.
+fn cell_tooltip(
+    mut contexts: EguiContexts,
+    window_query: Query<&Window>,
+    params: Res<AutomataParams>,
+    sim_size: Res<SimSize>,
+    mut inspector: ResMut<CellInspector>,
+) {
+    let ctx = contexts.ctx_mut();
+    let is_over_canvas = !ctx.is_pointer_over_area()
+        && window_query
+            .get_single()
+            .is_ok_and(|window| window.cursor_position().is_some());
+    let size = UVec2::new(sim_size.width, sim_size.height);
+    inspector.hover(
+        is_over_canvas
+            .then(|| hovered_cell(params.mouse_pos, size))
+            .flatten(),
+    );
+
+    let Some(cell) = inspector.inspected() else {
+        return;
+    };
+    // A cell is an `alive` and a `heat` value
+    let lines = match bytemuck::try_pod_read_unaligned::<[u32; 2]>(&cell.data) {
+        Ok([alive, heat]) => vec![format!("Alive: {}", alive != 0), format!("Heat: {}", heat)],
+        Err(_) => Vec::new(),
+    };
+    inspector_tooltip(ctx, cell, &lines);
+}
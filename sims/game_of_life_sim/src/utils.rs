@@ -18,8 +18,10 @@ pub fn create_image(width: u32, height: u32) -> Image {
         TextureFormat::Rgba8Unorm,
     );
 
-    image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+    image.texture_descriptor.usage = TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::STORAGE_BINDING
+        | TextureUsages::TEXTURE_BINDING;
 
     image.sampler_descriptor = ImageSampler::Descriptor(SamplerDescriptor {
         mag_filter: FilterMode::Nearest,
@@ -42,7 +44,7 @@ pub fn create_storage_buffer_with_data<T: bytemuck::Pod + bytemuck::Zeroable>(
     device.create_buffer_with_data(&BufferInitDescriptor {
         label,
         contents: bytemuck::cast_slice(data),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
     })
 }
 
@@ -60,10 +62,6 @@ pub fn create_uniform_buffer<T: bytemuck::Pod + bytemuck::Zeroable>(
 
 // ================================== Camera ================================== //
 
-pub fn world_pos_to_canvas_pos(world_pos: Vec2) -> Vec2 {
-    world_pos
-        + Vec2::new(
-            crate::SIM_SIZE.0 as f32 / 2.0,
-            crate::SIM_SIZE.1 as f32 / 2.0,
-        )
+pub fn world_pos_to_canvas_pos(world_pos: Vec2, sim_size: &crate::SimSize) -> Vec2 {
+    world_pos + sim_size.as_vec2() / 2.0
 }